@@ -3,15 +3,167 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+/**
+ * Opaque handle of a long-running operation started by `mpc_*` call
+ */
+typedef struct MpcOperation MpcOperation;
+
+/**
+ * Identifies operation started by `wire_*` call, `0` means that operation couldn't be started
+ */
+typedef uint64_t OperationHandle;
+
+/**
+ * Receives json objects reported by an operation
+ *
+ * `json` is nul-terminated string owned by the host, it must be released with
+ * [mpc_free_string]. `finished` is set for the last object of the operation.
+ */
+typedef void (*MpcCallback)(void *user_data, char *json, bool finished);
+
 #ifdef __cplusplus
 extern "C" {
 #endif // __cplusplus
 
-void wire_keygen(int64_t port_,
-                 const unsigned char *secrets_byte_vec,
-                 uintptr_t secrets_byte_len,
-                 const unsigned char *group_byte_vec,
-                 uintptr_t group_byte_len);
+/**
+ * Cancels operation started by `wire_*` call, returns `false` if it's already finished
+ *
+ * Cancelled operation posts error with code 600. Keygen and signing tell other parties that
+ * the session is aborted and post `device_secrets` before that.
+ */
+bool wire_cancel(OperationHandle handle);
+
+/**
+ * Links a new device to Signal account
+ *
+ * Posts `provisioning_url` to be scanned by Signal app, then `device_secrets` of the linked
+ * device once the code is scanned.
+ */
+OperationHandle wire_login(int64_t port_, const char *device_name);
+
+/**
+ * Posts `visit_card` of the device: its Signal address and identity key
+ *
+ * Visit cards of all parties make up the group passed into keygen and signing.
+ */
+OperationHandle wire_me(int64_t port_,
+                        const unsigned char *secrets_byte_vec,
+                        uintptr_t secrets_byte_len);
+
+/**
+ * Runs keygen of `t`-of-`n` key, posts `local_key` share
+ *
+ * `keygen_config` is an optional json-encoded `KeygenConfig`, pass null pointer to generate
+ * plain threshold key. `t` and `n` are validated against the group before keygen is started.
+ *
+ * `session` is a nonce all parties agree on for this run, e.g. generated by the initiator and
+ * handed out along with the group. It must be fresh for every run, retries included: device
+ * refuses to join the same session twice.
+ */
+OperationHandle wire_keygen(int64_t port_,
+                            const unsigned char *secrets_byte_vec,
+                            uintptr_t secrets_byte_len,
+                            const unsigned char *group_byte_vec,
+                            uintptr_t group_byte_len,
+                            uint16_t t,
+                            uint16_t n,
+                            const unsigned char *keygen_config,
+                            uintptr_t keygen_config_len,
+                            const char *session);
+
+/**
+ * Signs hex-encoded `message` with every party of the group, posts `signature`
+ *
+ * `local_key` is the one posted by `wire_keygen`. Signature is an object with `public_key`,
+ * `signature` (`r` and `s` concatenated), `r`, `s` and `recid`. `session` is handled the same
+ * way as in [wire_keygen].
+ */
+OperationHandle wire_sign(int64_t port_,
+                          const unsigned char *secrets_byte_vec,
+                          uintptr_t secrets_byte_len,
+                          const unsigned char *group_byte_vec,
+                          uintptr_t group_byte_len,
+                          const unsigned char *local_key,
+                          uintptr_t local_key_len,
+                          const char *message,
+                          const char *session);
+
+/**
+ * Checks that hex-encoded `signature` of `message` matches hex-encoded `public_key`, posts
+ * whether it's `valid`
+ */
+void wire_verify(int64_t port_, const char *public_key, const char *signature, const char *message);
+
+/**
+ * Releases string passed to [MpcCallback], null pointer is ignored
+ */
+void mpc_free_string(char *json);
+
+/**
+ * Releases handle of the operation, null pointer is ignored
+ *
+ * The operation keeps running, its callback is still called.
+ */
+void mpc_operation_free(MpcOperation *operation);
+
+/**
+ * Cancels the operation, returns `false` if it's already finished
+ *
+ * Cancelled operation reports error with code 600. Keygen and signing tell other parties that
+ * the session is aborted and report `device_secrets` before that.
+ */
+bool mpc_cancel(const MpcOperation *operation);
+
+/**
+ * Links a new device to Signal account, see [wire_login](crate::wire_login)
+ */
+MpcOperation *mpc_login(const char *device_name, MpcCallback callback, void *user_data);
+
+/**
+ * Reports `visit_card` of the device, see [wire_me](crate::wire_me)
+ */
+MpcOperation *mpc_me(const unsigned char *secrets_byte_vec,
+                     uintptr_t secrets_byte_len,
+                     MpcCallback callback,
+                     void *user_data);
+
+/**
+ * Runs keygen of `t`-of-`n` key, see [wire_keygen](crate::wire_keygen)
+ */
+MpcOperation *mpc_keygen(const unsigned char *secrets_byte_vec,
+                         uintptr_t secrets_byte_len,
+                         const unsigned char *group_byte_vec,
+                         uintptr_t group_byte_len,
+                         uint16_t t,
+                         uint16_t n,
+                         const unsigned char *keygen_config,
+                         uintptr_t keygen_config_len,
+                         const char *session,
+                         MpcCallback callback,
+                         void *user_data);
+
+/**
+ * Signs hex-encoded `message`, see [wire_sign](crate::wire_sign)
+ */
+MpcOperation *mpc_sign(const unsigned char *secrets_byte_vec,
+                       uintptr_t secrets_byte_len,
+                       const unsigned char *group_byte_vec,
+                       uintptr_t group_byte_len,
+                       const unsigned char *local_key,
+                       uintptr_t local_key_len,
+                       const char *message,
+                       const char *session,
+                       MpcCallback callback,
+                       void *user_data);
+
+/**
+ * Checks signature of `message`, see [wire_verify](crate::wire_verify)
+ */
+void mpc_verify(const char *public_key,
+                const char *signature,
+                const char *message,
+                MpcCallback callback,
+                void *user_data);
 
 #ifdef __cplusplus
 } // extern "C"
@@ -0,0 +1,210 @@
+//! Plain C ABI for hosts other than Flutter: Swift, Kotlin (via JNI), C++
+//!
+//! `mpc_*` functions mirror `wire_*` ones, but instead of posting json objects (see [crate
+//! level docs](crate)) to Dart port, they pass them to [MpcCallback] along with `user_data`
+//! given by the host.
+//!
+//! ## Memory ownership
+//!
+//! * Pointers passed into `mpc_*` functions are only borrowed until the function returns
+//! * Every json string passed to the callback is owned by the host and must be released with
+//!   [mpc_free_string]
+//! * [MpcOperation] returned by long-running calls is owned by the host and must be released
+//!   with [mpc_operation_free]. Releasing it doesn't cancel the operation
+//! * `user_data` is never dereferenced, the host must keep it valid until the callback is
+//!   called with `finished` set
+//!
+//! ## Threads
+//!
+//! Callback of a long-running call is called from the runtime thread (`mpc-runtime`) started by
+//! the library, never from the thread that started the call, so the host dispatches to its UI
+//! thread itself. The callback must return quickly: all running operations share the thread. The
+//! last call has `finished` set, the callback isn't called after it. If the operation couldn't
+//! be started, null is returned and the callback is never called.
+//!
+//! [mpc_verify] calls the callback (once, with `finished` set) on the calling thread before it
+//! returns.
+
+use std::ffi::CString;
+use std::os::raw::{c_char, c_uchar, c_void};
+use std::ptr;
+
+use serde_json::Value;
+
+use crate::{
+    cancel_operation, read_bytes, read_keygen_config, read_str, start_keygen, start_login,
+    start_me, start_sign, verify, OperationHandle, Reporter,
+};
+
+/// Receives json objects reported by an operation
+///
+/// `json` is nul-terminated string owned by the host, it must be released with
+/// [mpc_free_string]. `finished` is set for the last object of the operation.
+pub type MpcCallback = extern "C" fn(user_data: *mut c_void, json: *mut c_char, finished: bool);
+
+/// Opaque handle of a long-running operation started by `mpc_*` call
+pub struct MpcOperation {
+    handle: OperationHandle,
+}
+
+/// Callback along with its `user_data`
+#[derive(Clone, Copy)]
+pub(crate) struct Callback {
+    callback: MpcCallback,
+    user_data: *mut c_void,
+}
+
+// The host agrees that `user_data` is passed between threads, see module docs
+unsafe impl Send for Callback {}
+unsafe impl Sync for Callback {}
+
+impl Callback {
+    pub(crate) fn call(self, value: &Value, finished: bool) {
+        let json = CString::new(value.to_string()).expect("json never contains nul bytes");
+        (self.callback)(self.user_data, json.into_raw(), finished)
+    }
+}
+
+/// Releases string passed to [MpcCallback], null pointer is ignored
+#[no_mangle]
+pub extern "C" fn mpc_free_string(json: *mut c_char) {
+    if !json.is_null() {
+        drop(unsafe { CString::from_raw(json) });
+    }
+}
+
+/// Releases handle of the operation, null pointer is ignored
+///
+/// The operation keeps running, its callback is still called.
+#[no_mangle]
+pub extern "C" fn mpc_operation_free(operation: *mut MpcOperation) {
+    if !operation.is_null() {
+        drop(unsafe { Box::from_raw(operation) });
+    }
+}
+
+/// Cancels the operation, returns `false` if it's already finished
+///
+/// Cancelled operation reports error with code 600. Keygen and signing tell other parties that
+/// the session is aborted and report `device_secrets` before that.
+#[no_mangle]
+pub extern "C" fn mpc_cancel(operation: *const MpcOperation) -> bool {
+    match unsafe { operation.as_ref() } {
+        Some(operation) => cancel_operation(operation.handle),
+        None => false,
+    }
+}
+
+/// Links a new device to Signal account, see [wire_login](crate::wire_login)
+#[no_mangle]
+pub extern "C" fn mpc_login(
+    device_name: *const c_char,
+    callback: MpcCallback,
+    user_data: *mut c_void,
+) -> *mut MpcOperation {
+    let handle = start_login(
+        reporter(callback, user_data),
+        read_str(device_name, "device name"),
+    );
+    operation(handle)
+}
+
+/// Reports `visit_card` of the device, see [wire_me](crate::wire_me)
+#[no_mangle]
+pub extern "C" fn mpc_me(
+    secrets_byte_vec: *const c_uchar,
+    secrets_byte_len: usize,
+    callback: MpcCallback,
+    user_data: *mut c_void,
+) -> *mut MpcOperation {
+    let handle = start_me(
+        reporter(callback, user_data),
+        read_bytes(secrets_byte_vec, secrets_byte_len),
+    );
+    operation(handle)
+}
+
+/// Runs keygen of `t`-of-`n` key, see [wire_keygen](crate::wire_keygen)
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn mpc_keygen(
+    secrets_byte_vec: *const c_uchar,
+    secrets_byte_len: usize,
+    group_byte_vec: *const c_uchar,
+    group_byte_len: usize,
+    t: u16,
+    n: u16,
+    keygen_config: *const c_uchar,
+    keygen_config_len: usize,
+    session: *const c_char,
+    callback: MpcCallback,
+    user_data: *mut c_void,
+) -> *mut MpcOperation {
+    let handle = start_keygen(
+        reporter(callback, user_data),
+        read_bytes(secrets_byte_vec, secrets_byte_len),
+        read_bytes(group_byte_vec, group_byte_len),
+        t,
+        n,
+        read_keygen_config(keygen_config, keygen_config_len),
+        read_str(session, "session"),
+    );
+    operation(handle)
+}
+
+/// Signs hex-encoded `message`, see [wire_sign](crate::wire_sign)
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn mpc_sign(
+    secrets_byte_vec: *const c_uchar,
+    secrets_byte_len: usize,
+    group_byte_vec: *const c_uchar,
+    group_byte_len: usize,
+    local_key: *const c_uchar,
+    local_key_len: usize,
+    message: *const c_char,
+    session: *const c_char,
+    callback: MpcCallback,
+    user_data: *mut c_void,
+) -> *mut MpcOperation {
+    let handle = start_sign(
+        reporter(callback, user_data),
+        read_bytes(secrets_byte_vec, secrets_byte_len),
+        read_bytes(group_byte_vec, group_byte_len),
+        read_bytes(local_key, local_key_len),
+        read_str(message, "message"),
+        read_str(session, "session"),
+    );
+    operation(handle)
+}
+
+/// Checks signature of `message`, see [wire_verify](crate::wire_verify)
+#[no_mangle]
+pub extern "C" fn mpc_verify(
+    public_key: *const c_char,
+    signature: *const c_char,
+    message: *const c_char,
+    callback: MpcCallback,
+    user_data: *mut c_void,
+) {
+    verify(
+        reporter(callback, user_data),
+        read_str(public_key, "public key"),
+        read_str(signature, "signature"),
+        read_str(message, "message"),
+    )
+}
+
+fn reporter(callback: MpcCallback, user_data: *mut c_void) -> Reporter {
+    Reporter::C(Callback {
+        callback,
+        user_data,
+    })
+}
+
+fn operation(handle: OperationHandle) -> *mut MpcOperation {
+    if handle == 0 {
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(MpcOperation { handle }))
+}
@@ -1,83 +1,108 @@
 #![allow(clippy::missing_safety_doc, clippy::not_unsafe_ptr_arg_deref)]
 
+//! Bindings of [gg18_multi_party_ecdsa] for Flutter, and plain C ABI for other hosts (see
+//! [c_api])
+//!
+//! Every `wire_*` function returns immediately and reports the outcome by posting json
+//! objects to Dart port `port_`. Long-running calls return [OperationHandle] that can be passed
+//! to [wire_cancel]. Every json object has a single key telling what it is:
+//!
+//! * `{"provisioning_url": ".."}` — url to show as QR code (login only)
+//! * `{"device_secrets": ".."}` — serialized device secrets to pass into other calls. Calls
+//!   running a protocol post them every time they change and once more when the call ends, the
+//!   latest ones must be persisted and used in the next call
+//! * `{"progress": {"event": .., ..}}` — progress of running protocol (keygen, signing), see
+//!   [Progress] for the list of events
+//! * `{"visit_card": {..}}`, `{"local_key": {..}}`, `{"signature": {..}}`, `{"valid": ..}` —
+//!   result of the call, nothing is posted after it
+//! * `{"error": {"code": .., "message": .., "culprits": [..]}}` — the call failed, see
+//!   [Error::code] for the list of codes. Nothing is posted after it
+
 use allo_isolate::Isolate;
-use async_ffi::{FfiFuture, FutureExt};
+use futures::channel::mpsc;
+use futures::future::{self, Either, Future};
+use futures::StreamExt;
 use std::os::raw::{c_char, c_uchar};
 use core::slice;
 
-//use gg18_multi_party_ecdsa::common::*;
-//use gg18_multi_party_ecdsa::dkg::*;
-//use gg18_multi_party_ecdsa::signing::*;
-//use gg18_multi_party_ecdsa::utilities::*;
-use gg18_multi_party_ecdsa::cli::*;
-
-use anyhow::{ anyhow, bail, ensure, Context, Result};
-use structopt::StructOpt;
-use std::{fs, vec::Vec};
-use mpc_over_signal::{DeviceStore, Group, ParticipantIdentity, SignalClient};
+use gg18_multi_party_ecdsa::async_runtime::{cancellation, CancelHandle, CancelSignal, Progress};
+use gg18_multi_party_ecdsa::config::{KeygenConfig, MpcConfig};
+use gg18_multi_party_ecdsa::error::{Error, Result};
+use mpc_over_signal::DeviceStore;
 
+use anyhow::{anyhow, Context};
+use serde_json::{json, Value};
+use std::vec::Vec;
 
 use lazy_static::lazy_static;
-use std::{ffi::CStr, io, os::raw};
-use tokio::runtime::{Builder, Runtime};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::{ffi::CStr, io};
+
+pub mod c_api;
+
+/// Identifies operation started by `wire_*` call, `0` means that operation couldn't be started
+pub type OperationHandle = u64;
+
+/// Operation sent to the runtime thread
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
 
 lazy_static! {
-    static ref RUNTIME: io::Result<Runtime> = Builder::new()
-        .threaded_scheduler()
-        .enable_all()
-        .core_threads(4)
-        .thread_name("flutterust")
-        .build();
-}
-
-macro_rules! runtime {
-    () => {
-        match RUNTIME.as_ref() {
-            Ok(rt) => rt,
-            Err(_) => {
-                return ();
-            }
-        }
-    };
+    /// Sends operations to the runtime thread, see [start_runtime]
+    static ref RUNTIME: io::Result<mpsc::UnboundedSender<Job>> = start_runtime();
+    static ref OPERATIONS: Mutex<HashMap<OperationHandle, CancelHandle>> = Default::default();
+}
+
+static NEXT_OPERATION: AtomicU64 = AtomicU64::new(1);
+
+/// Cancels operation started by `wire_*` call, returns `false` if it's already finished
+///
+/// Cancelled operation posts error with code 600. Keygen and signing tell other parties that
+/// the session is aborted and post `device_secrets` before that.
+#[no_mangle]
+pub extern "C" fn wire_cancel(handle: OperationHandle) -> bool {
+    cancel_operation(handle)
 }
 
-#[derive(StructOpt, Debug)]
-pub struct SignalServer {
-    /// Signal Server URL
-    ///
-    /// Allows overriding Signal Server URL in case if you're using own-hosted Signal Server
-    #[structopt(
-        long = "signal-host",
-        default_value = "https://textsecure-service.whispersystems.org/",
-        display_order = 21
-    )]
-    pub host: String,
-    /// Path to PEM-encoded certificate
-    ///
-    /// Sets root of trust in establishing secure connection to server. By default, original
-    /// Signal Server certificate is used.
-    #[structopt(long = "signal-cert", display_order = 22)]
-    pub certificate: Option<String>,
-}
-
-#[derive(StructOpt, Debug)]
-pub struct SecretsFile {
-    /// Path to file containing sensitive information like secrets keys and tokens
-    ///
-    /// Keep it in secret! Information in this file can be used to perform actions on behalf of
-    /// your account: send, receive messages, edit profile info, etc.
-    #[structopt(
-        long = "secrets-file",
-        default_value = "secrets.json",
-        display_order = 20
-    )]
-    pub path: String,
-}
-
-//MPC CONSTANTS
-const THRESHOLD:u16 = 1;
-const PARTIES:u16 = 3;
+/// Links a new device to Signal account
+///
+/// Posts `provisioning_url` to be scanned by Signal app, then `device_secrets` of the linked
+/// device once the code is scanned.
+#[no_mangle]
+pub extern "C" fn wire_login(port_: i64, device_name: *const c_char) -> OperationHandle {
+    start_login(
+        Reporter::Dart(Isolate::new(port_)),
+        read_str(device_name, "device name"),
+    )
+}
+
+/// Posts `visit_card` of the device: its Signal address and identity key
+///
+/// Visit cards of all parties make up the group passed into keygen and signing.
+#[no_mangle]
+pub extern "C" fn wire_me(
+    port_: i64,
+    secrets_byte_vec: *const c_uchar,
+    secrets_byte_len: usize,
+) -> OperationHandle {
+    start_me(
+        Reporter::Dart(Isolate::new(port_)),
+        read_bytes(secrets_byte_vec, secrets_byte_len),
+    )
+}
 
+/// Runs keygen of `t`-of-`n` key, posts `local_key` share
+///
+/// `keygen_config` is an optional json-encoded `KeygenConfig`, pass null pointer to generate
+/// plain threshold key. `t` and `n` are validated against the group before keygen is started.
+///
+/// `session` is a nonce all parties agree on for this run, e.g. generated by the initiator and
+/// handed out along with the group. It must be fresh for every run, retries included: device
+/// refuses to join the same session twice.
+#[allow(clippy::too_many_arguments)]
 #[no_mangle]
 pub extern "C" fn wire_keygen(
     port_: i64,
@@ -85,52 +110,393 @@ pub extern "C" fn wire_keygen(
     secrets_byte_len: usize,
     group_byte_vec: *const c_uchar,
     group_byte_len: usize,
+    t: u16,
+    n: u16,
+    keygen_config: *const c_uchar,
+    keygen_config_len: usize,
+    session: *const c_char,
+) -> OperationHandle {
+    start_keygen(
+        Reporter::Dart(Isolate::new(port_)),
+        read_bytes(secrets_byte_vec, secrets_byte_len),
+        read_bytes(group_byte_vec, group_byte_len),
+        t,
+        n,
+        read_keygen_config(keygen_config, keygen_config_len),
+        read_str(session, "session"),
+    )
+}
+
+/// Signs hex-encoded `message` with every party of the group, posts `signature`
+///
+/// `local_key` is the one posted by `wire_keygen`. Signature is an object with `public_key`,
+/// `signature` (`r` and `s` concatenated), `r`, `s` and `recid`. `session` is handled the same
+/// way as in [wire_keygen].
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub extern "C" fn wire_sign(
+    port_: i64,
+    secrets_byte_vec: *const c_uchar,
+    secrets_byte_len: usize,
+    group_byte_vec: *const c_uchar,
+    group_byte_len: usize,
+    local_key: *const c_uchar,
+    local_key_len: usize,
+    message: *const c_char,
+    session: *const c_char,
+) -> OperationHandle {
+    start_sign(
+        Reporter::Dart(Isolate::new(port_)),
+        read_bytes(secrets_byte_vec, secrets_byte_len),
+        read_bytes(group_byte_vec, group_byte_len),
+        read_bytes(local_key, local_key_len),
+        read_str(message, "message"),
+        read_str(session, "session"),
+    )
+}
+
+/// Checks that hex-encoded `signature` of `message` matches hex-encoded `public_key`, posts
+/// whether it's `valid`
+#[no_mangle]
+pub extern "C" fn wire_verify(
+    port_: i64,
+    public_key: *const c_char,
+    signature: *const c_char,
+    message: *const c_char,
 ) {
-    let rt = runtime!();
-    let secrets_byte_vec = unsafe {slice::from_raw_parts(secrets_byte_vec, secrets_byte_len)};
-    let api_secrets_byte_vec: Vec<u8> = Vec::from(secrets_byte_vec);
-    
-    let group_byte_vec = unsafe {slice::from_raw_parts(group_byte_vec, group_byte_len)};
-    let api_group_byte_vec: Vec<u8> = Vec::from(group_byte_vec);
-
-    rt.spawn(async move {
-
-        let isolate = Isolate::new(port_);
-        isolate.post("Reading device_secrets . . .");
-        /*
-        let device_secrets = DeviceStore::from_byte_vec(api_secrets_byte_vec)
+    verify(
+        Reporter::Dart(Isolate::new(port_)),
+        read_str(public_key, "public key"),
+        read_str(signature, "signature"),
+        read_str(message, "message"),
+    )
+}
+
+/// Where json objects of an operation are delivered: Dart port or C callback
+#[derive(Clone, Copy)]
+enum Reporter {
+    Dart(Isolate),
+    C(c_api::Callback),
+}
+
+impl Reporter {
+    /// Reports intermediate object, more objects will follow
+    fn post(self, value: Value) {
+        match self {
+            Reporter::Dart(isolate) => {
+                isolate.post(value.to_string());
+            }
+            Reporter::C(callback) => callback.call(&value, false),
+        }
+    }
+
+    /// Reports outcome of the operation, nothing is reported after it
+    fn finish(self, result: Result<Value>) {
+        let value = match result {
+            Ok(value) => value,
+            Err(err) => error_json(&err),
+        };
+        match self {
+            Reporter::Dart(isolate) => {
+                isolate.post(value.to_string());
+            }
+            Reporter::C(callback) => callback.call(&value, true),
+        }
+    }
+}
+
+fn start_login(reporter: Reporter, device_name: Result<String>) -> OperationHandle {
+    spawn_operation(move |cancel| async move {
+        let result = match device_name {
+            Ok(device_name) => {
+                let login =
+                    gg18_multi_party_ecdsa::login(&MpcConfig::default(), device_name, |url| {
+                        reporter.post(json!({ "provisioning_url": url }));
+                    });
+                cancellable(cancel, login).await
+            }
+            Err(err) => Err(err),
+        };
+        reporter.finish(result.and_then(device_secrets_json));
+    })
+}
+
+fn start_me(reporter: Reporter, secrets_byte_vec: Vec<u8>) -> OperationHandle {
+    spawn_operation(move |cancel| async move {
+        let me = async {
+            let device_secrets = gg18_multi_party_ecdsa::read_device(secrets_byte_vec).await?;
+            gg18_multi_party_ecdsa::me(&device_secrets).await
+        };
+        let result = cancellable(cancel, me)
             .await
-            .context("read device from file")?;
-        isolate.post("device_secrets read completed. start reading my public key . .");
-        let me = device_secrets.read().await.me();
-        let group = read_group(api_group_byte_vec).context("read group")?;
-        let result: String;
-        let my_ind = match group.party_index(&me.addr) {
-            Some(i) => {isolate.post("my index is found")},
-            None => {isolate.post("group must contain this party too")},
+            .and_then(|me| parse_json(&me))
+            .map(|me| json!({ "visit_card": me }));
+        reporter.finish(result);
+    })
+}
+
+fn start_keygen(
+    reporter: Reporter,
+    secrets_byte_vec: Vec<u8>,
+    group_byte_vec: Vec<u8>,
+    t: u16,
+    n: u16,
+    keygen_config: Result<Option<KeygenConfig>>,
+    session: Result<String>,
+) -> OperationHandle {
+    spawn_operation(move |cancel| async move {
+        let result = match (keygen_config, session) {
+            (Ok(keygen_config), Ok(session)) => {
+                with_device(reporter, secrets_byte_vec, |device_secrets| async move {
+                    gg18_multi_party_ecdsa::keygen(
+                        &MpcConfig::default(),
+                        device_secrets,
+                        group_byte_vec,
+                        session.as_bytes(),
+                        t,
+                        n,
+                        keygen_config,
+                        report_progress(reporter),
+                        cancel,
+                    )
+                    .await
+                })
+                .await
+            }
+            (Err(err), _) | (_, Err(err)) => Err(err),
+        };
+        let result = result
+            .and_then(|local_key| parse_json(&local_key))
+            .map(|local_key| json!({ "local_key": local_key }));
+        reporter.finish(result);
+    })
+}
+
+fn start_sign(
+    reporter: Reporter,
+    secrets_byte_vec: Vec<u8>,
+    group_byte_vec: Vec<u8>,
+    local_key: Vec<u8>,
+    message: Result<String>,
+    session: Result<String>,
+) -> OperationHandle {
+    spawn_operation(move |cancel| async move {
+        let result = match (message, session) {
+            (Ok(message), Ok(session)) => {
+                with_device(reporter, secrets_byte_vec, |device_secrets| async move {
+                    gg18_multi_party_ecdsa::sign(
+                        &MpcConfig::default(),
+                        device_secrets,
+                        group_byte_vec,
+                        session.as_bytes(),
+                        local_key,
+                        &message,
+                        report_progress(reporter),
+                        cancel,
+                    )
+                    .await
+                })
+                .await
+            }
+            (Err(err), _) | (_, Err(err)) => Err(err),
         };
+        let result = result
+            .and_then(|signature| parse_json(&signature))
+            .map(|signature| json!({ "signature": signature }));
+        reporter.finish(result);
+    })
+}
+
+/// Verification is cheap, it's done on the calling thread
+fn verify(
+    reporter: Reporter,
+    public_key: Result<String>,
+    signature: Result<String>,
+    message: Result<String>,
+) {
+    let result = check_signature(public_key, signature, message);
+    reporter.finish(result.map(|valid| json!({ "valid": valid })));
+}
 
-        if group.parties_count() != PARTIES 
-        {
-            isolate.post("group.parties_count() != PARTIES");
+fn check_signature(
+    public_key: Result<String>,
+    signature: Result<String>,
+    message: Result<String>,
+) -> Result<bool> {
+    gg18_multi_party_ecdsa::verify(&public_key?, &signature?, &message?)
+}
+
+/// Spawns `operation` on the runtime, it can be cancelled with returned handle until it's
+/// finished
+fn spawn_operation<F, Fut>(operation: F) -> OperationHandle
+where
+    F: FnOnce(CancelSignal) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let runtime = match RUNTIME.as_ref() {
+        Ok(runtime) => runtime,
+        Err(_) => return 0,
+    };
+    let mut operations = match OPERATIONS.lock() {
+        Ok(operations) => operations,
+        Err(_) => return 0,
+    };
+    let handle = NEXT_OPERATION.fetch_add(1, Ordering::Relaxed);
+    let (cancel_handle, cancel) = cancellation();
+    operations.insert(handle, cancel_handle);
+    drop(operations);
+
+    let operation = operation(cancel);
+    let job: Job = Box::pin(async move {
+        operation.await;
+        if let Ok(mut operations) = OPERATIONS.lock() {
+            operations.remove(&handle);
+        }
+    });
+    if runtime.unbounded_send(job).is_err() {
+        // runtime thread is gone
+        if let Ok(mut operations) = OPERATIONS.lock() {
+            operations.remove(&handle);
         }
-        isolate.post("keygen_run ..");
+        return 0;
+    }
+    handle
+}
 
-        let my_ind = match group.party_index(&me.addr) {
-            Some(i) => i,
-            None => bail!("group must contain this party too"),
-        };
-        let keygen_json = keygen_run(
-            device_secrets.clone(),
-            group,
-            me,
-            my_ind,
-            THRESHOLD,
-            PARTIES,
-        )
-        .await;
-        isolate.post(keygen_json);
-        */
-        //Ok(())
-    }.into_ffi());
-}
\ No newline at end of file
+/// Starts a thread running actix [System](actix_rt::System) that executes operations sent
+/// into returned channel
+///
+/// Signal transport is built on actix: it spawns actors and tasks on the current system, so
+/// operations can't be executed by a plain tokio runtime. Heavy computations of the protocols
+/// are moved to blocking thread pool, the system thread only drives networking.
+fn start_runtime() -> io::Result<mpsc::UnboundedSender<Job>> {
+    let (jobs_tx, mut jobs_rx) = mpsc::unbounded::<Job>();
+    thread::Builder::new()
+        .name("mpc-runtime".to_owned())
+        .spawn(move || {
+            actix_rt::System::new().block_on(async move {
+                while let Some(job) = jobs_rx.next().await {
+                    actix_rt::spawn(job);
+                }
+            })
+        })?;
+    Ok(jobs_tx)
+}
+
+fn cancel_operation(handle: OperationHandle) -> bool {
+    let cancel = OPERATIONS.lock().ok().and_then(|mut ops| ops.remove(&handle));
+    match cancel {
+        Some(cancel) => {
+            cancel.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Drops `call` and returns [Error::Cancelled] once `cancel` is resolved
+///
+/// For calls that don't run a protocol, so there's nobody to tell about cancellation.
+async fn cancellable<T>(cancel: CancelSignal, call: impl Future<Output = Result<T>>) -> Result<T> {
+    futures::pin_mut!(call);
+    match future::select(call, cancel).await {
+        Either::Left((result, _)) => result,
+        Either::Right(((), _)) => Err(Error::Cancelled),
+    }
+}
+
+/// Runs `call` with device secrets parsed from `secrets_byte_vec`, reporting `device_secrets`
+/// every time they change and once `call` is finished
+async fn with_device<F, Fut, T>(
+    reporter: Reporter,
+    secrets_byte_vec: Vec<u8>,
+    call: F,
+) -> Result<T>
+where
+    F: FnOnce(DeviceStore) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let device_secrets = gg18_multi_party_ecdsa::read_device(secrets_byte_vec).await?;
+    let mut changes = device_secrets.subscribe();
+    let call = call(device_secrets.clone());
+    let updates = async {
+        while changes.changed().await.is_ok() {
+            report_device_secrets(reporter, &device_secrets).await;
+        }
+    };
+    futures::pin_mut!(call, updates);
+    let result = match future::select(call, updates).await {
+        Either::Left((result, _)) => result,
+        // never happens: `device_secrets` keeps the channel open
+        Either::Right(((), call)) => call.await,
+    };
+    // the latest changes might have not been reported yet
+    report_device_secrets(reporter, &device_secrets).await;
+    result
+}
+
+fn report_progress(reporter: Reporter) -> impl FnMut(Progress) + Send + 'static {
+    move |progress| reporter.post(json!({ "progress": progress }))
+}
+
+async fn report_device_secrets(reporter: Reporter, device_secrets: &DeviceStore) {
+    let secrets = gg18_multi_party_ecdsa::write_device(device_secrets).await;
+    match secrets.and_then(device_secrets_json) {
+        Ok(value) => reporter.post(value),
+        Err(err) => reporter.post(error_json(&err)),
+    }
+}
+
+fn device_secrets_json(secrets: Vec<u8>) -> Result<Value> {
+    let secrets = String::from_utf8(secrets)
+        .context("device secrets are not utf-8")
+        .map_err(Error::Internal)?;
+    Ok(json!({ "device_secrets": secrets }))
+}
+
+fn read_bytes(ptr: *const c_uchar, len: usize) -> Vec<u8> {
+    if ptr.is_null() {
+        return vec![];
+    }
+    Vec::from(unsafe { slice::from_raw_parts(ptr, len) })
+}
+
+fn read_str(ptr: *const c_char, what: &str) -> Result<String> {
+    if ptr.is_null() {
+        return Err(Error::Config(anyhow!("{} is null", what)));
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map(String::from)
+        .with_context(|| format!("{} is not utf-8", what))
+        .map_err(Error::Config)
+}
+
+/// Null pointer means default config
+fn read_keygen_config(ptr: *const c_uchar, len: usize) -> Result<Option<KeygenConfig>> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    serde_json::from_slice::<KeygenConfig>(&read_bytes(ptr, len))
+        .map(Some)
+        .context("parse keygen config")
+        .map_err(Error::Config)
+}
+
+/// Results of the library are json strings, they're embedded into posted json as objects
+fn parse_json(json: &str) -> Result<Value> {
+    serde_json::from_str(json)
+        .context("parse result")
+        .map_err(Error::Internal)
+}
+
+/// Structured error reported to the host app
+fn error_json(err: &Error) -> Value {
+    json!({
+        "error": {
+            "code": err.code(),
+            "message": err.to_string(),
+            "culprits": err.culprits(),
+        }
+    })
+}
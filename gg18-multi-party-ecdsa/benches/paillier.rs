@@ -0,0 +1,54 @@
+//! Paillier operations of MtA: `paillier` crate against CRT with precomputed parameters
+//!
+//! `without crt` measures [PrecomputedDecryptionKey::without_crt], which signing uses when it's
+//! benchmarked without CRT, to check it costs the same as calling `paillier` crate directly.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use paillier::traits::EncryptWithChosenRandomness;
+use paillier::{Decrypt, Paillier, Randomness, RawCiphertext, RawPlaintext};
+use rand::rngs::OsRng;
+
+use gg18_multi_party_ecdsa::utilities::paillier_crt::PrecomputedDecryptionKey;
+use gg18_multi_party_ecdsa::utilities::sampling::{
+    paillier_keypair, sample_below, sample_unit, PAILLIER_KEY_SIZE,
+};
+
+fn paillier(c: &mut Criterion) {
+    let (ek, dk) = paillier_keypair(&mut OsRng, PAILLIER_KEY_SIZE);
+    let precomputed = PrecomputedDecryptionKey::new(dk.clone()).unwrap();
+    let plain = PrecomputedDecryptionKey::without_crt(dk.clone());
+    let m = sample_below(&mut OsRng, &ek.n);
+    let r = sample_unit(&mut OsRng, &ek.n);
+    let ciphertext = precomputed.encrypt_with_chosen_randomness(&m, &r);
+
+    let mut group = c.benchmark_group("encrypt");
+    group.bench_function("paillier", |b| {
+        b.iter(|| {
+            Paillier::encrypt_with_chosen_randomness(
+                &ek,
+                RawPlaintext::from(&m),
+                &Randomness::from(r.clone()),
+            )
+        })
+    });
+    group.bench_function("without crt", |b| {
+        b.iter(|| plain.encrypt_with_chosen_randomness(&m, &r))
+    });
+    group.bench_function("crt", |b| {
+        b.iter(|| precomputed.encrypt_with_chosen_randomness(&m, &r))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("decrypt");
+    group.bench_function("paillier", |b| {
+        b.iter(|| -> RawPlaintext {
+            Paillier::decrypt(&dk, &RawCiphertext::from(&ciphertext))
+        })
+    });
+    group.bench_function("without crt", |b| b.iter(|| plain.decrypt(&ciphertext)));
+    group.bench_function("crt", |b| b.iter(|| precomputed.decrypt(&ciphertext)));
+    group.finish();
+}
+
+criterion_group!(benches, paillier);
+criterion_main!(benches);
@@ -0,0 +1,94 @@
+//! Simulated signing between `n` parties on a single thread
+//!
+//! Compare `cargo bench --bench simulation` against
+//! `cargo bench --bench simulation --features parallel` to see the speedup of per-counterparty
+//! proofs. Keys are generated once per `n` and aren't included in measurements. Before
+//! measuring, time spent in every round of a single run is printed.
+//!
+//! Every `n` is measured twice: `crt` signs with [PrecomputedDecryptionKey] as stored keys do,
+//! `paillier` with the same keys [without CRT](PrecomputedDecryptionKey::without_crt), so
+//! Paillier operations fall back to `paillier` crate.
+
+use std::convert::TryFrom;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use curv::arithmetic::traits::Converter;
+use curv::BigInt;
+use rand::rngs::OsRng;
+use round_based::dev::Simulation;
+
+use gg18_multi_party_ecdsa::common::party_i::LocalKeyShare;
+use gg18_multi_party_ecdsa::dkg::keygen::Keygen;
+use gg18_multi_party_ecdsa::signing::sign::OfflineStage;
+use gg18_multi_party_ecdsa::utilities::paillier_crt::PrecomputedDecryptionKey;
+
+const SESSION_ID: [u8; 32] = [0x42; 32];
+
+fn keygen(n: u16) -> Vec<LocalKeyShare> {
+    let mut simulation = Simulation::new();
+    for i in 1..=n {
+        simulation.add_party(Keygen::new(SESSION_ID, i, n - 1, n, OsRng).unwrap());
+    }
+    simulation
+        .run()
+        .unwrap()
+        .into_iter()
+        .map(LocalKeyShare::from)
+        .collect()
+}
+
+/// Same keys, but Paillier operations of OfflineStage are done by `paillier` crate
+fn without_crt(keys: &[LocalKeyShare]) -> Vec<LocalKeyShare> {
+    keys.iter()
+        .cloned()
+        .map(|mut key| {
+            let dk = (*key.party_keys.dk).clone();
+            key.party_keys.dk = PrecomputedDecryptionKey::without_crt(dk);
+            key
+        })
+        .collect()
+}
+
+fn offline_stage_simulation(keys: &[LocalKeyShare], message: &BigInt) -> Simulation<OfflineStage> {
+    let t = u16::try_from(keys.len() - 1).unwrap();
+    let mut simulation = Simulation::new();
+    for (i, key) in (1..).zip(keys) {
+        simulation.add_party(
+            OfflineStage::new(SESSION_ID, i, t, key.clone(), message.clone(), OsRng).unwrap(),
+        );
+    }
+    simulation
+}
+
+fn offline_stage(keys: &[LocalKeyShare], message: &BigInt) {
+    offline_stage_simulation(keys, message).run().unwrap();
+}
+
+/// Prints how long every round took, to compare Paillier and proof changes round by round
+fn print_round_times(name: &str, keys: &[LocalKeyShare], message: &BigInt) {
+    let mut simulation = offline_stage_simulation(keys, message);
+    simulation.enable_benchmarks(true);
+    simulation.run().unwrap();
+    println!("offline stage ({}), n = {}", name, keys.len());
+    println!("{:#?}", simulation.benchmark_results().unwrap());
+}
+
+fn signing(c: &mut Criterion) {
+    let message = BigInt::from_bytes(b"message to sign");
+    let mut group = c.benchmark_group("offline stage");
+    group.sample_size(10);
+    for &n in &[5u16, 7, 10] {
+        let crt_keys = keygen(n);
+        let paillier_keys = without_crt(&crt_keys);
+        for &(name, keys) in &[("crt", &crt_keys), ("paillier", &paillier_keys)] {
+            print_round_times(name, keys, &message);
+            group.bench_with_input(BenchmarkId::new(name, n), keys, |b, keys| {
+                b.iter(|| offline_stage(keys, &message))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, signing);
+criterion_main!(benches);
@@ -0,0 +1,432 @@
+//! Async driver of protocol state machines
+//!
+//! Same as `round_based::AsyncProtocol`, but [proceed](StateMachine::proceed), where rounds
+//! marked `is_expensive()` generate Paillier keys, encrypt and prove ranges, is executed on
+//! tokio's blocking thread pool. The task driving the transport (Signal websocket and its
+//! keepalives) keeps running while a round is being computed.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::{fmt, mem};
+
+use futures::channel::oneshot;
+use futures::future::{self, Either};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use round_based::{IsCritical, Msg, StateMachine};
+use serde::Serialize;
+use thiserror::Error;
+use tokio::task::{self, JoinError};
+use tokio::time;
+
+use crate::dkg::traits::RoundBlame;
+
+/// Progress of protocol execution, see [AsyncProtocol::on_progress]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Progress {
+    /// Party entered round `round`
+    RoundStarted { round: u16, total_rounds: Option<u16> },
+    /// Party waits for messages of `parties` to complete the round
+    Awaiting { round: u16, parties: Vec<u16> },
+    /// Party started computing the round on the blocking pool
+    Computing { round: u16, total_rounds: Option<u16> },
+    /// Computation is done, party is at round `round` now
+    Computed { round: u16, total_rounds: Option<u16> },
+    /// Message was sent, `sent` messages are sent so far
+    MessageSent { sent: usize },
+    /// Message was received, `received` messages are received so far
+    MessageReceived { received: usize },
+    /// Protocol is finished, successfully or with error returned by [AsyncProtocol::run]
+    Finished { success: bool },
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let round = |f: &mut fmt::Formatter, round: u16, total_rounds: Option<u16>| {
+            match total_rounds {
+                Some(total_rounds) => write!(f, "round {}/{}", round, total_rounds),
+                None => write!(f, "round {}", round),
+            }
+        };
+        match self {
+            Progress::RoundStarted {
+                round: r,
+                total_rounds,
+            } => round(f, *r, *total_rounds),
+            Progress::Awaiting { round: r, parties } => {
+                write!(f, "round {}: waiting for parties {:?}", r, parties)
+            }
+            Progress::Computing {
+                round: r,
+                total_rounds,
+            } => {
+                round(f, *r, *total_rounds)?;
+                write!(f, ": computing")
+            }
+            Progress::Computed {
+                round: r,
+                total_rounds,
+            } => {
+                round(f, *r, *total_rounds)?;
+                write!(f, ": computed")
+            }
+            Progress::MessageSent { sent } => write!(f, "{} messages sent", sent),
+            Progress::MessageReceived { received } => {
+                write!(f, "{} messages received", received)
+            }
+            Progress::Finished { success: true } => write!(f, "finished"),
+            Progress::Finished { success: false } => write!(f, "failed"),
+        }
+    }
+}
+
+pub type ProgressCallback = Box<dyn FnMut(Progress) + Send>;
+
+/// Creates a handle cancelling protocol execution and the signal to pass into
+/// [AsyncProtocol::cancel_on]
+pub fn cancellation() -> (CancelHandle, CancelSignal) {
+    let (tx, rx) = oneshot::channel();
+    (CancelHandle(tx), CancelSignal(Some(rx)))
+}
+
+/// Cancels protocol execution, see [cancellation]
+#[derive(Debug)]
+pub struct CancelHandle(oneshot::Sender<()>);
+
+impl CancelHandle {
+    pub fn cancel(self) {
+        let _ = self.0.send(());
+    }
+}
+
+/// Future resolved once [CancelHandle::cancel] is called
+///
+/// Dropping the handle without calling `cancel` doesn't resolve the signal.
+#[derive(Debug)]
+pub struct CancelSignal(Option<oneshot::Receiver<()>>);
+
+impl CancelSignal {
+    /// Signal that is never resolved
+    pub fn never() -> Self {
+        Self(None)
+    }
+}
+
+impl Future for CancelSignal {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let rx = match &mut self.0 {
+            Some(rx) => rx,
+            None => return Poll::Pending,
+        };
+        match Pin::new(rx).poll(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(()),
+            Poll::Ready(Err(oneshot::Canceled)) => {
+                self.0 = None;
+                Poll::Pending
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Executes protocol in async environment
+pub struct AsyncProtocol<SM, I, O> {
+    state: Option<SM>,
+    incoming: I,
+    outgoing: O,
+    on_progress: Option<ProgressCallback>,
+    cancel: CancelSignal,
+    reported_round: Option<u16>,
+    reported_awaiting: Option<Vec<u16>>,
+    sent: usize,
+    received: usize,
+}
+
+impl<SM, I, O, IErr> AsyncProtocol<SM, I, O>
+where
+    SM: StateMachine + RoundBlame + Send + 'static,
+    SM::Err: Send + 'static,
+    I: Stream<Item = Result<Msg<SM::MessageBody>, IErr>> + Unpin,
+    O: Sink<Msg<SM::MessageBody>> + Unpin,
+{
+    /// Takes initial state of the party, stream of incoming and sink of outgoing messages
+    pub fn new(state: SM, incoming: I, outgoing: O) -> Self {
+        Self {
+            state: Some(state),
+            incoming,
+            outgoing,
+            on_progress: None,
+            cancel: CancelSignal::never(),
+            reported_round: None,
+            reported_awaiting: None,
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    /// Sets a callback that is notified about every [Progress] of the party: rounds it enters,
+    /// parties it waits for, computations, messages and the final result
+    ///
+    /// Callback is called from the task running the protocol, so it must not block.
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(Progress) + Send + 'static,
+    {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Stops the protocol with [Error::Cancelled] once `signal` is resolved
+    ///
+    /// Protocol is stopped at the next await point. A round being computed on the blocking pool
+    /// is computed till the end, but its result is discarded.
+    pub fn cancel_on(mut self, signal: CancelSignal) -> Self {
+        self.cancel = signal;
+        self
+    }
+
+    /// Runs the protocol until it's finished, cancelled or a critical error occurs
+    ///
+    /// Non-critical errors (e.g. a message that the state machine refused) are ignored, as in
+    /// `round_based::AsyncProtocol`.
+    pub async fn run(mut self) -> Result<SM::Output, Error<SM::Err, IErr, O::Error>> {
+        let cancel = mem::replace(&mut self.cancel, CancelSignal::never());
+        let result = {
+            let running = self.run_until_finished();
+            futures::pin_mut!(running);
+            match future::select(running, cancel).await {
+                Either::Left((result, _)) => result,
+                Either::Right(((), _)) => Err(Error::Cancelled),
+            }
+        };
+        self.report(Progress::Finished {
+            success: result.is_ok(),
+        });
+        result
+    }
+
+    async fn run_until_finished(&mut self) -> Result<SM::Output, Error<SM::Err, IErr, O::Error>> {
+        loop {
+            self.send_outgoing().await?;
+            self.report_round()?;
+
+            let state = self.state.as_mut().ok_or(Error::StateGone)?;
+            if let Some(output) = state.pick_output() {
+                return output.map_err(Error::Finish);
+            }
+            if state.wants_to_proceed() {
+                self.proceed().await?;
+            } else {
+                self.handle_incoming().await?;
+            }
+        }
+    }
+
+    async fn send_outgoing(&mut self) -> Result<(), Error<SM::Err, IErr, O::Error>> {
+        let state = self.state.as_mut().ok_or(Error::StateGone)?;
+        let msgs = mem::take(state.message_queue());
+        for msg in msgs {
+            self.outgoing.send(msg).await.map_err(Error::Send)?;
+            self.sent += 1;
+            self.report(Progress::MessageSent { sent: self.sent });
+        }
+        Ok(())
+    }
+
+    /// Reports entering a new round and parties the round is waiting for, if they changed since
+    /// the last report
+    fn report_round(&mut self) -> Result<(), Error<SM::Err, IErr, O::Error>> {
+        let state = self.state.as_ref().ok_or(Error::StateGone)?;
+        let round = state.current_round();
+        let total_rounds = state.total_rounds();
+        let awaiting = if state.is_finished() || state.wants_to_proceed() {
+            None
+        } else {
+            Some(state.round_blame().1)
+        };
+
+        if self.reported_round != Some(round) {
+            self.reported_round = Some(round);
+            self.reported_awaiting = None;
+            self.report(Progress::RoundStarted {
+                round,
+                total_rounds,
+            });
+        }
+        if let Some(parties) = awaiting {
+            if !parties.is_empty() && self.reported_awaiting.as_ref() != Some(&parties) {
+                self.reported_awaiting = Some(parties.clone());
+                self.report(Progress::Awaiting { round, parties });
+            }
+        }
+        Ok(())
+    }
+
+    async fn proceed(&mut self) -> Result<(), Error<SM::Err, IErr, O::Error>> {
+        let mut state = self.state.take().ok_or(Error::StateGone)?;
+        self.report(Progress::Computing {
+            round: state.current_round(),
+            total_rounds: state.total_rounds(),
+        });
+
+        let (state, result) = task::spawn_blocking(move || {
+            let result = state.proceed();
+            (state, result)
+        })
+        .await
+        .map_err(Error::ProceedPanicked)?;
+
+        self.report(Progress::Computed {
+            round: state.current_round(),
+            total_rounds: state.total_rounds(),
+        });
+        self.state = Some(state);
+        match result {
+            Err(err) if err.is_critical() => Err(Error::Proceed(err)),
+            _ => Ok(()),
+        }
+    }
+
+    async fn handle_incoming(&mut self) -> Result<(), Error<SM::Err, IErr, O::Error>> {
+        let state = self.state.as_mut().ok_or(Error::StateGone)?;
+        let msg = match state.round_timeout() {
+            Some(timeout) => match time::timeout(timeout, self.incoming.next()).await {
+                Ok(msg) => msg,
+                Err(_) => return Err(Error::RoundTimeout(state.round_timeout_reached())),
+            },
+            None => self.incoming.next().await,
+        };
+        let msg = match msg {
+            Some(Ok(msg)) => msg,
+            Some(Err(err)) => return Err(Error::Recv(err)),
+            None => return Err(Error::RecvEof),
+        };
+        self.received += 1;
+        let received = self.received;
+        self.report(Progress::MessageReceived { received });
+        let state = self.state.as_mut().ok_or(Error::StateGone)?;
+        match state.handle_incoming(msg) {
+            Err(err) if err.is_critical() => Err(Error::HandleIncoming(err)),
+            _ => Ok(()),
+        }
+    }
+
+    fn report(&mut self, progress: Progress) {
+        if let Some(callback) = &mut self.on_progress {
+            callback(progress)
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error<E, RE, SE> {
+    #[error("receive next message: {0}")]
+    Recv(RE),
+    #[error("incoming stream terminated before protocol is completed")]
+    RecvEof,
+    #[error("send message: {0}")]
+    Send(SE),
+    #[error("handle received message: {0}")]
+    HandleIncoming(E),
+    #[error("round timeout reached: {0}")]
+    RoundTimeout(E),
+    #[error("proceed round: {0}")]
+    Proceed(E),
+    #[error("proceed round panicked: {0}")]
+    ProceedPanicked(JoinError),
+    #[error("protocol finished with error: {0}")]
+    Finish(E),
+    /// State machine was lost after computation panicked
+    #[error("state machine is gone")]
+    StateGone,
+    /// Execution was cancelled, see [AsyncProtocol::cancel_on]
+    #[error("cancelled")]
+    Cancelled,
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    use futures::channel::mpsc;
+    use rand::rngs::OsRng;
+
+    use super::*;
+    use crate::dkg::keygen::{test::SESSION_ID, Keygen};
+
+    #[test]
+    fn keygen_runs_off_the_reactor() {
+        let (tx1, rx1) = mpsc::unbounded();
+        let (tx2, rx2) = mpsc::unbounded();
+        let progress = Arc::new(Mutex::new(vec![]));
+
+        let party1 = {
+            let progress = progress.clone();
+            let keygen = Keygen::new(SESSION_ID, 1, 1, 2, OsRng).unwrap();
+            AsyncProtocol::new(keygen, rx2.map(Ok::<_, Infallible>), tx1)
+                .on_progress(move |p| progress.lock().unwrap().push(p))
+                .run()
+        };
+        let party2 = {
+            let keygen = Keygen::new(SESSION_ID, 2, 1, 2, OsRng).unwrap();
+            AsyncProtocol::new(keygen, rx1.map(Ok::<_, Infallible>), tx2).run()
+        };
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let (key1, key2) = runtime
+            .block_on(futures::future::try_join(party1, party2))
+            .unwrap();
+        assert_eq!(key1.public_key(), key2.public_key());
+
+        let progress = progress.lock().unwrap();
+        assert_eq!(
+            progress[0],
+            Progress::RoundStarted {
+                round: 0,
+                total_rounds: Some(5)
+            }
+        );
+        assert!(progress.contains(&Progress::Computing {
+            round: 0,
+            total_rounds: Some(5)
+        }));
+        assert!(progress.contains(&Progress::Computed {
+            round: 1,
+            total_rounds: Some(5)
+        }));
+        assert!(progress.contains(&Progress::Awaiting {
+            round: 1,
+            parties: vec![2]
+        }));
+        assert!(progress.iter().any(|p| matches!(p, Progress::MessageReceived { .. })));
+        assert_eq!(progress.last(), Some(&Progress::Finished { success: true }));
+    }
+
+    #[test]
+    fn cancelled_protocol_stops() {
+        let (tx1, _rx1) = mpsc::unbounded();
+        // party 2 never answers, protocol would wait forever
+        let (_tx2, rx2) = mpsc::unbounded();
+        let (handle, signal) = cancellation();
+
+        let keygen = Keygen::new(SESSION_ID, 1, 1, 2, OsRng).unwrap();
+        let party1 = AsyncProtocol::new(keygen, rx2.map(Ok::<_, Infallible>), tx1)
+            .cancel_on(signal)
+            .run();
+        handle.cancel();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(party1);
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+}
@@ -0,0 +1,297 @@
+//! Command line arguments of `gg18` binary
+
+use std::fs;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use structopt::StructOpt;
+
+use gg18_multi_party_ecdsa::common::access_structure::AccessStructure;
+use gg18_multi_party_ecdsa::config::{
+    DeviceStoreConfig, GroupError, KeygenConfig, MpcConfig, SignalServerConfig,
+};
+use gg18_multi_party_ecdsa::key_recovery::export::ExportFormat;
+
+#[derive(StructOpt, Debug)]
+/// Demo CLI
+pub struct App {
+    #[structopt(long)]
+    pub debug: bool,
+    #[structopt(flatten)]
+    pub signal_server: SignalServer,
+    #[structopt(flatten)]
+    pub secrets: SecretsFile,
+    #[structopt(subcommand)]
+    pub command: Cmd,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Cmd {
+    #[structopt(display_order = 1)]
+    Login(LoginArgs),
+    #[structopt(display_order = 2)]
+    Me(MeArgs),
+    #[structopt(display_order = 3)]
+    Keygen(KeygenArgs),
+    #[structopt(display_order = 4)]
+    Sign(SignArgs),
+    #[structopt(display_order = 5)]
+    Import(ImportArgs),
+    #[structopt(display_order = 6)]
+    Reconstruct(ReconstructArgs),
+    #[structopt(display_order = 7)]
+    Verify(VerifyArgs),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct SignalServer {
+    /// Signal Server URL
+    ///
+    /// Allows overriding Signal Server URL in case if you're using own-hosted Signal Server
+    #[structopt(
+        long = "signal-host",
+        default_value = "https://textsecure-service.whispersystems.org/",
+        display_order = 21
+    )]
+    pub host: String,
+    /// Path to PEM-encoded certificate
+    ///
+    /// Sets root of trust in establishing secure connection to server. By default, original
+    /// Signal Server certificate is used.
+    #[structopt(long = "signal-cert", display_order = 22)]
+    pub certificate: Option<String>,
+    /// Seconds given to establish connection to Signal Server
+    #[structopt(long = "signal-connect-timeout", default_value = "30", display_order = 23)]
+    pub connect_timeout: u64,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct SecretsFile {
+    /// Path to file containing sensitive information like secrets keys and tokens
+    ///
+    /// Keep it in secret! Information in this file can be used to perform actions on behalf of
+    /// your account: send, receive messages, edit profile info, etc.
+    #[structopt(
+        long = "secrets-file",
+        default_value = "secrets.json",
+        display_order = 20
+    )]
+    pub path: String,
+}
+
+#[derive(StructOpt, Debug)]
+/// Pairs your Signal app account with MPC device that will be used to send and receive MPC messages
+///
+/// You will be prompted to scan QR code that allow us linking with your Signal account. New device
+/// will be displayed in "Linked Devices" in Signal app on your phone and you'll be able to unlink
+/// it at any moment.
+///
+/// Not that after logging in, this app will receive your private identity and profile keys and
+/// will save them in `secrets-file` (see `--secrets-file` option).
+pub struct LoginArgs {
+    /// Device name that will be associated with a new device
+    ///
+    /// It will be displayed in "Linked Devices" in Signal app on your phone.
+    #[structopt(long, default_value = "MPC-over-Signal device", display_order = 1)]
+    pub device_name: String,
+}
+
+#[derive(StructOpt, Debug)]
+/// Prints information about MPC device: account name, device id, public key
+pub struct MeArgs {
+    /// Prints your visit card in json format
+    #[structopt(long)]
+    pub json: bool,
+}
+
+#[derive(StructOpt, Debug)]
+/// Distributed key generation
+pub struct KeygenArgs {
+    /// Threshold value `t`.
+    ///
+    /// `t`+1 parties will be required to perform signing
+    #[structopt(short = "t", long, display_order = 1)]
+    pub threshold: u16,
+    /// Number of parties involved in keygen
+    #[structopt(short = "n", long, display_order = 1)]
+    pub parties: u16,
+    /// Comma-separated weights of parties (in group order) for weighted threshold key
+    ///
+    /// Party of weight `w` holds `w` shares, any set of parties holding `t`+1 shares together
+    /// can sign.
+    #[structopt(long, use_delimiter = true, conflicts_with = "levels", display_order = 1)]
+    pub weights: Option<Vec<u16>>,
+    /// Comma-separated levels of parties (in group order) for hierarchical threshold key
+    ///
+    /// Level `0` is the most senior one. Signing set of `t`+1 parties must contain at least
+    /// `l`+1 parties of levels `0..=l` for every level `l`.
+    #[structopt(long, use_delimiter = true, display_order = 1)]
+    pub levels: Option<Vec<u16>>,
+    /// Path to file containing addresses and public keys of every party of the protocol
+    #[structopt(long, display_order = 2)]
+    pub group: String,
+    /// Nonce of this run, all parties must pass the same one
+    ///
+    /// Every run (retries included) needs a fresh session, e.g. random hex string generated by
+    /// the initiator and handed out along with the group file. Reused session is rejected.
+    #[structopt(long, display_order = 2)]
+    pub session: String,
+    /// Path to file where to save resulting local party key
+    ///
+    /// File must not exist, it's created readable by the owner only
+    #[structopt(short, long, display_order = 3)]
+    pub output: String,
+}
+
+#[derive(StructOpt, Debug)]
+/// Threshold signing
+pub struct SignArgs {
+    /// Path to local secret key file obtained after keygen
+    #[structopt(long, display_order = 1)]
+    pub local_key: String,
+
+    /// Path to file containing addresses and public keys of every party of the signing protocol
+    #[structopt(long, display_order = 2)]
+    pub group: String,
+    /// Nonce of this run, all parties must pass the same one
+    ///
+    /// Every run (retries included) needs a fresh session, e.g. random hex string generated by
+    /// the initiator and handed out along with the group file. Reused session is rejected.
+    #[structopt(long, display_order = 2)]
+    pub session: String,
+
+    /// Message to sign
+    #[structopt(long, parse(from_str), display_order = 3)]
+    pub digits: String,
+}
+
+#[derive(StructOpt, Debug)]
+/// Imports existing secp256k1 private key into threshold key shares
+///
+/// One party of the group (the dealer) provides the private key, every party ends up with a local
+/// key share of the same public key. The dealer should destroy the original private key once import
+/// is completed.
+pub struct ImportArgs {
+    /// Threshold value `t`.
+    ///
+    /// `t`+1 parties will be required to perform signing
+    #[structopt(short = "t", long, display_order = 1)]
+    pub threshold: u16,
+    /// Index of the dealer party in the group
+    #[structopt(long, display_order = 1)]
+    pub dealer: u16,
+    /// Hex-encoded private key being imported, must be provided by the dealer only
+    #[structopt(long, display_order = 2)]
+    pub secret_key: Option<String>,
+    /// Hex-encoded public key of imported wallet
+    ///
+    /// Parties other than dealer should provide it to make sure that the dealer shares the right key.
+    #[structopt(long, display_order = 2)]
+    pub public_key: Option<String>,
+    /// Path to file containing addresses and public keys of every party of the protocol
+    #[structopt(long, display_order = 3)]
+    pub group: String,
+    /// Nonce of this run, all parties must pass the same one
+    ///
+    /// Every run (retries included) needs a fresh session, e.g. random hex string generated by
+    /// the initiator and handed out along with the group file. Reused session is rejected.
+    #[structopt(long, display_order = 3)]
+    pub session: String,
+    /// Path to file where to save resulting local party key
+    ///
+    /// File must not exist, it's created readable by the owner only
+    #[structopt(short, long, display_order = 4)]
+    pub output: String,
+}
+
+#[derive(StructOpt, Debug)]
+/// Emergency offline reconstruction of the full private key
+///
+/// Loads local keys of `t`+1 parties, makes sure that they belong to the same key and interpolates
+/// the private key. For disaster recovery only: run it on an air-gapped machine, as reconstructed
+/// key gives full control over the funds without any other party.
+pub struct ReconstructArgs {
+    /// Paths to local key files of the parties
+    #[structopt(long = "local-key", required = true, min_values = 2, display_order = 1)]
+    pub local_keys: Vec<String>,
+    /// Output format: `hex`, `wif` or `keystore`
+    #[structopt(long, default_value = "keystore", display_order = 2)]
+    pub format: ExportFormat,
+    /// Encode WIF key for Bitcoin testnet
+    #[structopt(long, display_order = 2)]
+    pub testnet: bool,
+    /// Path to file containing password to encrypt keystore with
+    #[structopt(long, required_if("format", "keystore"), display_order = 2)]
+    pub password_file: Option<String>,
+    /// Path to file where to save reconstructed key
+    ///
+    /// File must not exist, it's created readable by the owner only
+    #[structopt(short, long, display_order = 3)]
+    pub output: String,
+    /// Confirms that you do intend to bring the full private key into existence
+    #[structopt(long = "i-understand-key-will-be-exposed", display_order = 4)]
+    pub confirm: bool,
+}
+
+#[derive(StructOpt, Debug)]
+/// Locally verifies that message matches signature
+pub struct VerifyArgs {
+    /// Hex-encoded public key which was used to sign message
+    #[structopt(long)]
+    pub public_key: String,
+    /// Hex-encoded signature, `r` and `s` concatenated (64 bytes)
+    #[structopt(long)]
+    pub signature: String,
+    /// Being verified message, interpreted the same way as in `sign`
+    #[structopt(long)]
+    pub digits: String,
+}
+
+impl Cmd {
+    /// Name of the step reported in json output
+    pub fn step(&self) -> &'static str {
+        match self {
+            Cmd::Login(_) => "login",
+            Cmd::Me(_) => "me",
+            Cmd::Keygen(_) => "keygen",
+            Cmd::Sign(_) => "sign",
+            Cmd::Import(_) => "import",
+            Cmd::Reconstruct(_) => "reconstruct",
+            Cmd::Verify(_) => "verify",
+        }
+    }
+}
+
+impl App {
+    /// Library configuration requested by global options
+    pub fn config(&self) -> Result<MpcConfig> {
+        let root_certificate = self
+            .signal_server
+            .certificate
+            .as_ref()
+            .map(|path| fs::read_to_string(path).context("read signal server certificate"))
+            .transpose()?;
+        Ok(MpcConfig {
+            signal_server: SignalServerConfig {
+                host: self.signal_server.host.clone(),
+                root_certificate,
+                connect_timeout: Duration::from_secs(self.signal_server.connect_timeout),
+                request_timeout: None,
+            },
+            device_store: DeviceStoreConfig {
+                secrets_file: Some(self.secrets.path.clone().into()),
+            },
+        })
+    }
+}
+
+impl KeygenArgs {
+    /// Access structure of the key requested by arguments
+    pub fn access_structure(&self) -> Result<AccessStructure, GroupError> {
+        let config = KeygenConfig {
+            weights: self.weights.clone(),
+            levels: self.levels.clone(),
+        };
+        config.access_structure(self.threshold, self.parties)
+    }
+}
@@ -0,0 +1,369 @@
+//! Command-line client of threshold ECDSA over Signal
+//!
+//! Every step prints a single-line json object to stdout, so the binary can be driven by
+//! scripts. Human-oriented output (QR code for linking the device, progress of the protocol)
+//! goes to stderr.
+//!
+//! Failed step prints `{"step": .., "error": {"code": .., "message": .., "culprits": [..]}}`
+//! (see [Error::code] for the list of codes) and exits with non-zero status. So does `verify`
+//! of a signature that isn't valid, it prints `"valid": false`.
+
+use std::fs;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::process;
+
+use anyhow::{anyhow, Context};
+use futures::channel::oneshot;
+use rand::rngs::OsRng;
+use serde_json::json;
+use structopt::StructOpt;
+
+use curv::elliptic::curves::secp256_k1::Secp256k1;
+use mpc_over_signal::{DeviceStore, Group, ParticipantIdentity};
+
+use gg18_multi_party_ecdsa::async_runtime::{CancelSignal, Progress};
+use gg18_multi_party_ecdsa::cli;
+use gg18_multi_party_ecdsa::common::party_i::LocalKeyShare;
+use gg18_multi_party_ecdsa::config::{self, GroupError, MpcConfig};
+use gg18_multi_party_ecdsa::dkg::rounds::LocalKey;
+use gg18_multi_party_ecdsa::error::{Error, Result};
+use gg18_multi_party_ecdsa::key_recovery::export::Network;
+
+mod args;
+use args::{App, Cmd};
+
+#[actix::main]
+async fn main() {
+    let args: App = StructOpt::from_args();
+    if args.debug {
+        tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+    }
+    let step = args.command.step();
+    if let Err(err) = run(args).await {
+        println!(
+            "{}",
+            json!({
+                "step": step,
+                "error": {
+                    "code": err.code(),
+                    "message": err.to_string(),
+                    "culprits": err.culprits(),
+                },
+            })
+        );
+        process::exit(1);
+    }
+}
+
+async fn run(args: App) -> Result<()> {
+    let config = args.config().map_err(Error::Config)?;
+    let secrets_path = args.secrets.path;
+    match args.command {
+        Cmd::Login(args) => login(&config, &secrets_path, args).await,
+        Cmd::Me(args) => me(&secrets_path, args).await,
+        Cmd::Keygen(args) => keygen(&config, &secrets_path, args).await,
+        Cmd::Sign(args) => sign(&config, &secrets_path, args).await,
+        Cmd::Import(args) => import(&config, &secrets_path, args).await,
+        Cmd::Reconstruct(args) => reconstruct(args),
+        Cmd::Verify(args) => verify(args),
+    }
+}
+
+async fn login(config: &MpcConfig, secrets_path: &str, args: args::LoginArgs) -> Result<()> {
+    let signal_client = config
+        .signal_server
+        .client()
+        .context("construct signal client")
+        .map_err(Error::Config)?;
+    let (provision_url_tx, provision_url) = oneshot::channel();
+    let (device_tx, device) = oneshot::channel();
+    actix::spawn(async move {
+        let device = signal_client
+            .login(&mut OsRng, provision_url_tx, args.device_name)
+            .await;
+        let _ = device_tx.send(device);
+    });
+
+    if let Ok(url) = provision_url.await {
+        let url = url.to_string();
+        eprintln!();
+        eprintln!("To continue, scan following QR code using Signal app on your phone.");
+        eprintln!("On Android: Signal Settings → Linked Devices → '+' Button");
+        eprintln!("On iOS:     Signal Settings → Linked Devices → Link New Device");
+        eprintln!();
+        eprintln!(
+            "{}",
+            qr2term::generate_qr_string(&url)
+                .context("render provisioning QR code")
+                .map_err(Error::Internal)?
+        );
+        print_step(json!({ "step": "provision", "url": url }))?;
+    }
+    // if provisioning failed, actual error is received here
+
+    let device = match device.await {
+        Ok(device) => device
+            .context("signing in failed")
+            .map_err(Error::Transport)?,
+        Err(canceled) => {
+            return Err(Error::Internal(anyhow!(
+                "signing in future was canceled: {}",
+                canceled
+            )))
+        }
+    };
+    let me = device.me();
+    DeviceStore::new(device)
+        .save_no_overwrite(secrets_path)
+        .await
+        .context("save secrets")
+        .map_err(Error::Storage)?;
+
+    print_step(json!({ "step": "login", "me": identity_json(&me) }))
+}
+
+async fn me(secrets_path: &str, args: args::MeArgs) -> Result<()> {
+    let device_secrets = read_device(secrets_path).await?;
+    let me = device_secrets.read().await.me();
+    if args.json {
+        // visit card, other parties put it into their group files
+        let json = serde_json::to_string(&me)
+            .context("serialize")
+            .map_err(Error::Internal)?;
+        println!("{}", json);
+        Ok(())
+    } else {
+        print_step(json!({ "step": "me", "me": identity_json(&me) }))
+    }
+}
+
+async fn keygen(config: &MpcConfig, secrets_path: &str, args: args::KeygenArgs) -> Result<()> {
+    let access_structure = args.access_structure()?;
+    ensure_absent(&args.output)?;
+    let device_secrets = read_device(secrets_path).await?;
+    let me = device_secrets.read().await.me();
+    let group = read_group(&args.group)?;
+    let my_ind = config::check_group(&group, &me, args.parties)?;
+
+    let local_key = cli::keygen_run(
+        config,
+        device_secrets,
+        group,
+        args.session.as_bytes(),
+        me,
+        my_ind,
+        access_structure,
+        None,
+        show_progress("keygen"),
+        CancelSignal::never(),
+    )
+    .await?;
+    write_secret_file(&args.output, local_key.as_bytes())
+        .context("save local key")
+        .map_err(Error::Storage)?;
+
+    let local_key: LocalKey<Secp256k1> = serde_json::from_str(&local_key)
+        .context("parse local key")
+        .map_err(Error::Internal)?;
+    print_step(json!({
+        "step": "keygen",
+        "public_key": hex::encode(local_key.public_key().to_bytes(true).as_ref()),
+        "output": args.output,
+    }))
+}
+
+async fn import(config: &MpcConfig, secrets_path: &str, args: args::ImportArgs) -> Result<()> {
+    ensure_absent(&args.output)?;
+    let device_secrets = read_device(secrets_path).await?;
+    let me = device_secrets.read().await.me();
+    let group = read_group(&args.group)?;
+    let my_ind = group.party_index(&me.addr).ok_or(GroupError::NotInGroup)?;
+
+    let local_key = cli::import_run(
+        config,
+        device_secrets,
+        group,
+        args.session.as_bytes(),
+        me,
+        my_ind,
+        args.threshold,
+        args.dealer,
+        args.secret_key.as_deref(),
+        args.public_key.as_deref(),
+        None,
+        show_progress("import"),
+        CancelSignal::never(),
+    )
+    .await?;
+    write_secret_file(&args.output, local_key.as_bytes())
+        .context("save local key")
+        .map_err(Error::Storage)?;
+
+    let local_key: LocalKey<Secp256k1> = serde_json::from_str(&local_key)
+        .context("parse local key")
+        .map_err(Error::Internal)?;
+    print_step(json!({
+        "step": "import",
+        "public_key": hex::encode(local_key.public_key().to_bytes(true).as_ref()),
+        "output": args.output,
+    }))
+}
+
+async fn sign(config: &MpcConfig, secrets_path: &str, args: args::SignArgs) -> Result<()> {
+    let local_key = fs::read(&args.local_key)
+        .context("read local key, did you run keygen?")
+        .map_err(Error::Storage)?;
+    let local_key: LocalKey<Secp256k1> = serde_json::from_slice(&local_key)
+        .context("parse local key")
+        .map_err(Error::Storage)?;
+    let public_key = local_key.public_key();
+
+    let device_secrets = read_device(secrets_path).await?;
+    let me = device_secrets.read().await.me();
+    let group = read_group(&args.group)?;
+    let my_ind = group.party_index(&me.addr).ok_or(GroupError::NotInGroup)?;
+
+    let signature = cli::sign_run(
+        config,
+        device_secrets,
+        group,
+        args.session.as_bytes(),
+        me,
+        my_ind,
+        LocalKeyShare::from(local_key),
+        cli::message_to_sign(&args.digits),
+        show_progress("sign"),
+        CancelSignal::never(),
+    )
+    .await?;
+
+    let (r, s) = (cli::scalar_hex(&signature.r), cli::scalar_hex(&signature.s));
+    print_step(json!({
+        "step": "sign",
+        "public_key": hex::encode(public_key.to_bytes(true).as_ref()),
+        "signature": format!("{}{}", r, s),
+        "r": r,
+        "s": s,
+        "recid": signature.recid,
+    }))
+}
+
+fn reconstruct(args: args::ReconstructArgs) -> Result<()> {
+    if !args.confirm {
+        return Err(Error::Config(anyhow!(
+            "refusing to reconstruct private key without `--i-understand-key-will-be-exposed` flag"
+        )));
+    }
+    ensure_absent(&args.output)?;
+    let keys = args
+        .local_keys
+        .iter()
+        .map(|path| {
+            let key = fs::read(path).with_context(|| format!("read local key {}", path))?;
+            serde_json::from_slice::<LocalKey<Secp256k1>>(&key)
+                .with_context(|| format!("parse local key {}", path))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(Error::Storage)?;
+    let network = if args.testnet {
+        Network::Testnet
+    } else {
+        Network::Mainnet
+    };
+    let password = args
+        .password_file
+        .as_ref()
+        .map(|path| fs::read_to_string(path).context("read password file"))
+        .transpose()
+        .map_err(Error::Config)?;
+    let password = password
+        .as_ref()
+        .map(|password| password.trim_end_matches('\n').as_bytes());
+
+    let key = cli::reconstruct_run(&keys, args.format, network, password).map_err(Error::Config)?;
+    write_secret_file(&args.output, key.as_bytes())
+        .context("save reconstructed key")
+        .map_err(Error::Storage)?;
+    print_step(json!({ "step": "reconstruct", "output": args.output }))
+}
+
+fn verify(args: args::VerifyArgs) -> Result<()> {
+    let message = cli::message_to_sign(&args.digits);
+    let valid =
+        cli::verify_run(&args.public_key, &args.signature, &message).map_err(Error::Config)?;
+    print_step(json!({ "step": "verify", "valid": valid }))?;
+    if !valid {
+        // scripts checking exit status only must not take invalid signature for a valid one
+        process::exit(1);
+    }
+    Ok(())
+}
+
+async fn read_device(secrets_path: &str) -> Result<DeviceStore> {
+    let secrets = fs::read(secrets_path)
+        .context("read secrets file, did you run login?")
+        .map_err(Error::Storage)?;
+    DeviceStore::from_byte_vec(secrets)
+        .await
+        .context("read device from file")
+        .map_err(Error::Storage)
+}
+
+fn read_group(path: &str) -> Result<Group> {
+    let group = fs::read(path)
+        .context("read group file")
+        .map_err(Error::Config)?;
+    cli::read_group(group)
+        .context("read group")
+        .map_err(Error::Config)
+}
+
+/// Fails if `path` exists, so that the protocol isn't run just to find out that its output
+/// can't be saved
+fn ensure_absent(path: &str) -> Result<()> {
+    if Path::new(path).exists() {
+        return Err(Error::Config(anyhow!("output file {} already exists", path)));
+    }
+    Ok(())
+}
+
+/// Writes secret `content` to a new file accessible by the owner only, existing file is never
+/// overwritten
+fn write_secret_file(path: &str, content: &[u8]) -> anyhow::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.write(true).create_new(true);
+    let mut file = options.open(path).context("create file")?;
+    file.write_all(content).context("write file")?;
+    file.sync_all().context("sync file")
+}
+
+fn identity_json(me: &ParticipantIdentity) -> serde_json::Value {
+    json!({
+        "name": me.addr.name(),
+        "device_id": me.addr.device_id(),
+        "public_key": base64::encode(me.public_key.serialize()),
+    })
+}
+
+/// Renders progress of `protocol` in a single line of stderr, updated in place
+fn show_progress(protocol: &'static str) -> impl FnMut(Progress) + Send + 'static {
+    move |progress| {
+        eprint!("\r\x1b[2K{}: {}", protocol, progress);
+        if let Progress::Finished { .. } = progress {
+            eprintln!();
+        }
+    }
+}
+
+fn print_step(step: serde_json::Value) -> Result<()> {
+    let step = serde_json::to_string(&step)
+        .context("serialize output")
+        .map_err(Error::Internal)?;
+    println!("{}", step);
+    Ok(())
+}
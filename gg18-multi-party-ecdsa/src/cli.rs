@@ -1,288 +1,393 @@
 
-use structopt::StructOpt;
-
-use std::time::Duration;
-
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, ensure, Context};
 
 
 use futures::{StreamExt};
 
-use std::{fs, vec::Vec};
-use mpc_over_signal::{DeviceStore, Group, ParticipantIdentity, SignalClient};
+use std::vec::Vec;
+use mpc_over_signal::{
+    ComputationID, DeviceStore, Group, ParticipantIdentity, SignalClientConnected,
+};
+use rand::rngs::OsRng;
 
+use crate::async_runtime::{self, AsyncProtocol, CancelSignal, Progress};
+use crate::config::{GroupError, MpcConfig};
 use crate::dkg::keygen::Keygen;
+use crate::error::{Error, Result};
+use crate::dkg::pre_params::PreParams;
+use crate::dkg::rounds::LocalKey;
+use crate::key_import::import::KeyImport;
+use crate::key_recovery::export::{self, ExportFormat, Keystore, Network};
+use crate::key_recovery::reconstruct;
 use curv::{
+    arithmetic::traits::*,
+    elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar},
     BigInt,
 };
-use crate::common::party_i::{LocalKeyShare, Params};
+use crate::common::access_structure::AccessStructure;
+use crate::common::party_i::{verify, LocalKeyShare, SignatureRecid};
 use crate::signing::sign::OfflineStage;
 
-#[derive(StructOpt, Debug)]
-/// Demo CLI
-pub struct App {
-    #[structopt(long)]
-    pub debug: bool,
-    #[structopt(subcommand)]
-    pub command: Cmd,
-}
-
-#[derive(StructOpt, Debug)]
-pub enum Cmd {
-    #[structopt(display_order = 1)]
-    Login(LoginArgs),
-    #[structopt(display_order = 2)]
-    Me(MeArgs),
-    #[structopt(display_order = 3)]
-    Keygen(KeygenArgs),
-    #[structopt(display_order = 4)]
-    Sign(SignArgs),
-    //#[structopt(display_order = 5)]
-    //Verify(VerifyArgs),
-}
-
-#[derive(StructOpt, Debug)]
-pub struct SignalServer {
-    /// Signal Server URL
-    ///
-    /// Allows overriding Signal Server URL in case if you're using own-hosted Signal Server
-    #[structopt(
-        long = "signal-host",
-        default_value = "https://textsecure-service.whispersystems.org/",
-        display_order = 21
-    )]
-    pub host: String,
-    /// Path to PEM-encoded certificate
-    ///
-    /// Sets root of trust in establishing secure connection to server. By default, original
-    /// Signal Server certificate is used.
-    #[structopt(long = "signal-cert", display_order = 22)]
-    pub certificate: Option<String>,
-}
-
-#[derive(StructOpt, Debug)]
-pub struct SecretsFile {
-    /// Path to file containing sensitive information like secrets keys and tokens
-    ///
-    /// Keep it in secret! Information in this file can be used to perform actions on behalf of
-    /// your account: send, receive messages, edit profile info, etc.
-    #[structopt(
-        long = "secrets-file",
-        default_value = "secrets.json",
-        display_order = 20
-    )]
-    pub path: String,
+#[allow(clippy::too_many_arguments)]
+pub async fn keygen_run(
+    config: &MpcConfig,
+    device_secrets: DeviceStore,
+    group: Group,
+    session: &[u8],
+    me: ParticipantIdentity,
+    i: u16,
+    access_structure: AccessStructure,
+    pre_params: Option<PreParams>,
+    on_progress: impl FnMut(Progress) + Send + 'static,
+    cancel: CancelSignal,
+) -> Result<String> {
+    device_secrets
+        .write()
+        .await
+        .trust_to(&group)
+        .map_err(|e| GroupError::UntrustedParty(format!("{:#}", e)))?;
+    
+    let mut signal_client = config
+        .signal_server
+        .client()
+        .map_err(Error::Config)?
+        .start_listening_for_incoming_messages(device_secrets.clone())
+        .await
+        .context("connecting to signal api")
+        .map_err(Error::Transport)?;
+    
+    let computation_id = join_session(&device_secrets, &group, b"keygen", session, &[]).await?;
+    let (incoming, outgoing) = signal_client
+        .join_computation(me.addr.clone(), computation_id, group.clone())
+        .await
+        .context("join computation")
+        .map_err(Error::Transport)?;
+    let incoming = incoming.fuse();
+    let initial = Keygen::with_access_structure(computation_id, i, access_structure, OsRng)
+        .context("create initial state")
+        .map_err(Error::Config)?;
+    let initial = match pre_params {
+        Some(pre_params) => initial.with_pre_params(pre_params),
+        None => initial,
+    };
+    let output = AsyncProtocol::new(initial, incoming, outgoing)
+        .on_progress(report_progress("keygen", on_progress))
+        .cancel_on(cancel)
+        .run()
+        .await;
+    abort_if_cancelled(&output, &signal_client, &me, computation_id, &group).await;
+    save_device_secrets(config, device_secrets).await;
+    let output = output?;
+    // save local key shares (together with access structure) into json file
+    let keygen_json = serde_json::to_string(&output)
+        .context("serialize local key")
+        .map_err(Error::Internal)?;
+    
+    Ok(keygen_json)
 }
 
-#[derive(StructOpt, Debug)]
-/// Pairs your Signal app account with MPC device that will be used to send and receive MPC messages
+/// Derives id of computation `protocol` carried out by `group` in `session`
 ///
-/// You will be prompted to scan QR code that allow us linking with your Signal account. New device
-/// will be displayed in "Linked Devices" in Signal app on your phone and you'll be able to unlink
-/// it at any moment.
-///
-/// Not that after logging in, this app will receive your private identity and profile keys and
-/// will save them in `secrets-file` (see `--secrets-file` option).
-pub struct LoginArgs {
-    /// Device name that will be associated with a new device
-    ///
-    /// It will be displayed in "Linked Devices" in Signal app on your phone.
-    #[structopt(long, default_value = "MPC-over-Signal device", display_order = 1)]
-    pub device_name: String,
-}
-
-#[derive(StructOpt, Debug)]
-/// Prints information about MPC device: account name, device id, public key
-pub struct MeArgs {
-    /// Prints your visit card in json format
-    #[structopt(long)]
-    pub json: bool,
+/// `session` is a nonce the parties agree on for this particular run (e.g. the initiator
+/// generates it and hands out along with the group file). Every run needs its own one, a retry
+/// of a failed or cancelled run too: proofs are bound to computation id, and leftover messages
+/// of the previous run would be mixed into the retry otherwise. Session which this device has
+/// already taken part in is rejected. `params` distinguishes computations of the same session.
+async fn join_session(
+    device_secrets: &DeviceStore,
+    group: &Group,
+    protocol: &[u8],
+    session: &[u8],
+    params: &[u8],
+) -> Result<ComputationID> {
+    if session.is_empty() {
+        return Err(Error::Config(anyhow!("session must not be empty")));
+    }
+    let mut label = protocol.to_vec();
+    label.extend_from_slice(&(session.len() as u64).to_be_bytes());
+    label.extend_from_slice(session);
+    label.extend_from_slice(params);
+    let computation_id = group.computation_id(&label);
+    device_secrets
+        .write()
+        .await
+        .join_computation(computation_id)
+        .context("session can't be reused")
+        .map_err(Error::Config)?;
+    Ok(computation_id)
 }
 
-#[derive(StructOpt, Debug)]
-/// Distributed key generation
-pub struct KeygenArgs {
-    /// Threshold value `t`.
-    ///
-    /// `t`+1 parties will be required to perform signing
-    #[structopt(short = "t", long, display_order = 1)]
-    pub threshold: u16,
-    /// Number of parties involved in keygen
-    #[structopt(short = "n", long, display_order = 1)]
-    pub parties: u16,
-    /// Path to file containing addresses and public keys of every party of the protocol
-    #[structopt(long, display_order = 2)]
-    pub group: String,
-    /// Path to file where to save resulting local party key
-    ///
-    /// If file already exist, it will be overwritten
-    #[structopt(short, long, display_order = 3)]
-    pub output: String,
+/// Logs progress of `protocol` before passing it to `on_progress`
+fn report_progress(
+    protocol: &'static str,
+    mut on_progress: impl FnMut(Progress) + Send + 'static,
+) -> impl FnMut(Progress) + Send + 'static {
+    move |progress| {
+        tracing::event!(tracing::Level::DEBUG, protocol, ?progress, "Protocol progress");
+        on_progress(progress)
+    }
 }
 
-#[derive(StructOpt, Debug)]
-/// Threshold signing
-pub struct SignArgs {
-    /// Path to local secret key file obtained after keygen
-    #[structopt(long, display_order = 1)]
-    pub local_key: String,
-
-    /// Path to file containing addresses and public keys of every party of the signing protocol
-    #[structopt(long, display_order = 2)]
-    pub group: String,
-
-    /// Message to sign
-    #[structopt(long, parse(from_str), display_order = 3)]
-    pub digits: String,
+/// Tells other parties that this party left the computation, if it was cancelled
+async fn abort_if_cancelled<T, E, RE, SE>(
+    output: &std::result::Result<T, async_runtime::Error<E, RE, SE>>,
+    signal_client: &SignalClientConnected,
+    me: &ParticipantIdentity,
+    computation_id: ComputationID,
+    group: &Group,
+) {
+    if let Err(async_runtime::Error::Cancelled) = output {
+        if let Err(err) = signal_client
+            .abort_computation(&me.addr, computation_id, group)
+            .await
+        {
+            tracing::event!(tracing::Level::WARN, %err, "Failed to notify parties about abort");
+        }
+    }
 }
 
-type Bytes = Vec<u8>;
-
-#[derive(StructOpt, Debug)]
-/// Locally verifies that message matches signature
-pub struct VerifyArgs {
-    /// Public key which was used to sign message
-    #[structopt(long)]
-    pub public_key: String,
-    /// Signature
-    #[structopt(long)]
-    pub signature: String,
-    /// Being verified message
-    #[structopt(long, parse(from_str))]
-    pub digits: Bytes,
+/// Persists device secrets changed by the protocol, as configured in `config`
+///
+/// Failure is only logged: protocol output must not be lost because of it.
+async fn save_device_secrets(config: &MpcConfig, mut device_secrets: DeviceStore) {
+    if let Err(err) = config.device_store.save(&mut device_secrets).await {
+        tracing::event!(tracing::Level::ERROR, %err, "Failed to save device secrets");
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
-pub async fn keygen_run(
+pub async fn import_run(
+    config: &MpcConfig,
     device_secrets: DeviceStore,
     group: Group,
+    session: &[u8],
     me: ParticipantIdentity,
     i: u16,
     t: u16,
-    n: u16,
+    dealer: u16,
+    secret_key: Option<&str>,
+    public_key: Option<&str>,
+    pre_params: Option<PreParams>,
+    on_progress: impl FnMut(Progress) + Send + 'static,
+    cancel: CancelSignal,
 ) -> Result<String> {
+    let secret = secret_key
+        .map(|hex| {
+            let secret = BigInt::from_hex(hex.trim_start_matches("0x"))
+                .map_err(|_| anyhow!("secret key is not a valid hex"))?;
+            ensure!(
+                secret > BigInt::zero() && &secret < Scalar::<Secp256k1>::group_order(),
+                "secret key is out of range"
+            );
+            Ok(Scalar::<Secp256k1>::from_bigint(&secret))
+        })
+        .transpose()
+        .map_err(Error::Config)?;
+    let expected_public_key = public_key
+        .map(|hex| {
+            let bytes = hex::decode(hex.trim_start_matches("0x")).context("decode public key")?;
+            Point::<Secp256k1>::from_bytes(&bytes).map_err(|e| anyhow!("invalid public key: {}", e))
+        })
+        .transpose()
+        .map_err(Error::Config)?;
+
     device_secrets
         .write()
         .await
         .trust_to(&group)
-        .context("adding trust to the group")?;
-    
-    let mut signal_client = signal_client()
-        .unwrap()
-        .start_listening_for_incoming_messages(device_secrets)
+        .map_err(|e| GroupError::UntrustedParty(format!("{:#}", e)))?;
+
+    let n = group.parties_count();
+    let mut signal_client = config
+        .signal_server
+        .client()
+        .map_err(Error::Config)?
+        .start_listening_for_incoming_messages(device_secrets.clone())
         .await
-        .context("connecting to signal api")?;
-    
+        .context("connecting to signal api")
+        .map_err(Error::Transport)?;
+
+    let computation_id =
+        join_session(&device_secrets, &group, b"import", session, &dealer.to_be_bytes()).await?;
     let (incoming, outgoing) = signal_client
-        .join_computation(me.addr, group)
+        .join_computation(me.addr.clone(), computation_id, group.clone())
         .await
-        .context("join computation")?;
+        .context("join computation")
+        .map_err(Error::Transport)?;
     let incoming = incoming.fuse();
-    let initial = Keygen::new(i, t, n).context("create initial state")?;
-    let output = round_based::AsyncProtocol::new(initial, incoming, outgoing)
+    let initial = KeyImport::new(
+        computation_id,
+        i,
+        t,
+        n,
+        dealer,
+        secret,
+        expected_public_key,
+        OsRng,
+    )
+    .context("create initial state")
+    .map_err(Error::Config)?;
+    let initial = match pre_params {
+        Some(pre_params) => initial.with_pre_params(pre_params),
+        None => initial,
+    };
+    let output = AsyncProtocol::new(initial, incoming, outgoing)
+        .on_progress(report_progress("key import", on_progress))
+        .cancel_on(cancel)
         .run()
-        .await
-        .map_err(|e| anyhow!("execute keygen protocol: {}", e))?;
-    // save local key shares into json file
-    let keygen_json = serde_json::to_string(&(
-        output.keys,
-        output.shared_keys,
-        output.party_num_int,
-        output.vss_vec,
-        output.pail_key_vec,
-        output.y_sum_s,
-        ))
-        .unwrap();
-    
-    Ok(keygen_json)
+        .await;
+    abort_if_cancelled(&output, &signal_client, &me, computation_id, &group).await;
+    save_device_secrets(config, device_secrets).await;
+    let output = output?;
+    // local key share has the same format as one obtained after keygen
+    let import_json = serde_json::to_string(&output)
+        .context("serialize local key")
+        .map_err(Error::Internal)?;
+
+    Ok(import_json)
 }
 
+/// Interpolates the private key from local keys of `t`+1 parties and encodes it in `format`
+///
+/// `network` only matters for WIF format, `password` is required for keystore format.
+pub fn reconstruct_run(
+    keys: &[LocalKey<Secp256k1>],
+    format: ExportFormat,
+    network: Network,
+    password: Option<&[u8]>,
+) -> anyhow::Result<String> {
+    let secret = reconstruct(keys).context("reconstruct private key")?;
+
+    let encoded = match format {
+        ExportFormat::Hex => export::to_hex(&secret),
+        ExportFormat::Wif => export::to_wif(&secret, network),
+        ExportFormat::Keystore => {
+            let password = password.context("password is required for keystore format")?;
+            let keystore = Keystore::encrypt(&secret, password).context("encrypt keystore")?;
+            serde_json::to_string_pretty(&keystore).context("serialize keystore")?
+        }
+    };
+    Ok(encoded)
+}
+
+/// Signs `message_bn` with parties of `group`, returns resulting signature
+///
+/// Every party of `group` takes part in signing, so the group must be authorized by access
+/// structure of the key. Re-signing the same message needs a new `session`, see [join_session].
+#[allow(clippy::too_many_arguments)]
 pub async fn sign_run(
-    signal_client: SignalClient,
+    config: &MpcConfig,
     device_secrets: DeviceStore,
     group: Group,
+    session: &[u8],
     me: ParticipantIdentity,
     my_ind: u16,
     key_share: LocalKeyShare,
     message_bn: BigInt,
-) -> Result<()> {
+    on_progress: impl FnMut(Progress) + Send + 'static,
+    cancel: CancelSignal,
+) -> Result<SignatureRecid> {
+    let t = group.parties_count() - 1;
+    // fail before the session is joined, the key won't become usable on retry
+    if !key_share.has_dlog_setup() {
+        return Err(Error::MissingDLogSetup);
+    }
 
-    //read parameters:
-    let data = fs::read_to_string("params.json")
-        .expect("Unable to read params, make sure config file is present in the same folder ");
-    let params: Params = serde_json::from_str(&data).unwrap();
-    let t = params.threshold.parse::<u16>().unwrap();
+    device_secrets
+        .write()
+        .await
+        .trust_to(&group)
+        .map_err(|e| GroupError::UntrustedParty(format!("{:#}", e)))?;
 
-    let mut signal_client = signal_client
-        .start_listening_for_incoming_messages(device_secrets)
+    let mut signal_client = config
+        .signal_server
+        .client()
+        .map_err(Error::Config)?
+        .start_listening_for_incoming_messages(device_secrets.clone())
         .await
-        .context("connecting to signal api")?;
+        .context("connecting to signal api")
+        .map_err(Error::Transport)?;
+    // signing of another message is a different computation
+    let computation_id =
+        join_session(&device_secrets, &group, b"sign", session, &message_bn.to_bytes()).await?;
     let (incoming, outgoing) = signal_client
-        .join_computation(me.addr, group)
+        .join_computation(me.addr.clone(), computation_id, group.clone())
         .await
-        .context("join computation")?;
+        .context("join computation")
+        .map_err(Error::Transport)?;
     let incoming = incoming.fuse();
 
-    let signing = OfflineStage::new(my_ind, t, key_share, message_bn)?;
-    let completed_offline_stage = round_based::AsyncProtocol::new(signing, incoming, outgoing)
+    let signing = OfflineStage::new(
+        computation_id,
+        my_ind,
+        t,
+        key_share,
+        message_bn.clone(),
+        OsRng,
+    )
+    .context("create initial state")
+    .map_err(Error::Config)?;
+    let completed = AsyncProtocol::new(signing, incoming, outgoing)
+        .on_progress(report_progress("signing", on_progress))
+        .cancel_on(cancel)
         .run()
-        .await
-        .map_err(|e| anyhow!("protocol execution terminated with error: {}", e))?;
-
-    
-    /*outgoing
-        .send(Msg {
-            sender: my_ind,
-            receiver: None,
-            body: partial_signature,
-        })
-        .await?;
-
-    let partial_signatures: Vec<_> = incoming
-        .take(number_of_parties - 1)
-        .map_ok(|msg| msg.body)
-        .try_collect()
-        .await?;
+        .await;
+    abort_if_cancelled(&completed, &signal_client, &me, computation_id, &group).await;
+    save_device_secrets(config, device_secrets).await;
+    let completed = completed?;
 
-    let signature = signing
-        .complete(&partial_signatures)
-        .context("online stage failed")?;
-    let signature = serde_json::to_string(&signature).context("serialize signature")?;
-    println!("{}", signature);*/
-    
+    verify(&completed.signature_rec_id, completed.public_key(), &message_bn)
+        .map_err(|e| Error::Internal(anyhow!("resulting signature is not valid: {:?}", e)))?;
+    Ok(completed.signature_rec_id)
+}
 
-    Ok(())
+/// Checks whether hex-encoded `signature` (`r` and `s` concatenated) of `message` matches
+/// hex-encoded `public_key`
+///
+/// Malformed key or signature is reported as error, not as invalid signature.
+pub fn verify_run(public_key: &str, signature: &str, message: &BigInt) -> anyhow::Result<bool> {
+    let public_key = hex::decode(public_key.trim_start_matches("0x"))
+        .context("public key is not valid hex encoded string")?;
+    let public_key = Point::<Secp256k1>::from_bytes(&public_key)
+        .map_err(|e| anyhow!("invalid public key: {}", e))?;
+    let signature = hex::decode(signature.trim_start_matches("0x"))
+        .context("signature is not valid hex encoded string")?;
+    ensure!(signature.len() == 64, "signature must be 64 bytes long");
+    let scalar = |bytes: &[u8]| {
+        let n = BigInt::from_bytes(bytes);
+        ensure!(
+            n > BigInt::zero() && &n < Scalar::<Secp256k1>::group_order(),
+            "signature is out of range"
+        );
+        Ok(Scalar::<Secp256k1>::from_bigint(&n))
+    };
+    let signature = SignatureRecid {
+        r: scalar(&signature[..32])?,
+        s: scalar(&signature[32..])?,
+        // recovery id isn't used in verification
+        recid: 0,
+    };
+
+    Ok(verify(&signature, &public_key, message).is_ok())
 }
-/*
-pub async fn verify(args: cli::VerifyArgs) -> Result<()> {
-    let public_key =
-        hex::decode(args.public_key).context("public key is not valid hex encoded string")?;
-    let signature =
-        hex::decode(args.signature).context("signature key is not valid hex encoded string")?;
 
-    let signature = GE1::from_bytes(&signature)
-        .map_err(|e| anyhow!("signature is not valid g1 point: {:?}", e))?;
-    let public_key = GE2::from_bytes(&public_key)
-        .map_err(|e| anyhow!("public key is not valid g2 point: {:?}", e))?;
+/// Hex-encoded scalar padded to 32 bytes, the way `r` and `s` of a signature are written
+pub fn scalar_hex(scalar: &Scalar<Secp256k1>) -> String {
+    format!("{:0>64}", scalar.to_bigint().to_hex())
+}
 
-    let valid = BLSSignature { sigma: signature }.verify(&args.digits, &public_key);
-    if valid {
-        println!("Signature is valid");
-    } else {
-        bail!("Signature is not valid");
+/// Message given in command line as a number being signed
+///
+/// Hex string is decoded into bytes, any other string is taken as is. Message is expected to be
+/// hashed already.
+pub fn message_to_sign(digits: &str) -> BigInt {
+    match hex::decode(digits.trim_start_matches("0x")) {
+        Ok(bytes) => BigInt::from_bytes(&bytes),
+        Err(_) => BigInt::from_bytes(digits.as_bytes()),
     }
-
-    Ok(())
-}
-*/
-pub fn signal_client() -> Result<SignalClient> {
-    let server = SignalServer::from_args();
-    let mut builder = SignalClient::builder()?;
-    builder.set_server_host(server.host)?;
-    Ok(builder.finish())
 }
 
-pub fn read_group(file_content:Vec<u8>) -> Result<Group> {
+pub fn read_group(file_content:Vec<u8>) -> anyhow::Result<Group> {
     let parties_raw =
         serde_json::Deserializer::from_slice(&file_content).into_iter::<ParticipantIdentity>();
     let mut parties = vec![];
@@ -291,138 +396,3 @@ pub fn read_group(file_content:Vec<u8>) -> Result<Group> {
     }
     Ok(Group::new(parties))
 }
-
-/*
-pub async fn save_local_key(local_key: &LocalKey, output_file: File) -> Result<()> {
-    let ser_output = serde_json::to_vec_pretty(&local_key).context("serialize output")?;
-    tokio::io::copy(&mut ser_output.as_slice(), &mut output_file)
-        .await
-        .context("save output to file")?;
-}
-
-pub async fn read_local_key(path: impl AsRef<Path>) -> Result<LocalKey> {
-    let local_share = tokio::fs::read(args.local_share)
-        .await
-        .context("cannot read local share")?;
-    let local_share = serde_json::from_slice(&local_share).context("parse local share")?;
-}
-
-pub fn sign(args: cli::SignArgs) -> Result<()> {
-    let rt = Runtime::new().unwrap();
-    rt.block_on(async {
-        //1) establish the signal client
-        let signal_client = signal_client()
-            .await
-            .context("constructing signal client")?;
-        //2) read the MPC device id, address, public key
-        let mut device_secrets = DeviceStore::from_file("secrets.json")
-            .await
-            .context("read device from file")?;
-        let me = device_secrets.read().await.me();
-        //3) read the group.json file and ensure this device is included
-        let group = read_group(args.group).await.context("read group")?;
-        let my_ind = match group.party_index(&me.addr) {
-            Some(i) => i,
-            None => bail!("group must contain this party too"),
-        };
-        //4) read the local shares from file
-        let data = fs::read_to_string(args.local_key)
-            .expect("Unable to load keys, did you run keygen first? ");
-        let (party_keys, shared_keys, party_id, vss_scheme_vec, paillier_key_vector, y_sum): (
-            Keys,
-            SharedKeys,
-            u16,
-            Vec<VerifiableSS<Secp256k1>>,
-            Vec<EncryptionKey>,
-            Point<Secp256k1>,
-        ) = serde_json::from_str(&data).unwrap();
-        let key_share = LocalKeyShare {
-            party_keys, shared_keys, party_id, vss_scheme_vec, paillier_key_vector, y_sum
-        };
-        //5) read the message string to be signed
-        let message_str = args.digits;
-        let message = match hex::decode(message_str.clone()) {
-            Ok(x) => x,
-            Err(_e) => message_str.as_bytes().to_vec(),
-        };
-        let message = &message[..];
-        // we assume the message is already hashed (by the signer).
-        let message_bn = BigInt::from_bytes(message);
-        sign_run(
-            signal_client,
-            device_secrets.clone(),
-            group,
-            me,
-            my_ind,
-            key_share,
-            message_bn,
-        )
-        .await;
-        if let Err(err) = device_secrets.save("secrets.json").await {
-            tracing::event!(tracing::Level::ERROR, %err, "Failed to save secrets to file");
-        }
-        Ok(())
-    })
-}
-pub fn login(args: cli::LoginArgs) -> Result<()> {
-    let rt = Runtime::new().unwrap();
-    rt.block_on(async {
-        let signal_client = signal_client()
-            .await
-            .context("constructing signal client")?;
-        let (provision_url_tx, provision_url) = oneshot::channel();
-        let (device_tx, device) = oneshot::channel();
-        let device_name = args.device_name;
-        actix::spawn(async move {
-            let device = signal_client
-                .login(&mut OsRng, provision_url_tx, device_name)
-                .await
-                .context("login failed");
-            let _ = device_tx.send(device);
-        });
-        //return QR string
-        match provision_url.await {
-            Ok(url) => {
-                println!();
-                println!("To continue, scan following QR code using Signal app on your phone.");
-                println!("On Android: Signal Settings → Linked Devices → '+' Button");
-                println!("On iOS:     Signal Settings → Linked Devices → Link New Device");
-                println!();
-                qr2term::print_qr(url.to_string()).context("printing QR code")?
-            }
-            Err(_e) => {
-                // real error will be discovered below
-            }
-        }
-////////////////////////Trigger after scan QR finished/////////////////////////////////////
-        let device = device.await.context("retrieving device")??;
-        DeviceStore::new(device)
-            .save_no_overwrite("secrets.json")
-            .await
-            .context("save secrets")?;
-        println!();
-        println!("MPC device successfully created");
-        Ok(())
-    })
-}
-pub fn me(args: cli::MeArgs) -> Result<()> {
-    let rt = Runtime::new().unwrap();
-    rt.block_on(async {
-        let device = DeviceStore::from_file("secrets.json")
-            .await
-            .context("read device from file")?;
-        let device = device.read().await;
-        let me = device.me();
-        if args.json {
-            let json = serde_json::to_string(&me).context("serialize")?;
-            println!("{}", json);
-        } else {
-            println!("Name:       {}", me.addr.name());
-            println!("Device ID:  {}", me.addr.device_id());
-            println!("Public key: {}", base64::encode(me.public_key.serialize()));
-        }
-    Ok(())
-    })
-}
-
-*/
\ No newline at end of file
@@ -0,0 +1,563 @@
+//! Access structures beyond plain `t`-of-`n`
+//!
+//! Every party holds one or more *share slots*. A slot is an evaluation of the `order`-th
+//! derivative of the degree-`t` sharing polynomial at point `index`:
+//!
+//! * plain threshold — every party holds a single slot `(i, 0)`,
+//! * weighted — a party of weight `w` holds `w` slots of order `0` at consecutive indexes,
+//! * hierarchical (Tassa) — a party of level `l` holds a slot of order `l`, so lower levels
+//!   must be present for the set to be able to sign.
+//!
+//! A set of parties is authorized iff the constant term of the polynomial can be expressed
+//! as a linear combination of their slots (Birkhoff interpolation). For plain threshold
+//! structure that's exactly Lagrange interpolation.
+
+use std::convert::TryFrom;
+use std::iter;
+
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::{Point, Scalar, Secp256k1};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::utilities::sampling::sample_scalar;
+
+/// Share held by a party: value of `order`-th derivative of sharing polynomial at `index`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareSlot {
+    pub index: u16,
+    pub order: u16,
+}
+
+/// Describes which sets of parties are able to sign
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessStructure {
+    /// Degree `t` of the sharing polynomial
+    pub threshold: u16,
+    /// `slots[i]` lists share slots held by party `i+1`
+    pub slots: Vec<Vec<ShareSlot>>,
+}
+
+impl AccessStructure {
+    /// Plain `t`-of-`n` structure: any `t+1` parties can sign
+    pub fn threshold(t: u16, n: u16) -> Self {
+        Self {
+            threshold: t,
+            slots: (1..=n)
+                .map(|index| vec![ShareSlot { index, order: 0 }])
+                .collect(),
+        }
+    }
+
+    /// Structure of a key serialized before access structures were recorded
+    ///
+    /// Such keys are plain `t`-of-`n`, `t` and `n` are taken from parameters of the VSS schemes.
+    pub(crate) fn of_legacy_key(
+        vss_vec: &[VerifiableSS<Secp256k1>],
+    ) -> Result<Self, &'static str> {
+        let params = &vss_vec.first().ok_or("vss scheme vector is empty")?.parameters;
+        Ok(Self::threshold(params.threshold, params.share_count))
+    }
+
+    /// Weighted structure: party `i+1` holds `weights[i]` shares, any set of parties holding
+    /// together at least `t+1` shares can sign
+    pub fn weighted(t: u16, weights: &[u16]) -> Result<Self, AccessStructureError> {
+        let mut next_index = 1u16;
+        let mut slots = Vec::with_capacity(weights.len());
+        for &weight in weights {
+            let party_slots = (0..weight)
+                .map(|_| {
+                    let index = next_index;
+                    next_index = next_index
+                        .checked_add(1)
+                        .ok_or(AccessStructureError::TooManyShares)?;
+                    Ok(ShareSlot { index, order: 0 })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            slots.push(party_slots);
+        }
+        Self::new(t, slots)
+    }
+
+    /// Hierarchical structure: party `i+1` belongs to level `levels[i]` (`0` is the top level)
+    ///
+    /// A set of parties can sign if it has at least `t+1` members and, for every level `l`,
+    /// at least `l+1` of them belong to levels `0..=l`.
+    pub fn hierarchical(t: u16, levels: &[u16]) -> Result<Self, AccessStructureError> {
+        let slots = levels
+            .iter()
+            .enumerate()
+            .map(|(i, &order)| {
+                let index = u16::try_from(i + 1).map_err(|_| AccessStructureError::TooManyShares)?;
+                Ok(vec![ShareSlot { index, order }])
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(t, slots)
+    }
+
+    /// Constructs arbitrary access structure from share slots of every party
+    ///
+    /// Returns error if slots are not distinct, some party holds no slots, a single party is
+    /// able to sign on its own, or all parties together are not able to sign.
+    pub fn new(t: u16, slots: Vec<Vec<ShareSlot>>) -> Result<Self, AccessStructureError> {
+        let structure = Self {
+            threshold: t,
+            slots,
+        };
+        structure.validate()?;
+        Ok(structure)
+    }
+
+    /// Checks that structure is well-formed
+    pub fn validate(&self) -> Result<(), AccessStructureError> {
+        let n = self.parties();
+        if n < 2 {
+            return Err(AccessStructureError::TooFewParties);
+        }
+        if self.threshold == 0 {
+            return Err(AccessStructureError::InvalidThreshold);
+        }
+        if let Some(party) = self.slots.iter().position(|s| s.is_empty()) {
+            return Err(AccessStructureError::NoSharesForParty {
+                party: party as u16 + 1,
+            });
+        }
+        let all_slots: Vec<_> = self.slots.iter().flatten().collect();
+        for (i, slot) in all_slots.iter().enumerate() {
+            if slot.index == 0 || slot.order > self.threshold {
+                return Err(AccessStructureError::InvalidSlot(**slot));
+            }
+            if all_slots[..i].contains(slot) {
+                return Err(AccessStructureError::DuplicatedSlot(**slot));
+            }
+        }
+        for party in 0..n {
+            if self.is_authorized(&[party]) {
+                return Err(AccessStructureError::SinglePartyAuthorized { party: party + 1 });
+            }
+        }
+        let everyone: Vec<u16> = (0..n).collect();
+        if !self.is_authorized(&everyone) {
+            return Err(AccessStructureError::Unsatisfiable);
+        }
+        Ok(())
+    }
+
+    /// Number of parties
+    pub fn parties(&self) -> u16 {
+        self.slots.len() as u16
+    }
+
+    /// Total number of share slots
+    pub fn total_slots(&self) -> u16 {
+        self.slots.iter().map(|s| s.len() as u16).sum()
+    }
+
+    /// Whether every party holds a single share at its own index, i.e. structure is plain
+    /// `t`-of-`n` threshold
+    pub fn is_plain_threshold(&self) -> bool {
+        self.slots.iter().enumerate().all(|(i, party_slots)| {
+            party_slots.as_slice()
+                == [ShareSlot {
+                    index: i as u16 + 1,
+                    order: 0,
+                }]
+        })
+    }
+
+    /// Whether parties `s` (indexes in range `[0; n)`) are able to sign
+    pub fn is_authorized(&self, s: &[u16]) -> bool {
+        self.reconstruction_coefficients(s).is_ok()
+    }
+
+    /// Shares `secret` according to the structure
+    ///
+    /// Returns Feldman commitments to the sharing polynomial and, for every party, shares
+    /// matching its slots.
+    pub fn share<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        secret: &Scalar<Secp256k1>,
+    ) -> (VerifiableSS<Secp256k1>, Vec<Vec<Scalar<Secp256k1>>>) {
+        let coefficients: Vec<Scalar<Secp256k1>> = iter::once(secret.clone())
+            .chain((0..self.threshold).map(|_| sample_scalar(rng)))
+            .collect();
+        let commitments = coefficients
+            .iter()
+            .map(|a| Point::generator() * a)
+            .collect();
+        let vss = VerifiableSS {
+            parameters: ShamirSecretSharing {
+                threshold: self.threshold,
+                share_count: self.total_slots(),
+            },
+            commitments,
+        };
+        let shares = self
+            .slots
+            .iter()
+            .map(|party_slots| {
+                party_slots
+                    .iter()
+                    .map(|slot| {
+                        slot_row(slot, self.threshold)
+                            .iter()
+                            .zip(&coefficients)
+                            .map(|(r, a)| r * a)
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect();
+        (vss, shares)
+    }
+
+    /// Verifies `shares` of party `party` (in range `[1; n]`) against Feldman commitments
+    pub fn validate_shares(
+        &self,
+        vss: &VerifiableSS<Secp256k1>,
+        party: u16,
+        shares: &[Scalar<Secp256k1>],
+    ) -> bool {
+        let party_slots = match self.slots.get(usize::from(party).wrapping_sub(1)) {
+            Some(s) => s,
+            None => return false,
+        };
+        vss.parameters.threshold == self.threshold
+            && party_slots.len() == shares.len()
+            && party_slots.iter().zip(shares).all(|(slot, share)| {
+                self.slot_commitment(&vss.commitments, slot)
+                    .map(|c| c == Point::generator() * share)
+                    .unwrap_or(false)
+            })
+    }
+
+    /// Computes `g^share` of a slot out of commitments to the sharing polynomial
+    ///
+    /// Returns `None` if number of commitments doesn't match threshold.
+    pub fn slot_commitment(
+        &self,
+        commitments: &[Point<Secp256k1>],
+        slot: &ShareSlot,
+    ) -> Option<Point<Secp256k1>> {
+        if commitments.len() != usize::from(self.threshold) + 1 {
+            return None;
+        }
+        Some(
+            slot_row(slot, self.threshold)
+                .iter()
+                .zip(commitments)
+                .map(|(r, c)| c * r)
+                .sum(),
+        )
+    }
+
+    /// Commitments to the joint sharing polynomial (sum of polynomials of every party)
+    pub fn joint_commitments(
+        &self,
+        vss_scheme_vec: &[VerifiableSS<Secp256k1>],
+    ) -> Vec<Point<Secp256k1>> {
+        (0..=usize::from(self.threshold))
+            .map(|k| {
+                vss_scheme_vec
+                    .iter()
+                    .map(|vss| vss.commitments.get(k).cloned().unwrap_or_else(Point::zero))
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Computes interpolation coefficients for every slot of every party in `s`
+    ///
+    /// `s` lists parties indexes in range `[0; n)` (the same convention as in
+    /// [VerifiableSS::map_share_to_new_params]). Returned vector is aligned with `s`, every
+    /// element is aligned with slots of corresponding party. Secret equals to sum of
+    /// `coefficient * share` over all the slots.
+    pub fn reconstruction_coefficients(
+        &self,
+        s: &[u16],
+    ) -> Result<Vec<Vec<Scalar<Secp256k1>>>, AccessStructureError> {
+        let mut signer_slots = Vec::with_capacity(s.len());
+        for (i, &party) in s.iter().enumerate() {
+            if s[..i].contains(&party) {
+                return Err(AccessStructureError::DuplicatedSigner { party: party + 1 });
+            }
+            let party_slots = self
+                .slots
+                .get(usize::from(party))
+                .ok_or(AccessStructureError::UnknownSigner { party: party + 1 })?;
+            signer_slots.push(party_slots);
+        }
+
+        if self.is_plain_threshold() {
+            if s.len() <= usize::from(self.threshold) {
+                return Err(AccessStructureError::Unauthorized { signers: s.to_vec() });
+            }
+            let params = ShamirSecretSharing {
+                threshold: self.threshold,
+                share_count: self.parties(),
+            };
+            return Ok(s
+                .iter()
+                .map(|&i| {
+                    vec![VerifiableSS::<Secp256k1>::map_share_to_new_params(
+                        &params, i, s,
+                    )]
+                })
+                .collect());
+        }
+
+        // Solve `sum_j lambda_j * row_j = e_0`, where `row_j` expresses slot `j` in terms of
+        // polynomial coefficients
+        let rows: Vec<Vec<Scalar<Secp256k1>>> = signer_slots
+            .iter()
+            .flat_map(|party_slots| party_slots.iter())
+            .map(|slot| slot_row(slot, self.threshold))
+            .collect();
+        let matrix: Vec<Vec<Scalar<Secp256k1>>> = (0..=usize::from(self.threshold))
+            .map(|k| rows.iter().map(|row| row[k].clone()).collect())
+            .collect();
+        let rhs: Vec<Scalar<Secp256k1>> = (0..=self.threshold)
+            .map(|k| {
+                if k == 0 {
+                    Scalar::from(1u16)
+                } else {
+                    Scalar::zero()
+                }
+            })
+            .collect();
+        let lambda = solve(matrix, rhs, rows.len())
+            .ok_or_else(|| AccessStructureError::Unauthorized { signers: s.to_vec() })?;
+
+        let mut lambda = lambda.into_iter();
+        Ok(signer_slots
+            .iter()
+            .map(|party_slots| lambda.by_ref().take(party_slots.len()).collect())
+            .collect())
+    }
+
+    /// Interpolation coefficients for slots of party `index` (in range `[0; n)`) signing
+    /// together with parties `s`
+    pub fn signer_coefficients(
+        &self,
+        index: u16,
+        s: &[u16],
+    ) -> Result<Vec<Scalar<Secp256k1>>, AccessStructureError> {
+        let position = s
+            .iter()
+            .position(|&j| j == index)
+            .ok_or(AccessStructureError::UnknownSigner { party: index + 1 })?;
+        let mut coefficients = self.reconstruction_coefficients(s)?;
+        Ok(coefficients.swap_remove(position))
+    }
+
+    /// Computes `g^w_i` of party `index` (in range `[0; n)`) signing together with parties `s`
+    ///
+    /// Public counterpart of [SignKeys::create_with_access_structure](super::party_i::SignKeys::create_with_access_structure).
+    pub fn public_share(
+        &self,
+        vss_scheme_vec: &[VerifiableSS<Secp256k1>],
+        index: u16,
+        s: &[u16],
+    ) -> Result<Point<Secp256k1>, AccessStructureError> {
+        let coefficients = self.signer_coefficients(index, s)?;
+        let joint_commitments = self.joint_commitments(vss_scheme_vec);
+        self.slots[usize::from(index)]
+            .iter()
+            .zip(&coefficients)
+            .map(|(slot, l)| {
+                self.slot_commitment(&joint_commitments, slot)
+                    .map(|c| c * l)
+                    .ok_or(AccessStructureError::MismatchedCommitments)
+            })
+            .sum()
+    }
+}
+
+/// Coefficients of `f^(order)(index)` in terms of polynomial coefficients `a_0, ..., a_t`
+fn slot_row(slot: &ShareSlot, t: u16) -> Vec<Scalar<Secp256k1>> {
+    let x = Scalar::<Secp256k1>::from(slot.index);
+    let order = u32::from(slot.order);
+    (0..=u32::from(t))
+        .map(|k| {
+            if k < order {
+                return Scalar::zero();
+            }
+            // k! / (k - order)! * x^(k - order)
+            let falling_factorial: Scalar<Secp256k1> = (k - order + 1..=k)
+                .map(|m| Scalar::from(m as u16))
+                .fold(Scalar::from(1u16), |acc, m| acc * m);
+            let power = (0..k - order).fold(Scalar::from(1u16), |acc, _| acc * &x);
+            falling_factorial * power
+        })
+        .collect()
+}
+
+/// Finds any solution of linear system `matrix * x = rhs` with `columns` unknowns
+fn solve(
+    mut matrix: Vec<Vec<Scalar<Secp256k1>>>,
+    mut rhs: Vec<Scalar<Secp256k1>>,
+    columns: usize,
+) -> Option<Vec<Scalar<Secp256k1>>> {
+    let rows = matrix.len();
+    let mut pivots = vec![];
+    let mut r = 0;
+    for c in 0..columns {
+        if r == rows {
+            break;
+        }
+        let p = match (r..rows).find(|&i| !matrix[i][c].is_zero()) {
+            Some(p) => p,
+            None => continue,
+        };
+        matrix.swap(r, p);
+        rhs.swap(r, p);
+
+        let inv = matrix[r][c].invert()?;
+        matrix[r] = matrix[r].iter().map(|v| v * &inv).collect();
+        rhs[r] = &rhs[r] * &inv;
+
+        let pivot_row = matrix[r].clone();
+        let pivot_rhs = rhs[r].clone();
+        for i in (0..rows).filter(|&i| i != r) {
+            if matrix[i][c].is_zero() {
+                continue;
+            }
+            let factor = matrix[i][c].clone();
+            matrix[i] = matrix[i]
+                .iter()
+                .zip(&pivot_row)
+                .map(|(v, p)| v - &(&factor * p))
+                .collect();
+            rhs[i] = &rhs[i] - &(&factor * &pivot_rhs);
+        }
+        pivots.push(c);
+        r += 1;
+    }
+
+    if rhs[r..].iter().any(|v| !v.is_zero()) {
+        return None;
+    }
+    let mut x = vec![Scalar::zero(); columns];
+    for (row, &c) in pivots.iter().enumerate() {
+        x[c] = rhs[row].clone();
+    }
+    Some(x)
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum AccessStructureError {
+    #[error("at least 2 parties are required")]
+    TooFewParties,
+    #[error("threshold must be at least 1")]
+    InvalidThreshold,
+    #[error("too many shares, total number of shares must fit into u16")]
+    TooManyShares,
+    #[error("party {party} holds no shares")]
+    NoSharesForParty { party: u16 },
+    #[error("invalid share slot {0:?}: index must be non-zero, order must not exceed threshold")]
+    InvalidSlot(ShareSlot),
+    #[error("share slot {0:?} is given to several parties")]
+    DuplicatedSlot(ShareSlot),
+    #[error("party {party} is able to sign on its own")]
+    SinglePartyAuthorized { party: u16 },
+    #[error("all parties together are not able to sign")]
+    Unsatisfiable,
+    #[error("signer {party} is not a party of the access structure")]
+    UnknownSigner { party: u16 },
+    #[error("signer {party} is listed twice")]
+    DuplicatedSigner { party: u16 },
+    #[error("parties {signers:?} (0-indexed) are not authorized to sign")]
+    Unauthorized { signers: Vec<u16> },
+    #[error("access structure doesn't match key: {0}")]
+    MismatchedKey(&'static str),
+    #[error("number of commitments doesn't match threshold")]
+    MismatchedCommitments,
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use super::*;
+
+    fn reconstruct(
+        structure: &AccessStructure,
+        shares: &[Vec<Scalar<Secp256k1>>],
+        s: &[u16],
+    ) -> Option<Scalar<Secp256k1>> {
+        let coefficients = structure.reconstruction_coefficients(s).ok()?;
+        Some(
+            s.iter()
+                .zip(&coefficients)
+                .flat_map(|(&i, l)| shares[usize::from(i)].iter().zip(l))
+                .map(|(x, l)| x * l)
+                .sum(),
+        )
+    }
+
+    #[test]
+    fn threshold_matches_lagrange() {
+        let structure = AccessStructure::threshold(2, 4);
+        let secret = Scalar::random();
+        let (vss, shares) = structure.share(&mut OsRng, &secret);
+        for i in 1..=4 {
+            assert!(structure.validate_shares(&vss, i, &shares[usize::from(i - 1)]));
+        }
+        assert_eq!(reconstruct(&structure, &shares, &[0, 2, 3]), Some(secret));
+        assert!(!structure.is_authorized(&[1, 3]));
+    }
+
+    #[test]
+    fn weighted_structure() {
+        // two executives of weight 2, three operators of weight 1, 3 shares are required
+        let structure = AccessStructure::weighted(2, &[2, 2, 1, 1, 1]).unwrap();
+        let secret = Scalar::random();
+        let (vss, shares) = structure.share(&mut OsRng, &secret);
+        for i in 1..=5 {
+            assert!(structure.validate_shares(&vss, i, &shares[usize::from(i - 1)]));
+        }
+        assert_eq!(reconstruct(&structure, &shares, &[0, 1]), Some(secret.clone()));
+        assert_eq!(reconstruct(&structure, &shares, &[1, 4]), Some(secret.clone()));
+        assert_eq!(reconstruct(&structure, &shares, &[2, 3, 4]), Some(secret));
+        assert!(!structure.is_authorized(&[2, 3]));
+    }
+
+    #[test]
+    fn hierarchical_structure() {
+        // one executive (level 0) must be present, any 3 parties overall
+        let structure = AccessStructure::hierarchical(2, &[0, 0, 1, 1, 1]).unwrap();
+        let secret = Scalar::random();
+        let (vss, shares) = structure.share(&mut OsRng, &secret);
+        for i in 1..=5 {
+            assert!(structure.validate_shares(&vss, i, &shares[usize::from(i - 1)]));
+        }
+        assert_eq!(reconstruct(&structure, &shares, &[0, 2, 3]), Some(secret.clone()));
+        assert_eq!(reconstruct(&structure, &shares, &[0, 1, 4]), Some(secret));
+        assert!(!structure.is_authorized(&[2, 3, 4]));
+        assert!(!structure.is_authorized(&[0, 1]));
+    }
+
+    #[test]
+    fn tampered_share_is_rejected() {
+        let structure = AccessStructure::hierarchical(1, &[0, 1, 1]).unwrap();
+        let (vss, mut shares) = structure.share(&mut OsRng, &Scalar::random());
+        shares[1][0] = &shares[1][0] + Scalar::from(1u16);
+        assert!(!structure.validate_shares(&vss, 2, &shares[1]));
+    }
+
+    #[test]
+    fn rejects_degenerate_structures() {
+        assert_eq!(
+            AccessStructure::weighted(1, &[2, 1]),
+            Err(AccessStructureError::SinglePartyAuthorized { party: 1 })
+        );
+        assert_eq!(
+            AccessStructure::hierarchical(1, &[1, 1]),
+            Err(AccessStructureError::Unsatisfiable)
+        );
+    }
+}
@@ -1,4 +1,8 @@
+pub mod access_structure;
+pub mod parallel;
 pub mod party_i;
+pub mod transcript;
+pub mod validation;
 
 #[derive(Clone, Debug)]
 pub struct ErrorType {
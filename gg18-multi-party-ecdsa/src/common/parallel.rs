@@ -0,0 +1,74 @@
+//! Per-counterparty loops that run on rayon thread pool when `parallel` feature is enabled
+//!
+//! Without the feature, items are processed one after another on the calling thread. Results
+//! don't depend on the feature: outputs keep order of inputs, and when several items fail,
+//! the error of the first one is returned.
+
+use rand::rngs::StdRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Whether `f` holds for every index in `0..n`
+pub fn all<F>(n: usize, f: F) -> bool
+where
+    F: Fn(usize) -> bool + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    {
+        (0..n).into_par_iter().all(f)
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        (0..n).all(f)
+    }
+}
+
+/// Applies `f` to every item, returns outputs in order of `items` or the first error
+pub fn try_map<T, U, E, F>(items: Vec<T>, f: F) -> Result<Vec<U>, E>
+where
+    T: Send,
+    U: Send,
+    E: Send,
+    F: Fn(T) -> Result<U, E> + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    {
+        let results: Vec<Result<U, E>> = items.into_par_iter().map(f).collect();
+        results.into_iter().collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        items.into_iter().map(f).collect()
+    }
+}
+
+/// Derives `n` independent rngs from `rng`, one per counterparty
+///
+/// Rngs are derived in the same way regardless of `parallel` feature, so seeded runs
+/// are reproducible with and without it.
+pub fn fork_rngs<R: RngCore + CryptoRng>(rng: &mut R, n: usize) -> Vec<StdRng> {
+    (0..n)
+        .map(|_| {
+            let mut seed = <StdRng as SeedableRng>::Seed::default();
+            rng.fill_bytes(&mut seed);
+            StdRng::from_seed(seed)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn try_map_keeps_order_and_reports_first_error() {
+        let doubled: Result<Vec<u32>, u32> = try_map((0..100).collect(), |x| Ok(x * 2));
+        assert_eq!(doubled.unwrap(), (0..100).map(|x| x * 2).collect::<Vec<_>>());
+
+        let failed: Result<Vec<u32>, u32> =
+            try_map((0..100).collect(), |x| if x % 10 == 7 { Err(x) } else { Ok(x) });
+        assert_eq!(failed, Err(7));
+    }
+}
@@ -17,6 +17,7 @@
 */
 
 use std::convert::TryFrom;
+use std::fmt;
 
 use centipede::juggling::proof_system::{Helgamalsegmented, Witness};
 use centipede::juggling::segmentation::Msegmentation;
@@ -24,22 +25,40 @@ use curv::arithmetic::traits::*;
 use curv::cryptographic_primitives::commitments::hash_commitment::HashCommitment;
 use curv::cryptographic_primitives::commitments::traits::Commitment;
 use curv::cryptographic_primitives::hashing::{Digest, DigestExt};
-use curv::cryptographic_primitives::proofs::sigma_correct_homomorphic_elgamal_enc::*;
-use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::elliptic::curves::{Curve, Point, Scalar, Secp256k1};
 use curv::BigInt;
-use paillier::{
-    Decrypt, DecryptionKey, EncryptionKey, KeyGeneration, Paillier, RawCiphertext, RawPlaintext,
-};
+use paillier::{DecryptionKey, EncryptionKey, RawPlaintext};
 use sha2::Sha256;
-use zk_paillier::zkproofs::NiCorrectKeyProof;
+use zk_paillier::zkproofs::{DLogStatement, NiCorrectKeyProof};
 
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
-
+use zeroize::Zeroize;
+
+use crate::common::access_structure::{AccessStructure, AccessStructureError};
+use crate::common::parallel;
+use crate::common::transcript::ProofContext;
+use crate::common::validation::{InvalidValue, NonZeroPoint};
+use crate::utilities::sampling::{
+    paillier_keypair, paillier_keypair_safe_primes, sample_bits, sample_range, sample_scalar,
+    sample_unit, PAILLIER_KEY_SIZE,
+};
+use crate::utilities::paillier_crt::{InvalidDecryptionKey, PrecomputedDecryptionKey};
+use crate::utilities::zk_composite_dlog::CompositeDLogProof;
+use crate::utilities::zk_dlog::DLogProof;
+use crate::utilities::zk_homo_elgamal::{
+    HomoELGamalProof, HomoElGamalStatement, HomoElGamalWitness,
+};
 use crate::common::Error::{self, InvalidCom, InvalidKey, InvalidSig};
 use crate::common::ErrorType;
 const SECURITY: usize = 256;
+/// Bit length of `N_tilde` modulus used in range proofs
+#[cfg(not(test))]
+const N_TILDE_BIT_LENGTH: usize = 2048;
+/// Safe prime search is slow in debug builds, unit tests use smaller modulus
+#[cfg(test)]
+const N_TILDE_BIT_LENGTH: usize = 512;
 
 
 #[derive(Debug)]
@@ -48,42 +67,53 @@ pub struct Parameters {
     pub share_count: u16, //n
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Party's long-term keys
+///
+/// Like other secret-bearing types of this module, zeroizes its secrets on drop (curv scalars
+/// zeroize themselves) and prints `[redacted]` instead of them in `Debug` output.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Keys<E: Curve = Secp256k1> {
     pub u_i: Scalar<E>,
     pub y_i: Point<E>,
-    pub dk: DecryptionKey,
+    pub dk: PrecomputedDecryptionKey,
     pub ek: EncryptionKey,
     pub party_index: u16,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PartyPrivate {
     u_i: Scalar<Secp256k1>,
     x_i: Scalar<Secp256k1>,
-    dk: DecryptionKey,
+    extra_x_i: Vec<Scalar<Secp256k1>>,
+    dk: PrecomputedDecryptionKey,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KeyGenBroadcastMessage1 {
     pub e: EncryptionKey,
+    pub dlog_statement: DLogStatement,
     pub com: BigInt,
     pub correct_key_proof: NiCorrectKeyProof,
+    pub composite_dlog_proof_base_h1: CompositeDLogProof,
+    pub composite_dlog_proof_base_h2: CompositeDLogProof,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KeyGenDecommitMessage1 {
     pub blind_factor: BigInt,
-    pub y_i: Point<Secp256k1>,
+    pub y_i: NonZeroPoint,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SharedKeys {
     pub y: Point<Secp256k1>,
     pub x_i: Scalar<Secp256k1>,
+    /// Shares of the remaining slots if party holds more than one (see [AccessStructure])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_x_i: Vec<Scalar<Secp256k1>>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SignKeys {
     pub w_i: Scalar<Secp256k1>,
     pub g_w_i: Point<Secp256k1>,
@@ -100,10 +130,10 @@ pub struct SignBroadcastPhase1 {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SignDecommitPhase1 {
     pub blind_factor: BigInt,
-    pub g_gamma_i: Point<Secp256k1>,
+    pub g_gamma_i: NonZeroPoint,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct LocalSignature {
     pub l_i: Scalar<Secp256k1>,
     pub rho_i: Scalar<Secp256k1>,
@@ -125,19 +155,141 @@ pub struct Phase5Com2 {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Phase5ADecom1 {
-    pub V_i: Point<Secp256k1>,
-    pub A_i: Point<Secp256k1>,
-    pub B_i: Point<Secp256k1>,
+    pub V_i: NonZeroPoint,
+    pub A_i: NonZeroPoint,
+    pub B_i: NonZeroPoint,
     pub blind_factor: BigInt,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Phase5DDecom2 {
-    pub u_i: Point<Secp256k1>,
-    pub t_i: Point<Secp256k1>,
+    pub u_i: NonZeroPoint,
+    pub t_i: NonZeroPoint,
     pub blind_factor: BigInt,
 }
 
+/// Share `s_i` of the signature along with `l_i` that blinds it in phase 5A `V_i`
+///
+/// `l_i` is revealed only after phase 5D passed, it lets other parties check `s_i` against `V_i`
+/// and find out who sent a wrong share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PartialSignature {
+    pub s_i: Scalar<Secp256k1>,
+    pub l_i: Scalar<Secp256k1>,
+}
+
+/// Placeholder printed instead of a secret value
+pub(crate) struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+impl<E: Curve> fmt::Debug for Keys<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Keys")
+            .field("u_i", &Redacted)
+            .field("y_i", &self.y_i)
+            .field("dk", &Redacted)
+            .field("ek", &self.ek)
+            .field("party_index", &self.party_index)
+            .finish()
+    }
+}
+
+impl fmt::Debug for PartyPrivate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PartyPrivate")
+            .field("u_i", &Redacted)
+            .field("x_i", &Redacted)
+            .field("extra_x_i", &Redacted)
+            .field("dk", &Redacted)
+            .finish()
+    }
+}
+
+impl fmt::Debug for SharedKeys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedKeys")
+            .field("y", &self.y)
+            .field("x_i", &Redacted)
+            .field("extra_x_i", &Redacted)
+            .finish()
+    }
+}
+
+impl fmt::Debug for SignKeys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SignKeys")
+            .field("w_i", &Redacted)
+            .field("g_w_i", &self.g_w_i)
+            .field("k_i", &Redacted)
+            .field("gamma_i", &Redacted)
+            .field("g_gamma_i", &self.g_gamma_i)
+            .finish()
+    }
+}
+
+impl fmt::Debug for LocalSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LocalSignature")
+            .field("l_i", &Redacted)
+            .field("rho_i", &Redacted)
+            .field("R", &self.R)
+            .field("s_i", &Redacted)
+            .field("m", &self.m)
+            .field("y", &self.y)
+            .finish()
+    }
+}
+
+impl fmt::Debug for DLogSetup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DLogSetup")
+            .field("N_tilde", &self.N_tilde)
+            .field("h1", &self.h1)
+            .field("h2", &self.h2)
+            .field("alpha", &Redacted)
+            .field("beta", &Redacted)
+            .field("p", &Redacted)
+            .field("q", &Redacted)
+            .finish()
+    }
+}
+
+impl KeyGenDecommitMessage1 {
+    /// Checks message received from another party
+    pub fn validate(&self) -> Result<(), InvalidValue> {
+        self.y_i.validate("y_i")
+    }
+}
+
+impl SignDecommitPhase1 {
+    /// Checks message received from another party
+    pub fn validate(&self) -> Result<(), InvalidValue> {
+        self.g_gamma_i.validate("g_gamma_i")
+    }
+}
+
+impl Phase5ADecom1 {
+    /// Checks message received from another party
+    pub fn validate(&self) -> Result<(), InvalidValue> {
+        self.V_i.validate("V_i")?;
+        self.A_i.validate("A_i")?;
+        self.B_i.validate("B_i")
+    }
+}
+
+impl Phase5DDecom2 {
+    /// Checks message received from another party
+    pub fn validate(&self) -> Result<(), InvalidValue> {
+        self.u_i.validate("u_i")?;
+        self.t_i.validate("t_i")
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SignatureRecid {
     pub r: Scalar<Secp256k1>,
@@ -146,6 +298,7 @@ pub struct SignatureRecid {
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "SerializedLocalKeyShare")]
 pub struct LocalKeyShare {
     pub party_keys: Keys,
     pub shared_keys: SharedKeys,
@@ -153,6 +306,153 @@ pub struct LocalKeyShare {
     pub vss_scheme_vec: Vec<VerifiableSS<Secp256k1>>,
     pub paillier_key_vector: Vec<EncryptionKey>,
     pub y_sum: Point<Secp256k1>,
+    /// Plain `t`-of-`n` for key shares created before it was recorded
+    pub access_structure: AccessStructure,
+    pub dlog_setup: Option<DLogSetup>,
+    pub h1_h2_n_tilde_vec: Vec<DLogStatement>,
+}
+
+/// [LocalKeyShare] as it's deserialized, fields introduced after the first release are optional
+#[derive(Deserialize)]
+struct SerializedLocalKeyShare {
+    party_keys: Keys,
+    shared_keys: SharedKeys,
+    party_id: u16,
+    vss_scheme_vec: Vec<VerifiableSS<Secp256k1>>,
+    paillier_key_vector: Vec<EncryptionKey>,
+    y_sum: Point<Secp256k1>,
+    #[serde(default)]
+    access_structure: Option<AccessStructure>,
+    #[serde(default)]
+    dlog_setup: Option<DLogSetup>,
+    #[serde(default)]
+    h1_h2_n_tilde_vec: Vec<DLogStatement>,
+}
+
+impl TryFrom<SerializedLocalKeyShare> for LocalKeyShare {
+    type Error = &'static str;
+    fn try_from(key_share: SerializedLocalKeyShare) -> Result<Self, Self::Error> {
+        let access_structure = match key_share.access_structure {
+            Some(access_structure) => access_structure,
+            None => AccessStructure::of_legacy_key(&key_share.vss_scheme_vec)?,
+        };
+        Ok(LocalKeyShare {
+            party_keys: key_share.party_keys,
+            shared_keys: key_share.shared_keys,
+            party_id: key_share.party_id,
+            vss_scheme_vec: key_share.vss_scheme_vec,
+            paillier_key_vector: key_share.paillier_key_vector,
+            y_sum: key_share.y_sum,
+            access_structure,
+            dlog_setup: key_share.dlog_setup,
+            h1_h2_n_tilde_vec: key_share.h1_h2_n_tilde_vec,
+        })
+    }
+}
+
+impl LocalKeyShare {
+    /// Whether the key carries `N_tilde`, `h1`, `h2` of every party, which signing needs for
+    /// range proofs
+    ///
+    /// Keys imported from stores that don't have the setup (e.g. ZenGo key store) can't sign.
+    pub fn has_dlog_setup(&self) -> bool {
+        self.h1_h2_n_tilde_vec.len() == self.paillier_key_vector.len()
+    }
+}
+
+/// Party's `N_tilde`, `h1`, `h2` setup used by verifiers of range proofs
+///
+/// `N_tilde = p * q` is a product of safe primes `p = 2P + 1`, `q = 2Q + 1`, `h1` is a random
+/// quadratic residue, `h2 = h1^alpha mod N_tilde` and `h1 = h2^beta mod N_tilde` where
+/// `beta = alpha^-1 mod PQ` (same relation as in tss-lib).
+#[derive(Clone, Serialize, Deserialize, Zeroize)]
+#[zeroize(drop)]
+pub struct DLogSetup {
+    pub N_tilde: BigInt,
+    pub h1: BigInt,
+    pub h2: BigInt,
+    pub alpha: BigInt,
+    pub beta: BigInt,
+    pub p: BigInt,
+    pub q: BigInt,
+}
+
+impl DLogSetup {
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let keypair = paillier_keypair_safe_primes(rng, N_TILDE_BIT_LENGTH);
+        let (p, q) = (keypair.p, keypair.q);
+        let N_tilde = &p * &q;
+        let P = (&p - BigInt::one()) / BigInt::from(2);
+        let Q = (&q - BigInt::one()) / BigInt::from(2);
+        let pq = P * Q;
+
+        let f = sample_unit(rng, &N_tilde);
+        let h1 = BigInt::mod_mul(&f, &f, &N_tilde);
+        let (alpha, beta) = loop {
+            let alpha = sample_range(rng, &BigInt::one(), &pq);
+            if let Some(beta) = BigInt::mod_inv(&alpha, &pq) {
+                break (alpha, beta);
+            }
+        };
+        let h2 = BigInt::mod_pow(&h1, &alpha, &N_tilde);
+
+        Self {
+            N_tilde,
+            h1,
+            h2,
+            alpha,
+            beta,
+            p,
+            q,
+        }
+    }
+
+    /// Public part of the setup
+    pub fn statement(&self) -> DLogStatement {
+        DLogStatement {
+            N: self.N_tilde.clone(),
+            g: self.h1.clone(),
+            ni: self.h2.clone(),
+        }
+    }
+
+    /// Proves knowledge of `alpha` and `beta`, i.e. that `h1` and `h2` generate the same group
+    pub fn prove<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        ctx: &ProofContext,
+    ) -> (CompositeDLogProof, CompositeDLogProof) {
+        let statement_base_h1 = self.statement();
+        let statement_base_h2 = swap_bases(&statement_base_h1);
+        (
+            CompositeDLogProof::prove(rng, ctx, &statement_base_h1, &self.alpha),
+            CompositeDLogProof::prove(rng, ctx, &statement_base_h2, &self.beta),
+        )
+    }
+
+    /// Verifies proofs produced by [prove](Self::prove) against the public `statement`
+    pub fn verify_proofs(
+        ctx: &ProofContext,
+        statement: &DLogStatement,
+        proof_base_h1: &CompositeDLogProof,
+        proof_base_h2: &CompositeDLogProof,
+    ) -> bool {
+        proof_base_h1.verify(ctx, statement).is_ok()
+            && proof_base_h2.verify(ctx, &swap_bases(statement)).is_ok()
+    }
+}
+
+fn swap_bases(statement: &DLogStatement) -> DLogStatement {
+    DLogStatement {
+        N: statement.N.clone(),
+        g: statement.ni.clone(),
+        ni: statement.g.clone(),
+    }
+}
+
+/// CRT parameters of a key that we've just generated
+fn precompute(dk: DecryptionKey) -> PrecomputedDecryptionKey {
+    PrecomputedDecryptionKey::new(dk).expect("generated paillier primes are distinct")
 }
 
 #[derive(Serialize, Deserialize)]
@@ -162,72 +462,112 @@ pub struct Params {
 }
 
 impl Keys {
-    pub fn create(index: u16) -> Self {
-        let u = Scalar::<Secp256k1>::random();
+    pub fn create<R: RngCore + CryptoRng>(rng: &mut R, index: u16) -> Self {
+        let u = sample_scalar(rng);
         let y = Point::generator() * &u;
-        let (ek, dk) = Paillier::keypair().keys();
+        let (ek, dk) = paillier_keypair(rng, PAILLIER_KEY_SIZE);
 
         Self {
             u_i: u,
             y_i: y,
-            dk,
+            dk: precompute(dk),
             ek,
             party_index: index,
         }
     }
 
     // we recommend using safe primes if the code is used in production
-    pub fn create_safe_prime(index: u16) -> Keys {
-        let u = Scalar::<Secp256k1>::random();
+    pub fn create_safe_prime<R: RngCore + CryptoRng>(rng: &mut R, index: u16) -> Keys {
+        let u = sample_scalar(rng);
         let y = Point::generator() * &u;
 
-        let (ek, dk) = Paillier::keypair_safe_primes().keys();
+        let (ek, dk) = paillier_keypair_safe_primes(rng, PAILLIER_KEY_SIZE).keys();
 
         Keys {
             u_i: u,
             y_i: y,
-            dk,
+            dk: precompute(dk),
             ek,
             party_index: index,
         }
     }
-    pub fn create_from(u: Scalar<Secp256k1>, index: u16) -> Keys {
+    pub fn create_from<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        u: Scalar<Secp256k1>,
+        index: u16,
+    ) -> Keys {
         let y = Point::generator() * &u;
-        let (ek, dk) = Paillier::keypair().keys();
+        let (ek, dk) = paillier_keypair(rng, PAILLIER_KEY_SIZE);
 
         Self {
             u_i: u,
             y_i: y,
-            dk,
+            dk: precompute(dk),
             ek,
             party_index: index,
         }
     }
 
-    pub fn phase1_broadcast_phase3_proof_of_correct_key(
+    /// Same as [create](Self::create), but takes Paillier key pair generated in advance
+    ///
+    /// Returns error if `dk` is malformed.
+    pub fn create_with_paillier_key<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ek: EncryptionKey,
+        dk: DecryptionKey,
+        index: u16,
+    ) -> Result<Keys, InvalidDecryptionKey> {
+        let dk = PrecomputedDecryptionKey::new(dk)?;
+        let u = sample_scalar(rng);
+        let y = Point::generator() * &u;
+
+        Ok(Keys {
+            u_i: u,
+            y_i: y,
+            dk,
+            ek,
+            party_index: index,
+        })
+    }
+
+    pub fn phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2<
+        R: RngCore + CryptoRng,
+    >(
         &self,
+        rng: &mut R,
+        ctx: &ProofContext,
+        dlog_setup: &DLogSetup,
     ) -> (KeyGenBroadcastMessage1, KeyGenDecommitMessage1) {
-        let blind_factor = BigInt::sample(SECURITY);
-        let correct_key_proof = NiCorrectKeyProof::proof(&self.dk, None);
+        let blind_factor = sample_bits(rng, SECURITY);
+        let correct_key_proof =
+            NiCorrectKeyProof::proof(&self.dk, Some(BigInt::from_bytes(&ctx.salt())));
+        let (composite_dlog_proof_base_h1, composite_dlog_proof_base_h2) =
+            dlog_setup.prove(rng, ctx);
         let com = HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
             &BigInt::from_bytes(self.y_i.to_bytes(true).as_ref()),
             &blind_factor,
         );
         let bcm1 = KeyGenBroadcastMessage1 {
             e: self.ek.clone(),
+            dlog_statement: dlog_setup.statement(),
             com,
             correct_key_proof,
+            composite_dlog_proof_base_h1,
+            composite_dlog_proof_base_h2,
         };
         let decom1 = KeyGenDecommitMessage1 {
             blind_factor,
-            y_i: self.y_i.clone(),
+            y_i: NonZeroPoint::new(self.y_i.clone(), "y_i")
+                .expect("public key of random secret is not zero"),
         };
         (bcm1, decom1)
     }
 
     #[allow(clippy::type_complexity)]
-    pub fn phase1_verify_com_phase3_verify_correct_key_phase2_distribute(
+    pub fn phase1_verify_com_phase3_verify_correct_key_phase2_distribute<R: RngCore + CryptoRng>(
         &self,
+        rng: &mut R,
+        ctx: &ProofContext,
         params: &Parameters,
         decom_vec: &[KeyGenDecommitMessage1],
         bc1_vec: &[KeyGenBroadcastMessage1],
@@ -236,21 +576,44 @@ impl Keys {
         assert_eq!(decom_vec.len(), usize::from(params.share_count));
         assert_eq!(bc1_vec.len(), usize::from(params.share_count));
         // test paillier correct key and test decommitments
-        let correct_key_correct_decom_all = (0..bc1_vec.len()).all(|i| {
-            HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
-                &BigInt::from_bytes(decom_vec[i].y_i.to_bytes(true).as_ref()),
-                &decom_vec[i].blind_factor,
-            ) == bc1_vec[i].com
-                && bc1_vec[i]
-                    .correct_key_proof
-                    .verify(&bc1_vec[i].e, zk_paillier::zkproofs::SALT_STRING)
-                    .is_ok()
-        });
+        let correct_key_correct_decom_all =
+            Self::verify_com_and_correct_key(ctx, decom_vec, bc1_vec);
 
         let (vss_scheme, secret_shares) =
-            VerifiableSS::share(params.threshold, params.share_count, &self.u_i);
+            AccessStructure::threshold(params.threshold, params.share_count).share(rng, &self.u_i);
         if correct_key_correct_decom_all {
-            Ok((vss_scheme, secret_shares.to_vec(), self.party_index))
+            let secret_shares = secret_shares.into_iter().flatten().collect();
+            Ok((vss_scheme, secret_shares, self.party_index))
+        } else {
+            let err_type = ErrorType {
+                error_type: "invalid key".to_string()
+            };
+            Err(err_type)
+        }
+    }
+
+    /// Same as [phase1_verify_com_phase3_verify_correct_key_phase2_distribute](Self::phase1_verify_com_phase3_verify_correct_key_phase2_distribute),
+    /// but shares `u_i` according to `access_structure`
+    ///
+    /// Returned shares are grouped per party, every group is aligned with party's share slots.
+    #[allow(clippy::type_complexity)]
+    pub fn phase1_verify_com_phase3_verify_correct_key_phase2_distribute_shares<
+        R: RngCore + CryptoRng,
+    >(
+        &self,
+        rng: &mut R,
+        ctx: &ProofContext,
+        access_structure: &AccessStructure,
+        decom_vec: &[KeyGenDecommitMessage1],
+        bc1_vec: &[KeyGenBroadcastMessage1],
+    ) -> Result<(VerifiableSS<Secp256k1>, Vec<Vec<Scalar<Secp256k1>>>, u16), ErrorType> {
+        // test length:
+        assert_eq!(decom_vec.len(), usize::from(access_structure.parties()));
+        assert_eq!(bc1_vec.len(), usize::from(access_structure.parties()));
+        // test paillier correct key and test decommitments
+        if Self::verify_com_and_correct_key(ctx, decom_vec, bc1_vec) {
+            let (vss_scheme, secret_shares) = access_structure.share(rng, &self.u_i);
+            Ok((vss_scheme, secret_shares, self.party_index))
         } else {
             let err_type = ErrorType {
                 error_type: "invalid key".to_string()
@@ -259,14 +622,45 @@ impl Keys {
         }
     }
 
-    pub fn phase2_verify_vss_construct_keypair_phase3_pok_dlog(
+    /// `decom_vec` and `bc1_vec` must be ordered by party index, proofs of `i`-th message are
+    /// verified in context of party `i + 1`
+    fn verify_com_and_correct_key(
+        ctx: &ProofContext,
+        decom_vec: &[KeyGenDecommitMessage1],
+        bc1_vec: &[KeyGenBroadcastMessage1],
+    ) -> bool {
+        parallel::all(bc1_vec.len(), |i| {
+            let party_ctx = match u16::try_from(i + 1) {
+                Ok(party_index) => ctx.of_party(party_index),
+                Err(_) => return false,
+            };
+            HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
+                &BigInt::from_bytes(decom_vec[i].y_i.to_bytes(true).as_ref()),
+                &decom_vec[i].blind_factor,
+            ) == bc1_vec[i].com
+                && bc1_vec[i]
+                    .correct_key_proof
+                    .verify(&bc1_vec[i].e, &party_ctx.salt())
+                    .is_ok()
+                && DLogSetup::verify_proofs(
+                    &party_ctx,
+                    &bc1_vec[i].dlog_statement,
+                    &bc1_vec[i].composite_dlog_proof_base_h1,
+                    &bc1_vec[i].composite_dlog_proof_base_h2,
+                )
+        })
+    }
+
+    pub fn phase2_verify_vss_construct_keypair_phase3_pok_dlog<R: RngCore + CryptoRng>(
         &self,
+        rng: &mut R,
+        ctx: &ProofContext,
         params: &Parameters,
         y_vec: &[Point<Secp256k1>],
         secret_shares_vec: &[Scalar<Secp256k1>],
         vss_scheme_vec: &[VerifiableSS<Secp256k1>],
         index: u16,
-    ) -> Result<(SharedKeys, DLogProof<Secp256k1, Sha256>), ErrorType> {
+    ) -> Result<(SharedKeys, DLogProof), ErrorType> {
         assert_eq!(y_vec.len(), usize::from(params.share_count));
         assert_eq!(secret_shares_vec.len(), usize::from(params.share_count));
         assert_eq!(vss_scheme_vec.len(), usize::from(params.share_count));
@@ -281,8 +675,60 @@ impl Keys {
         if correct_ss_verify {
             let y: Point<Secp256k1> = y_vec.iter().sum();
             let x_i: Scalar<Secp256k1> = secret_shares_vec.iter().sum();
-            let dlog_proof = DLogProof::prove(&x_i);
-            Ok((SharedKeys { y, x_i }, dlog_proof))
+            let dlog_proof = DLogProof::prove(rng, ctx, &x_i);
+            Ok((
+                SharedKeys {
+                    y,
+                    x_i,
+                    extra_x_i: vec![],
+                },
+                dlog_proof,
+            ))
+        } else {
+            let err_type = ErrorType {
+                error_type: "invalid vss".to_string(),
+            };
+            Err(err_type)
+        }
+    }
+
+    /// Same as [phase2_verify_vss_construct_keypair_phase3_pok_dlog](Self::phase2_verify_vss_construct_keypair_phase3_pok_dlog),
+    /// but verifies shares of every slot party `index` holds in `access_structure`
+    pub fn phase2_verify_shares_construct_keypair_phase3_pok_dlog<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        ctx: &ProofContext,
+        access_structure: &AccessStructure,
+        y_vec: &[Point<Secp256k1>],
+        secret_shares_vec: &[Vec<Scalar<Secp256k1>>],
+        vss_scheme_vec: &[VerifiableSS<Secp256k1>],
+        index: u16,
+    ) -> Result<(SharedKeys, DLogProof), ErrorType> {
+        assert_eq!(y_vec.len(), usize::from(access_structure.parties()));
+        assert_eq!(secret_shares_vec.len(), usize::from(access_structure.parties()));
+        assert_eq!(vss_scheme_vec.len(), usize::from(access_structure.parties()));
+
+        let correct_ss_verify = (0..y_vec.len()).all(|i| {
+            access_structure.validate_shares(&vss_scheme_vec[i], index, &secret_shares_vec[i])
+                && vss_scheme_vec[i].commitments.first() == Some(&y_vec[i])
+        });
+
+        if correct_ss_verify {
+            let y: Point<Secp256k1> = y_vec.iter().sum();
+            let slots = access_structure.slots[usize::from(index - 1)].len();
+            let mut x_vec: Vec<Scalar<Secp256k1>> = (0..slots)
+                .map(|k| secret_shares_vec.iter().map(|shares| &shares[k]).sum())
+                .collect();
+            let x_i = x_vec.remove(0);
+            let dlog_proof = DLogProof::prove(rng, ctx, &x_i);
+            Ok((
+                SharedKeys {
+                    y,
+                    x_i,
+                    extra_x_i: x_vec,
+                },
+                dlog_proof,
+            ))
         } else {
             let err_type = ErrorType {
                 error_type: "invalid vss".to_string(),
@@ -315,16 +761,21 @@ impl Keys {
         comm * &li
     }
 
+    /// Proof at position `i` is verified in context of party `i + 1`
     pub fn verify_dlog_proofs(
+        ctx: &ProofContext,
         params: &Parameters,
-        dlog_proofs_vec: &[DLogProof<Secp256k1, Sha256>],
+        dlog_proofs_vec: &[DLogProof],
         y_vec: &[Point<Secp256k1>],
     ) -> Result<(), ErrorType> {
         assert_eq!(y_vec.len(), usize::from(params.share_count));
         assert_eq!(dlog_proofs_vec.len(), usize::from(params.share_count));
 
-        let xi_dlog_verify =
-            (0..y_vec.len()).all(|i| DLogProof::verify(&dlog_proofs_vec[i]).is_ok());
+        let xi_dlog_verify = parallel::all(dlog_proofs_vec.len(), |i| {
+            u16::try_from(i + 1)
+                .map(|party_index| dlog_proofs_vec[i].verify(&ctx.of_party(party_index)).is_ok())
+                .unwrap_or(false)
+        });
 
         if xi_dlog_verify {
             Ok(())
@@ -338,11 +789,12 @@ impl Keys {
 }
 
 impl PartyPrivate {
-    pub fn set_private(key: Keys, shared_key: SharedKeys) -> Self {
+    pub fn set_private(key: &Keys, shared_key: &SharedKeys) -> Self {
         Self {
-            u_i: key.u_i,
-            x_i: shared_key.x_i,
-            dk: key.dk,
+            u_i: key.u_i.clone(),
+            x_i: shared_key.x_i.clone(),
+            extra_x_i: shared_key.extra_x_i.clone(),
+            dk: key.dk.clone(),
         }
     }
 
@@ -351,33 +803,43 @@ impl PartyPrivate {
     }
 
     pub fn decrypt(&self, ciphertext: BigInt) -> RawPlaintext {
-        Paillier::decrypt(&self.dk, &RawCiphertext::from(ciphertext))
+        RawPlaintext::from(self.dk.decrypt(&ciphertext))
     }
 
-    pub fn refresh_private_key(&self, factor: &Scalar<Secp256k1>, index: u16) -> Keys {
+    pub fn refresh_private_key<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        factor: &Scalar<Secp256k1>,
+        index: u16,
+    ) -> Keys {
         let u: Scalar<Secp256k1> = &self.u_i + factor;
         let y = Point::generator() * &u;
-        let (ek, dk) = Paillier::keypair().keys();
+        let (ek, dk) = paillier_keypair(rng, PAILLIER_KEY_SIZE);
 
         Keys {
             u_i: u,
             y_i: y,
-            dk,
+            dk: precompute(dk),
             ek,
             party_index: index,
         }
     }
 
     // we recommend using safe primes if the code is used in production
-    pub fn refresh_private_key_safe_prime(&self, factor: &Scalar<Secp256k1>, index: u16) -> Keys {
+    pub fn refresh_private_key_safe_prime<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        factor: &Scalar<Secp256k1>,
+        index: u16,
+    ) -> Keys {
         let u: Scalar<Secp256k1> = &self.u_i + factor;
         let y = Point::generator() * &u;
-        let (ek, dk) = Paillier::keypair_safe_primes().keys();
+        let (ek, dk) = paillier_keypair_safe_primes(rng, PAILLIER_KEY_SIZE).keys();
 
         Keys {
             u_i: u,
             y_i: y,
-            dk,
+            dk: precompute(dk),
             ek,
             party_index: index,
         }
@@ -402,13 +864,15 @@ impl PartyPrivate {
         PartyPrivate {
             u_i: &self.u_i + factor_u_i,
             x_i: &self.x_i + factor_x_i,
+            extra_x_i: self.extra_x_i.clone(),
             dk: self.dk.clone(),
         }
     }
 }
 
 impl SignKeys {
-    pub fn create(
+    pub fn create<R: RngCore + CryptoRng>(
+        rng: &mut R,
         private: &PartyPrivate,
         vss_scheme: &VerifiableSS<Secp256k1>,
         index: u16,
@@ -416,23 +880,55 @@ impl SignKeys {
     ) -> Self {
         let li =
             VerifiableSS::<Secp256k1>::map_share_to_new_params(&vss_scheme.parameters, index, s);
-        let w_i = li * &private.x_i;
+        Self::from_w_i(rng, li * &private.x_i)
+    }
+
+    /// Same as [create](Self::create), but takes interpolation coefficients from
+    /// `access_structure`, so it works for weighted and hierarchical structures as well
+    ///
+    /// `index` and `s` are parties indexes in range `[0; n)`. Returns error if `s` is not
+    /// authorized to sign.
+    pub fn create_with_access_structure<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        private: &PartyPrivate,
+        access_structure: &AccessStructure,
+        index: u16,
+        s: &[u16],
+    ) -> Result<Self, AccessStructureError> {
+        let coefficients = access_structure.signer_coefficients(index, s)?;
+        if coefficients.len() != private.extra_x_i.len() + 1 {
+            return Err(AccessStructureError::MismatchedKey(
+                "number of shares doesn't match party's slots",
+            ));
+        }
+        let w_i = std::iter::once(&private.x_i)
+            .chain(&private.extra_x_i)
+            .zip(&coefficients)
+            .map(|(x_i, l_i)| l_i * x_i)
+            .sum();
+        Ok(Self::from_w_i(rng, w_i))
+    }
+
+    fn from_w_i<R: RngCore + CryptoRng>(rng: &mut R, w_i: Scalar<Secp256k1>) -> Self {
         let g = Point::generator();
         let g_w_i = g * &w_i;
-        let gamma_i = Scalar::<Secp256k1>::random();
+        let gamma_i = sample_scalar(rng);
         let g_gamma_i = g * &gamma_i;
 
         Self {
             w_i,
             g_w_i,
-            k_i: Scalar::<Secp256k1>::random(),
+            k_i: sample_scalar(rng),
             gamma_i,
             g_gamma_i,
         }
     }
 
-    pub fn phase1_broadcast(&self) -> (SignBroadcastPhase1, SignDecommitPhase1) {
-        let blind_factor = BigInt::sample(SECURITY);
+    pub fn phase1_broadcast<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+    ) -> (SignBroadcastPhase1, SignDecommitPhase1) {
+        let blind_factor = sample_bits(rng, SECURITY);
         let g = Point::generator();
         let g_gamma_i = g * &self.gamma_i;
         let com = HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
@@ -444,7 +940,8 @@ impl SignKeys {
             SignBroadcastPhase1 { com },
             SignDecommitPhase1 {
                 blind_factor,
-                g_gamma_i: self.g_gamma_i.clone(),
+                g_gamma_i: NonZeroPoint::new(self.g_gamma_i.clone(), "g_gamma_i")
+                    .expect("g_gamma_i of random gamma_i is not zero"),
             },
         )
     }
@@ -453,52 +950,71 @@ impl SignKeys {
         &self,
         alpha_vec: &[Scalar<Secp256k1>],
         beta_vec: &[Scalar<Secp256k1>],
-    ) -> Scalar<Secp256k1> {
-        assert_eq!(alpha_vec.len(), beta_vec.len());
+    ) -> Result<Scalar<Secp256k1>, Error> {
+        if alpha_vec.len() != beta_vec.len() {
+            return Err(InvalidKey);
+        }
         let ki_gamma_i = &self.k_i * &self.gamma_i;
-        ki_gamma_i + alpha_vec.iter().chain(beta_vec).sum::<Scalar<Secp256k1>>()
+        Ok(ki_gamma_i + alpha_vec.iter().chain(beta_vec).sum::<Scalar<Secp256k1>>())
     }
 
     pub fn phase2_sigma_i(
         &self,
         miu_vec: &[Scalar<Secp256k1>],
         ni_vec: &[Scalar<Secp256k1>],
-    ) -> Scalar<Secp256k1> {
-        assert_eq!(miu_vec.len(), ni_vec.len());
+    ) -> Result<Scalar<Secp256k1>, Error> {
+        if miu_vec.len() != ni_vec.len() {
+            return Err(InvalidKey);
+        }
         let ki_w_i = &self.k_i * &self.w_i;
-        ki_w_i + miu_vec.iter().chain(ni_vec).sum::<Scalar<Secp256k1>>()
+        Ok(ki_w_i + miu_vec.iter().chain(ni_vec).sum::<Scalar<Secp256k1>>())
     }
 
-    pub fn phase3_reconstruct_delta(delta_vec: &[Scalar<Secp256k1>]) -> Scalar<Secp256k1> {
+    /// Returns error if deltas sum up to zero
+    pub fn phase3_reconstruct_delta(
+        delta_vec: &[Scalar<Secp256k1>],
+    ) -> Result<Scalar<Secp256k1>, Error> {
         delta_vec
             .iter()
             .sum::<Scalar<Secp256k1>>()
             .invert()
-            .expect("sum of deltas is zero")
+            .ok_or(InvalidKey)
+    }
+
+    /// Checks decommitment of a single party: `g_gamma_i` opens the commitment and matches the
+    /// one proven in MtA
+    pub fn phase4_check(
+        b_proof: &DLogProof,
+        phase1_decommit: &SignDecommitPhase1,
+        bc1: &SignBroadcastPhase1,
+    ) -> bool {
+        b_proof.pk == phase1_decommit.g_gamma_i
+            && HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
+                &BigInt::from_bytes(phase1_decommit.g_gamma_i.to_bytes(true).as_ref()),
+                &phase1_decommit.blind_factor,
+            ) == bc1.com
     }
 
     pub fn phase4(
         delta_inv: &Scalar<Secp256k1>,
-        b_proof_vec: &[&DLogProof<Secp256k1, Sha256>],
+        b_proof_vec: &[&DLogProof],
         phase1_decommit_vec: Vec<SignDecommitPhase1>,
         bc1_vec: &[SignBroadcastPhase1],
     ) -> Result<Point<Secp256k1>, Error> {
         // note: b_proof_vec is populated using the results
         //from the MtAwc, which is handling the proof of knowledge verification of gamma_i such that
         // Gamme_i = gamma_i * G in the verify_proofs_get_alpha()
-        let test_b_vec_and_com = (0..b_proof_vec.len()).all(|i| {
-            b_proof_vec[i].pk == phase1_decommit_vec[i].g_gamma_i
-                && HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
-                    &BigInt::from_bytes(phase1_decommit_vec[i].g_gamma_i.to_bytes(true).as_ref()),
-                    &phase1_decommit_vec[i].blind_factor,
-                ) == bc1_vec[i].com
-        });
+        let test_b_vec_and_com = b_proof_vec.len() == phase1_decommit_vec.len()
+            && b_proof_vec.len() == bc1_vec.len()
+            && (0..b_proof_vec.len()).all(|i| {
+                Self::phase4_check(b_proof_vec[i], &phase1_decommit_vec[i], &bc1_vec[i])
+            });
 
         if test_b_vec_and_com {
             Ok({
                 let gamma_sum: Point<Secp256k1> = phase1_decommit_vec
                     .iter()
-                    .map(|decom| &decom.g_gamma_i)
+                    .map(|decom| decom.g_gamma_i.as_point())
                     .sum();
                 // R
                 gamma_sum * delta_inv
@@ -510,7 +1026,8 @@ impl SignKeys {
 }
 
 impl LocalSignature {
-    pub fn phase5_local_sig(
+    pub fn phase5_local_sig<R: RngCore + CryptoRng>(
+        rng: &mut R,
         k_i: &Scalar<Secp256k1>,
         message: &BigInt,
         R: &Point<Secp256k1>,
@@ -524,8 +1041,8 @@ impl LocalSignature {
                 .mod_floor(Scalar::<Secp256k1>::group_order()),
         );
         let s_i = m_fe * k_i + r * sigma_i;
-        let l_i = Scalar::<Secp256k1>::random();
-        let rho_i = Scalar::<Secp256k1>::random();
+        let l_i = sample_scalar(rng);
+        let rho_i = sample_scalar(rng);
         Self {
             l_i,
             rho_i,
@@ -536,15 +1053,12 @@ impl LocalSignature {
         }
     }
 
-    pub fn phase5a_broadcast_5b_zkproof(
+    pub fn phase5a_broadcast_5b_zkproof<R: RngCore + CryptoRng>(
         &self,
-    ) -> (
-        Phase5Com1,
-        Phase5ADecom1,
-        HomoELGamalProof<Secp256k1, Sha256>,
-        DLogProof<Secp256k1, Sha256>,
-    ) {
-        let blind_factor = BigInt::sample(SECURITY);
+        rng: &mut R,
+        ctx: &ProofContext,
+    ) -> (Phase5Com1, Phase5ADecom1, HomoELGamalProof, DLogProof) {
+        let blind_factor = sample_bits(rng, SECURITY);
         let g = Point::generator();
         let A_i = g * &self.rho_i;
         let l_i_rho_i = &self.l_i * &self.rho_i;
@@ -568,15 +1082,16 @@ impl LocalSignature {
             D: V_i.clone(),
             E: B_i.clone(),
         };
-        let dlog_proof_rho = DLogProof::prove(&self.rho_i);
-        let proof = HomoELGamalProof::prove(&witness, &delta);
+        let dlog_proof_rho = DLogProof::prove(rng, ctx, &self.rho_i);
+        let proof = HomoELGamalProof::prove(rng, ctx, &witness, &delta);
+        let non_zero = "points computed from random l_i and rho_i are not zero";
 
         (
             Phase5Com1 { com },
             Phase5ADecom1 {
-                V_i,
-                A_i,
-                B_i,
+                V_i: NonZeroPoint::new(V_i, "V_i").expect(non_zero),
+                A_i: NonZeroPoint::new(A_i, "A_i").expect(non_zero),
+                B_i: NonZeroPoint::new(B_i, "B_i").expect(non_zero),
                 blind_factor,
             },
             proof,
@@ -584,41 +1099,70 @@ impl LocalSignature {
         )
     }
 
-    pub fn phase5c(
+    /// Checks commitment and proofs sent by a single party in phase 5A/5B
+    pub fn phase5c_check(
+        ctx: &ProofContext,
+        decom: &Phase5ADecom1,
+        com: &Phase5Com1,
+        elgamal_proof: &HomoELGamalProof,
+        dlog_proof_rho: &DLogProof,
+        R: &Point<Secp256k1>,
+    ) -> bool {
+        let delta = HomoElGamalStatement {
+            G: decom.A_i.as_point().clone(),
+            H: R.clone(),
+            Y: Point::generator().to_point(),
+            D: decom.V_i.as_point().clone(),
+            E: decom.B_i.as_point().clone(),
+        };
+
+        let input_hash = Sha256::new()
+            .chain_points([decom.V_i.as_point(), decom.A_i.as_point(), decom.B_i.as_point()])
+            .result_bigint();
+
+        HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
+            &input_hash,
+            &decom.blind_factor,
+        ) == com.com
+            && elgamal_proof.verify(ctx, &delta).is_ok()
+            && dlog_proof_rho.verify(ctx).is_ok()
+    }
+
+    /// `contexts[i]` is the context of party that sent `i`-th message
+    #[allow(clippy::too_many_arguments)]
+    pub fn phase5c<R: RngCore + CryptoRng>(
         &self,
+        rng: &mut R,
+        contexts: &[ProofContext],
         decom_vec: &[Phase5ADecom1],
         com_vec: &[Phase5Com1],
-        elgamal_proofs: &[HomoELGamalProof<Secp256k1, Sha256>],
-        dlog_proofs_rho: &[DLogProof<Secp256k1, Sha256>],
+        elgamal_proofs: &[HomoELGamalProof],
+        dlog_proofs_rho: &[DLogProof],
         v_i: &Point<Secp256k1>,
         R: &Point<Secp256k1>,
     ) -> Result<(Phase5Com2, Phase5DDecom2), Error> {
-        assert_eq!(decom_vec.len(), com_vec.len());
-
-        let g = Point::generator();
-        let test_com_elgamal = (0..com_vec.len()).all(|i| {
-            let delta = HomoElGamalStatement {
-                G: decom_vec[i].A_i.clone(),
-                H: R.clone(),
-                Y: g.to_point(),
-                D: decom_vec[i].V_i.clone(),
-                E: decom_vec[i].B_i.clone(),
-            };
-
-            let input_hash = Sha256::new()
-                .chain_points([&decom_vec[i].V_i, &decom_vec[i].A_i, &decom_vec[i].B_i])
-                .result_bigint();
+        let n = decom_vec.len();
+        if com_vec.len() != n
+            || contexts.len() != n
+            || elgamal_proofs.len() != n
+            || dlog_proofs_rho.len() != n
+        {
+            return Err(InvalidCom);
+        }
 
-            HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
-                &input_hash,
-                &decom_vec[i].blind_factor,
-            ) == com_vec[i].com
-                && elgamal_proofs[i].verify(&delta).is_ok()
-                && DLogProof::verify(&dlog_proofs_rho[i]).is_ok()
+        let test_com_elgamal = parallel::all(n, |i| {
+            Self::phase5c_check(
+                &contexts[i],
+                &decom_vec[i],
+                &com_vec[i],
+                &elgamal_proofs[i],
+                &dlog_proofs_rho[i],
+                R,
+            )
         });
 
-        let v_iter = (0..com_vec.len()).map(|i| &decom_vec[i].V_i);
-        let a_iter = (0..com_vec.len()).map(|i| &decom_vec[i].A_i);
+        let v_iter = (0..n).map(|i| decom_vec[i].V_i.as_point());
+        let a_iter = (0..n).map(|i| decom_vec[i].A_i.as_point());
 
         let v = v_i + v_iter.sum::<Point<Secp256k1>>();
         // V = -mG -ry - sum (vi)
@@ -636,10 +1180,13 @@ impl LocalSignature {
         let m_fe = Scalar::<Secp256k1>::from(&self.m);
         let gm = g * m_fe;
         let v = v - &gm - &yr;
-        let u_i = v * &self.rho_i;
-        let t_i = a * &self.l_i;
-        let input_hash = Sha256::new().chain_points([&u_i, &t_i]).result_bigint();
-        let blind_factor = BigInt::sample(SECURITY);
+        // other parties could have chosen V_j, A_j that make them zero
+        let u_i = NonZeroPoint::new(v * &self.rho_i, "u_i").map_err(|_| InvalidKey)?;
+        let t_i = NonZeroPoint::new(a * &self.l_i, "t_i").map_err(|_| InvalidKey)?;
+        let input_hash = Sha256::new()
+            .chain_points([u_i.as_point(), t_i.as_point()])
+            .result_bigint();
+        let blind_factor = sample_bits(rng, SECURITY);
         let com = HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
             &input_hash,
             &blind_factor,
@@ -661,28 +1208,33 @@ impl LocalSignature {
         }
     }
 
+    /// Checks that a single party's phase 5D decommitment opens its commitment
+    pub fn phase5d_check(decom2: &Phase5DDecom2, com2: &Phase5Com2) -> bool {
+        let input_hash = Sha256::new()
+            .chain_points([decom2.u_i.as_point(), decom2.t_i.as_point()])
+            .result_bigint();
+        HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
+            &input_hash,
+            &decom2.blind_factor,
+        ) == com2.com
+    }
+
     pub fn phase5d(
         &self,
         decom_vec2: &[Phase5DDecom2],
         com_vec2: &[Phase5Com2],
         decom_vec1: &[Phase5ADecom1],
     ) -> Result<Scalar<Secp256k1>, Error> {
-        assert_eq!(decom_vec2.len(), decom_vec1.len());
-        assert_eq!(decom_vec2.len(), com_vec2.len());
+        if decom_vec2.len() != decom_vec1.len() || decom_vec2.len() != com_vec2.len() {
+            return Err(InvalidCom);
+        }
 
-        let test_com = (0..com_vec2.len()).all(|i| {
-            let input_hash = Sha256::new()
-                .chain_points([&decom_vec2[i].u_i, &decom_vec2[i].t_i])
-                .result_bigint();
-            HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
-                &input_hash,
-                &decom_vec2[i].blind_factor,
-            ) == com_vec2[i].com
-        });
+        let test_com =
+            (0..com_vec2.len()).all(|i| Self::phase5d_check(&decom_vec2[i], &com_vec2[i]));
 
-        let t_iter = decom_vec2.iter().map(|decom| &decom.t_i);
-        let u_iter = decom_vec2.iter().map(|decom| &decom.u_i);
-        let b_iter = decom_vec1.iter().map(|decom| &decom.B_i);
+        let t_iter = decom_vec2.iter().map(|decom| decom.t_i.as_point());
+        let u_iter = decom_vec2.iter().map(|decom| decom.u_i.as_point());
+        let b_iter = decom_vec1.iter().map(|decom| decom.B_i.as_point());
 
         let g = Point::generator();
         let biased_sum_tb = g + t_iter.chain(b_iter).sum::<Point<Secp256k1>>();
@@ -697,6 +1249,22 @@ impl LocalSignature {
             Err(InvalidCom)
         }
     }
+    pub fn partial_signature(&self) -> PartialSignature {
+        PartialSignature {
+            s_i: self.s_i.clone(),
+            l_i: self.l_i.clone(),
+        }
+    }
+
+    /// Checks that `partial` opens `V_i` sent by the same party in phase 5A
+    pub fn phase7_check(
+        partial: &PartialSignature,
+        decom: &Phase5ADecom1,
+        R: &Point<Secp256k1>,
+    ) -> bool {
+        R * &partial.s_i + Point::generator() * &partial.l_i == *decom.V_i.as_point()
+    }
+
     pub fn output_signature(&self, s_vec: &[Scalar<Secp256k1>]) -> Result<SignatureRecid, Error> {
         let mut s = &self.s_i + s_vec.iter().sum::<Scalar<Secp256k1>>();
         let s_bn = s.to_bigint();
@@ -796,4 +1364,39 @@ pub fn check_sig(
 
     let is_correct = verify(&msg, &secp_sig, &pk);
     assert!(is_correct);
-}*/
\ No newline at end of file
+}*/
+#[cfg(test)]
+mod test {
+    use rand::rngs::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn debug_output_redacts_secrets() {
+        let keys = Keys::create(&mut OsRng, 1);
+        let shared_keys = SharedKeys {
+            y: keys.y_i.clone(),
+            x_i: Scalar::random(),
+            extra_x_i: vec![],
+        };
+        let private = PartyPrivate::set_private(&keys, &shared_keys);
+
+        let secrets = [
+            format!("{:?}", keys.u_i),
+            format!("{:?}", keys.dk.p),
+            format!("{:?}", keys.dk.q),
+            format!("{:?}", shared_keys.x_i),
+        ];
+        let printed = [
+            format!("{:?}", keys),
+            format!("{:?}", shared_keys),
+            format!("{:?}", private),
+        ];
+        for printed in &printed {
+            assert!(printed.contains("[redacted]"));
+            for secret in &secrets {
+                assert!(!printed.contains(secret.as_str()), "{}", printed);
+            }
+        }
+    }
+}
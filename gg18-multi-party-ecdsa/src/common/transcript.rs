@@ -0,0 +1,70 @@
+//! Binding of zero-knowledge proofs to a protocol session
+//!
+//! Fiat-Shamir challenge of every non-interactive proof used in keygen, key import and signing
+//! absorbs a [ProofContext]: id of the session (computation id that parties agreed on before
+//! starting the protocol) and index of the party who produces the proof. Proof taken from
+//! another session, or replayed on behalf of another party, doesn't verify.
+//!
+//! Interactive [zk_pdl](crate::utilities::zk_pdl) doesn't need that: its challenge is chosen by
+//! the verifier.
+
+use curv::cryptographic_primitives::hashing::Digest;
+use sha2::Sha256;
+
+/// Domain separation tag prepended to the context in every challenge
+const DOMAIN_SEPARATION_TAG: &[u8] = b"gg18-multi-party-ecdsa/proof-context/v1";
+
+/// Identifies a run of the protocol, e.g. computation id used by `mpc-over-signal`
+pub type SessionId = [u8; 32];
+
+/// Session id and index of the prover that every proof is bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProofContext {
+    pub session_id: SessionId,
+    pub party_index: u16,
+}
+
+impl ProofContext {
+    pub fn new(session_id: SessionId, party_index: u16) -> Self {
+        Self {
+            session_id,
+            party_index,
+        }
+    }
+
+    /// Context of another party of the same session, used to verify proofs it produced
+    pub fn of_party(&self, party_index: u16) -> Self {
+        Self::new(self.session_id, party_index)
+    }
+
+    /// Absorbs the context into the hash computing a challenge
+    pub fn chain<D: Digest>(&self, hasher: D) -> D {
+        hasher
+            .chain(DOMAIN_SEPARATION_TAG)
+            .chain(&self.session_id)
+            .chain(&self.party_index.to_be_bytes())
+    }
+
+    /// Context compressed into 32 bytes
+    ///
+    /// Serves as salt for proofs implemented in other crates that take one, like
+    /// `NiCorrectKeyProof`.
+    pub fn salt(&self) -> [u8; 32] {
+        let mut salt = [0u8; 32];
+        salt.copy_from_slice(&self.chain(Sha256::new()).finalize());
+        salt
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn salt_depends_on_session_and_party() {
+        let ctx = ProofContext::new([1; 32], 1);
+        assert_eq!(ctx.salt(), ProofContext::new([1; 32], 1).salt());
+        assert_ne!(ctx.salt(), ctx.of_party(2).salt());
+        assert_ne!(ctx.salt(), ProofContext::new([2; 32], 1).salt());
+    }
+}
@@ -0,0 +1,162 @@
+//! Values received from other parties that have to be validated before use
+//!
+//! Curve points and Paillier ciphertexts sent in protocol messages are wrapped into
+//! [NonZeroPoint] and [PaillierCiphertext]. Wrappers are serialized exactly as the values they
+//! wrap, and deserialization doesn't check anything: keygen and signing state machines validate
+//! every received message in `handle_incoming`, where it's known which party sent it.
+
+use std::ops::Deref;
+
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point};
+use curv::BigInt;
+use paillier::EncryptionKey;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Reason why received value was rejected
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum InvalidValue {
+    #[error("{0} is the point at infinity")]
+    ZeroPoint(&'static str),
+    #[error("{0} is not in Z*_{{N^2}}")]
+    CiphertextOutOfRange(&'static str),
+    #[error("{0} is out of range")]
+    IndexOutOfRange(&'static str),
+}
+
+/// Curve point which is not the point at infinity
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NonZeroPoint(Point<Secp256k1>);
+
+impl NonZeroPoint {
+    /// Wraps a point, `name` is used in the error
+    pub fn new(point: Point<Secp256k1>, name: &'static str) -> Result<Self, InvalidValue> {
+        let point = Self(point);
+        point.validate(name)?;
+        Ok(point)
+    }
+
+    /// Checks deserialized point
+    pub fn validate(&self, name: &'static str) -> Result<(), InvalidValue> {
+        validate_point(&self.0, name)
+    }
+
+    pub fn as_point(&self) -> &Point<Secp256k1> {
+        &self.0
+    }
+
+    pub fn into_point(self) -> Point<Secp256k1> {
+        self.0
+    }
+}
+
+impl Deref for NonZeroPoint {
+    type Target = Point<Secp256k1>;
+
+    fn deref(&self) -> &Point<Secp256k1> {
+        &self.0
+    }
+}
+
+impl PartialEq<Point<Secp256k1>> for NonZeroPoint {
+    fn eq(&self, other: &Point<Secp256k1>) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<NonZeroPoint> for Point<Secp256k1> {
+    fn eq(&self, other: &NonZeroPoint) -> bool {
+        *self == other.0
+    }
+}
+
+/// Checks that a point which is not wrapped into [NonZeroPoint] (e.g. a point inside a proof) is
+/// not the point at infinity
+pub fn validate_point(point: &Point<Secp256k1>, name: &'static str) -> Result<(), InvalidValue> {
+    if point.is_zero() {
+        Err(InvalidValue::ZeroPoint(name))
+    } else {
+        Ok(())
+    }
+}
+
+/// Paillier ciphertext, element of `Z*_{N^2}`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PaillierCiphertext(BigInt);
+
+impl PaillierCiphertext {
+    /// Wraps a ciphertext encrypted with `ek`, `name` is used in the error
+    pub fn new(c: BigInt, ek: &EncryptionKey, name: &'static str) -> Result<Self, InvalidValue> {
+        let c = Self(c);
+        c.validate(ek, name)?;
+        Ok(c)
+    }
+
+    /// Checks that deserialized ciphertext is in `Z*_{N^2}` for given key
+    pub fn validate(&self, ek: &EncryptionKey, name: &'static str) -> Result<(), InvalidValue> {
+        if self.0 > BigInt::zero() && self.0 < ek.nn && self.0.gcd(&ek.n) == BigInt::one() {
+            Ok(())
+        } else {
+            Err(InvalidValue::CiphertextOutOfRange(name))
+        }
+    }
+
+    pub fn as_bigint(&self) -> &BigInt {
+        &self.0
+    }
+
+    pub fn into_bigint(self) -> BigInt {
+        self.0
+    }
+}
+
+impl Deref for PaillierCiphertext {
+    type Target = BigInt;
+
+    fn deref(&self) -> &BigInt {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use curv::elliptic::curves::Scalar;
+    use paillier::traits::KeyGeneration;
+    use paillier::Paillier;
+
+    use super::*;
+
+    #[test]
+    fn rejects_point_at_infinity() {
+        assert!(NonZeroPoint::new(Point::zero(), "y_i").is_err());
+        let point = Point::generator() * Scalar::<Secp256k1>::random();
+        let wrapped = NonZeroPoint::new(point.clone(), "y_i").unwrap();
+        assert_eq!(wrapped, point);
+
+        // what deserialization may produce
+        let received = NonZeroPoint(Point::zero());
+        assert_eq!(received.validate("y_i"), Err(InvalidValue::ZeroPoint("y_i")));
+    }
+
+    #[test]
+    fn rejects_ciphertext_outside_of_group() {
+        let (ek, _) = Paillier::keypair().keys();
+        assert!(PaillierCiphertext::new(BigInt::from(2), &ek, "c").is_ok());
+        let out_of_range = [
+            BigInt::zero(),
+            ek.n.clone(),
+            ek.nn.clone(),
+            &ek.nn + BigInt::one(),
+            &ek.n * BigInt::from(2),
+        ];
+        for c in out_of_range.iter().cloned() {
+            assert_eq!(
+                PaillierCiphertext::new(c, &ek, "c"),
+                Err(InvalidValue::CiphertextOutOfRange("c"))
+            );
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Settings of Signal transport and device store used by the protocols in [cli](crate::cli),
+//! and of the key being generated
+//!
+//! Library code never reads command line or environment: whoever embeds the crate (the `gg18`
+//! binary, FFI) builds [MpcConfig] and passes it into every call. Threshold `t` and number of
+//! parties `n` are given at runtime too, [KeygenConfig] and [check_group] validate them.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, ensure, Context, Result};
+use mpc_over_signal::{DeviceStore, Group, ParticipantIdentity, SignalClient};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::common::access_structure::{AccessStructure, AccessStructureError};
+
+/// Signal Server that every party of the group is registered at
+pub const DEFAULT_SIGNAL_HOST: &str = "https://textsecure-service.whispersystems.org/";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MpcConfig {
+    pub signal_server: SignalServerConfig,
+    pub device_store: DeviceStoreConfig,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SignalServerConfig {
+    /// Signal Server URL
+    ///
+    /// Differs from [DEFAULT_SIGNAL_HOST] if you're using own-hosted Signal Server.
+    pub host: String,
+    /// PEM-encoded root certificate trusted in establishing secure connection to the server
+    ///
+    /// If `None`, original Signal Server certificate is used.
+    pub root_certificate: Option<String>,
+    /// Time given to establish connection to the server
+    #[serde(with = "secs")]
+    pub connect_timeout: Duration,
+    /// Time given to every HTTP request to complete, `None` means no limit
+    #[serde(with = "secs::option")]
+    pub request_timeout: Option<Duration>,
+}
+
+impl Default for SignalServerConfig {
+    fn default() -> Self {
+        Self {
+            host: DEFAULT_SIGNAL_HOST.to_owned(),
+            root_certificate: None,
+            connect_timeout: Duration::from_secs(30),
+            request_timeout: None,
+        }
+    }
+}
+
+impl SignalServerConfig {
+    /// Constructs Signal client talking to the configured server
+    pub fn client(&self) -> Result<SignalClient> {
+        let mut builder = SignalClient::builder()?;
+        builder.set_server_host(self.host.clone())?;
+
+        let certificate = self
+            .root_certificate
+            .as_deref()
+            .unwrap_or(mpc_over_signal::SIGNAL_SERVER_CERTIFICATE);
+        let mut tls_config = rustls::ClientConfig::new();
+        let (valid, _invalid) = tls_config
+            .root_store
+            .add_pem_file(&mut certificate.as_bytes())
+            .map_err(|()| anyhow!("parse signal server certificate"))?;
+        ensure!(valid > 0, "no certificates found in signal server certificate");
+
+        let client = awc::Client::builder().connector(
+            awc::Connector::new()
+                .rustls(Arc::new(tls_config))
+                .timeout(self.connect_timeout)
+                .finish(),
+        );
+        let client = match self.request_timeout {
+            Some(timeout) => client.timeout(timeout),
+            None => client.disable_timeout(),
+        };
+        builder.set_http_client(client.finish());
+        Ok(builder.finish())
+    }
+}
+
+/// What to do with device secrets once protocol is finished
+///
+/// Device secrets change in every protocol execution (sessions with other parties are
+/// established and ratcheted), so they must be persisted after every call.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceStoreConfig {
+    /// File where updated secrets are saved
+    ///
+    /// If `None`, secrets aren't saved, caller is responsible for persisting the [DeviceStore]
+    /// it passed in.
+    pub secrets_file: Option<PathBuf>,
+}
+
+impl DeviceStoreConfig {
+    /// Saves `device_secrets` to [secrets_file](Self::secrets_file), if it's set
+    pub async fn save(&self, device_secrets: &mut DeviceStore) -> Result<()> {
+        match &self.secrets_file {
+            Some(path) => device_secrets
+                .save(path)
+                .await
+                .context("save device secrets"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Optional keygen settings on top of `t` and `n`
+///
+/// Default settings give plain `t`-of-`n` key.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeygenConfig {
+    /// Weights of parties (in group order) for weighted threshold key
+    ///
+    /// Party of weight `w` holds `w` shares, any set of parties holding `t`+1 shares together
+    /// can sign.
+    pub weights: Option<Vec<u16>>,
+    /// Levels of parties (in group order) for hierarchical threshold key
+    ///
+    /// Level `0` is the most senior one. Signing set of `t`+1 parties must contain at least
+    /// `l`+1 parties of levels `0..=l` for every level `l`.
+    pub levels: Option<Vec<u16>>,
+}
+
+impl KeygenConfig {
+    /// Access structure of the key shared between `n` parties with threshold `t`
+    pub fn access_structure(&self, t: u16, n: u16) -> Result<AccessStructure, GroupError> {
+        let access_structure = match (&self.weights, &self.levels) {
+            (Some(_), Some(_)) => return Err(GroupError::WeightsAndLevels),
+            (Some(weights), None) => AccessStructure::weighted(t, weights)?,
+            (None, Some(levels)) => AccessStructure::hierarchical(t, levels)?,
+            (None, None) => {
+                let access_structure = AccessStructure::threshold(t, n);
+                access_structure.validate()?;
+                access_structure
+            }
+        };
+        if access_structure.parties() != n {
+            return Err(GroupError::AccessStructureSize {
+                expected: n,
+                actual: access_structure.parties(),
+            });
+        }
+        Ok(access_structure)
+    }
+}
+
+/// Checks that `group` consists of `n` parties including `me`, returns index of `me`
+pub fn check_group(group: &Group, me: &ParticipantIdentity, n: u16) -> Result<u16, GroupError> {
+    if group.parties_count() != n {
+        return Err(GroupError::GroupSize {
+            expected: n,
+            actual: group.parties_count(),
+        });
+    }
+    group.party_index(&me.addr).ok_or(GroupError::NotInGroup)
+}
+
+#[derive(Debug, Error)]
+pub enum GroupError {
+    #[error("group must contain this party too")]
+    NotInGroup,
+    #[error("protocol expected to have {expected} parties, but group contains {actual}")]
+    GroupSize { expected: u16, actual: u16 },
+    #[error("access structure describes {actual} parties, but {expected} parties expected")]
+    AccessStructureSize { expected: u16, actual: u16 },
+    #[error("weights and levels of parties can't be set together")]
+    WeightsAndLevels,
+    #[error("invalid `t` and `n`: {0}")]
+    InvalidAccessStructure(#[from] AccessStructureError),
+    /// Party's identity key differs from the one this device trusted before
+    #[error("untrusted party: {0}")]
+    UntrustedParty(String),
+}
+
+/// Durations are written as a number of seconds
+mod secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(d.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        u64::deserialize(d).map(Duration::from_secs)
+    }
+
+    pub mod option {
+        use std::time::Duration;
+
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+            match d {
+                Some(d) => s.serialize_some(&d.as_secs()),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+            Option::<u64>::deserialize(d).map(|d| d.map(Duration::from_secs))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn config_defaults_are_filled_in() {
+        let config: MpcConfig =
+            serde_json::from_str(r#"{"signal_server": {"request_timeout": 60}}"#).unwrap();
+        assert_eq!(config.signal_server.host, DEFAULT_SIGNAL_HOST);
+        assert_eq!(config.signal_server.connect_timeout, Duration::from_secs(30));
+        assert_eq!(config.signal_server.request_timeout, Some(Duration::from_secs(60)));
+        assert!(config.device_store.secrets_file.is_none());
+    }
+
+    #[test]
+    fn keygen_config_validates_t_and_n() {
+        let plain = KeygenConfig::default();
+        assert!(plain.access_structure(1, 2).unwrap().is_plain_threshold());
+        assert!(plain.access_structure(2, 3).unwrap().is_plain_threshold());
+        assert!(plain.access_structure(2, 5).unwrap().is_plain_threshold());
+        assert!(matches!(
+            plain.access_structure(3, 3),
+            Err(GroupError::InvalidAccessStructure(AccessStructureError::Unsatisfiable))
+        ));
+        assert!(matches!(
+            plain.access_structure(0, 3),
+            Err(GroupError::InvalidAccessStructure(AccessStructureError::InvalidThreshold))
+        ));
+
+        let weighted = KeygenConfig {
+            weights: Some(vec![2, 1, 1]),
+            levels: None,
+        };
+        assert_eq!(weighted.access_structure(2, 3).unwrap().total_slots(), 4);
+        assert!(matches!(
+            weighted.access_structure(2, 4),
+            Err(GroupError::AccessStructureSize {
+                expected: 4,
+                actual: 3
+            })
+        ));
+    }
+}
@@ -4,25 +4,31 @@ use std::fmt;
 use std::mem::replace;
 use std::time::Duration;
 
-use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::elliptic::curves::{secp256_k1::Secp256k1, Scalar};
 use round_based::containers::{
     push::{Push, PushExt},
     *,
 };
+use rand::{CryptoRng, RngCore};
 use round_based::{IsCritical, Msg, StateMachine};
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
 use thiserror::Error;
 
+use crate::common::access_structure::{AccessStructure, AccessStructureError};
 use crate::common::party_i::{
     KeyGenBroadcastMessage1, KeyGenDecommitMessage1,
 };
+use crate::common::transcript::SessionId;
+use crate::common::validation::{validate_point, InvalidValue};
+use crate::error::Blame;
+use crate::utilities::sampling::BoxedRng;
+use crate::utilities::zk_dlog::DLogProof;
 
 use private::InternalError;
 pub use super::rounds::{LocalKey, ProceedError};
-use super::rounds::{Round0, Round1, Round2, Round3, Round4};
+use super::pre_params::PreParams;
+use super::rounds::{KeyGenConfirmation, Round0, Round1, Round2, Round3, Round4, Round5};
 
 /// Keygen protocol state machine
 ///
@@ -33,27 +39,41 @@ pub struct Keygen {
 
     msgs1: Option<Store<BroadcastMsgs<KeyGenBroadcastMessage1>>>,
     msgs2: Option<Store<BroadcastMsgs<KeyGenDecommitMessage1>>>,
-    msgs3: Option<Store<P2PMsgs<(VerifiableSS<Secp256k1>, Scalar<Secp256k1>)>>>,
-    msgs4: Option<Store<BroadcastMsgs<DLogProof<Secp256k1, Sha256>>>>,
+    msgs3: Option<Store<P2PMsgs<(VerifiableSS<Secp256k1>, Vec<Scalar<Secp256k1>>)>>>,
+    msgs4: Option<Store<BroadcastMsgs<DLogProof>>>,
+    msgs5: Option<Store<BroadcastMsgs<KeyGenConfirmation>>>,
 
     msgs_queue: Vec<Msg<ProtocolMessage>>,
 
     party_i: u16,
     party_n: u16,
+
+    rng: BoxedRng,
 }
 
 impl Keygen {
     /// Constructs a party of keygen protocol
     ///
-    /// Takes party index `i` (in range `[1; n]`), threshold value `t`, and total number of
-    /// parties `n`. Party index identifies this party in the protocol, so it must be guaranteed
-    /// to be unique.
+    /// Takes `session_id`, party index `i` (in range `[1; n]`), threshold value `t`, and total
+    /// number of parties `n`. Party index identifies this party in the protocol, so it must be
+    /// guaranteed to be unique.
+    ///
+    /// `session_id` must be the same for all parties and unique for every keygen (e.g. computation
+    /// id agreed through `mpc-over-signal`). Every zero-knowledge proof is bound to it, proofs
+    /// replayed from another session are rejected.
+    ///
+    /// All the randomness of the party (Paillier keys, `N_tilde` setup, secret shares, proofs)
+    /// is drawn from `rng`. Use `OsRng` in production; a seeded rng makes the run reproducible,
+    /// which is only meant for tests.
     ///
     /// Returns error if:
     /// * `n` is less than 2, returns [Error::TooFewParties]
     /// * `t` is not in range `[1; n-1]`, returns [Error::InvalidThreshold]
     /// * `i` is not in range `[1; n]`, returns [Error::InvalidPartyIndex]
-    pub fn new(i: u16, t: u16, n: u16) -> Result<Self> {
+    pub fn new<Rng>(session_id: SessionId, i: u16, t: u16, n: u16, rng: Rng) -> Result<Self>
+    where
+        Rng: RngCore + CryptoRng + Send + 'static,
+    {
         if n < 2 {
             return Err(Error::TooFewParties);
         }
@@ -63,29 +83,71 @@ impl Keygen {
         if i == 0 || i > n {
             return Err(Error::InvalidPartyIndex);
         }
+        Self::with_access_structure(session_id, i, AccessStructure::threshold(t, n), rng)
+    }
+
+    /// Constructs a party of keygen protocol producing key with arbitrary access structure
+    ///
+    /// Unlike [Keygen::new], allows weighted and hierarchical structures (see
+    /// [AccessStructure]). Number of parties `n` is a number of parties in the structure.
+    ///
+    /// Returns error if:
+    /// * `access_structure` is malformed, returns [Error::InvalidAccessStructure]
+    /// * `i` is not in range `[1; n]`, returns [Error::InvalidPartyIndex]
+    pub fn with_access_structure<Rng>(
+        session_id: SessionId,
+        i: u16,
+        access_structure: AccessStructure,
+        rng: Rng,
+    ) -> Result<Self>
+    where
+        Rng: RngCore + CryptoRng + Send + 'static,
+    {
+        access_structure
+            .validate()
+            .map_err(Error::InvalidAccessStructure)?;
+        let t = access_structure.threshold;
+        let n = access_structure.parties();
+        if i == 0 || i > n {
+            return Err(Error::InvalidPartyIndex);
+        }
         let mut state = Self {
-            round: R::Round0(Round0 { party_i: i, t, n }),
+            round: R::Round0(Round0 {
+                session_id,
+                party_i: i,
+                t,
+                n,
+                access_structure,
+                pre_params: None,
+            }),
 
             msgs1: Some(Round1::expects_messages(i, n)),
             msgs2: Some(Round2::expects_messages(i, n)),
             msgs3: Some(Round3::expects_messages(i, n)),
             msgs4: Some(Round4::expects_messages(i, n)),
+            msgs5: Some(Round5::expects_messages(i, n)),
 
             msgs_queue: vec![],
 
             party_i: i,
             party_n: n,
+
+            rng: Box::new(rng),
         };
 
         state.proceed_round(false)?;
         Ok(state)
     }
 
-    fn gmap_queue<'a, T, F>(&'a mut self, mut f: F) -> impl Push<Msg<T>> + 'a
-    where
-        F: FnMut(T) -> M + 'a,
-    {
-        (&mut self.msgs_queue).gmap(move |m: Msg<T>| m.map_body(|m| ProtocolMessage(f(m))))
+    /// Makes the party use Paillier key and `N_tilde` setup generated in advance (see
+    /// [pre_params](super::pre_params)) instead of searching for primes in the first round
+    ///
+    /// Has no effect if the party already proceeded the first round.
+    pub fn with_pre_params(mut self, pre_params: PreParams) -> Self {
+        if let R::Round0(round) = &mut self.round {
+            round.pre_params = Some(pre_params);
+        }
+        self
     }
 
     /// Proceeds round state if it received enough messages and if it's cheap to compute or
@@ -95,12 +157,13 @@ impl Keygen {
         let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
         let store3_wants_more = self.msgs3.as_ref().map(|s| s.wants_more()).unwrap_or(false);
         let store4_wants_more = self.msgs4.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store5_wants_more = self.msgs5.as_ref().map(|s| s.wants_more()).unwrap_or(false);
 
         let next_state: R;
         let try_again: bool = match replace(&mut self.round, R::Gone) {
             R::Round0(round) if !round.is_expensive() || may_block => {
                 next_state = round
-                    .proceed(self.gmap_queue(M::Round1))
+                    .proceed(&mut self.rng, gmap_queue(&mut self.msgs_queue, M::Round1))
                     .map(R::Round1)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -115,7 +178,7 @@ impl Keygen {
                     .finish()
                     .map_err(InternalError::RetrieveRoundMessages)?;
                 next_state = round
-                    .proceed(msgs, self.gmap_queue(M::Round2))
+                    .proceed(msgs, gmap_queue(&mut self.msgs_queue, M::Round2))
                     .map(R::Round2)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -130,7 +193,7 @@ impl Keygen {
                     .finish()
                     .map_err(InternalError::RetrieveRoundMessages)?;
                 next_state = round
-                    .proceed(msgs, self.gmap_queue(M::Round3))
+                    .proceed(&mut self.rng, msgs, gmap_queue(&mut self.msgs_queue, M::Round3))
                     .map(R::Round3)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -145,7 +208,7 @@ impl Keygen {
                     .finish()
                     .map_err(InternalError::RetrieveRoundMessages)?;
                 next_state = round
-                    .proceed(msgs, self.gmap_queue(M::Round4))
+                    .proceed(&mut self.rng, msgs, gmap_queue(&mut self.msgs_queue, M::Round4))
                     .map(R::Round4)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -156,6 +219,21 @@ impl Keygen {
             }
             R::Round4(round) if !store4_wants_more && (!round.is_expensive() || may_block) => {
                 let store = self.msgs4.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs, gmap_queue(&mut self.msgs_queue, M::Round5))
+                    .map(R::Round5)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round4(_) => {
+                next_state = s;
+                false
+            }
+            R::Round5(round) if !store5_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs5.take().ok_or(InternalError::StoreGone)?;
                 let msgs = store
                     .finish()
                     .map_err(InternalError::RetrieveRoundMessages)?;
@@ -165,7 +243,7 @@ impl Keygen {
                     .map_err(Error::ProceedRound)?;
                 true
             }
-            s @ R::Round4(_) => {
+            s @ R::Round5(_) => {
                 next_state = s;
                 false
             }
@@ -184,6 +262,18 @@ impl Keygen {
     }
 }
 
+/// Wraps round messages into [ProtocolMessage]. Borrows only the queue, so the round can be
+/// given the party's rng at the same time.
+fn gmap_queue<'a, T, F>(
+    queue: &'a mut Vec<Msg<ProtocolMessage>>,
+    mut f: F,
+) -> impl Push<Msg<T>> + 'a
+where
+    F: FnMut(T) -> M + 'a,
+{
+    queue.gmap(move |m: Msg<T>| m.map_body(|m| ProtocolMessage(f(m))))
+}
+
 impl StateMachine for Keygen {
     type MessageBody = ProtocolMessage;
     type Err = Error;
@@ -191,6 +281,8 @@ impl StateMachine for Keygen {
 
     fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
         let current_round = self.current_round();
+        let sender = msg.sender;
+        let invalid = move |reason| Error::InvalidMessage { sender, reason };
 
         match msg.body {
             ProtocolMessage(M::Round1(m)) => {
@@ -211,6 +303,7 @@ impl StateMachine for Keygen {
                 self.proceed_round(false)
             }
             ProtocolMessage(M::Round2(m)) => {
+                m.validate().map_err(invalid)?;
                 let store = self
                     .msgs2
                     .as_mut()
@@ -228,6 +321,9 @@ impl StateMachine for Keygen {
                 self.proceed_round(false)
             }
             ProtocolMessage(M::Round3(m)) => {
+                for commitment in &m.0.commitments {
+                    validate_point(commitment, "vss commitment").map_err(invalid)?;
+                }
                 let store = self
                     .msgs3
                     .as_mut()
@@ -245,6 +341,7 @@ impl StateMachine for Keygen {
                 self.proceed_round(false)
             }
             ProtocolMessage(M::Round4(m)) => {
+                m.validate().map_err(invalid)?;
                 let store = self
                     .msgs4
                     .as_mut()
@@ -261,6 +358,23 @@ impl StateMachine for Keygen {
                     .map_err(Error::HandleMessage)?;
                 self.proceed_round(false)
             }
+            ProtocolMessage(M::Round5(m)) => {
+                let store = self
+                    .msgs5
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 5,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
         }
     }
 
@@ -273,6 +387,7 @@ impl StateMachine for Keygen {
         let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
         let store3_wants_more = self.msgs3.as_ref().map(|s| s.wants_more()).unwrap_or(false);
         let store4_wants_more = self.msgs4.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store5_wants_more = self.msgs5.as_ref().map(|s| s.wants_more()).unwrap_or(false);
 
         match &self.round {
             R::Round0(_) => true,
@@ -280,6 +395,7 @@ impl StateMachine for Keygen {
             R::Round2(_) => !store2_wants_more,
             R::Round3(_) => !store3_wants_more,
             R::Round4(_) => !store4_wants_more,
+            R::Round5(_) => !store5_wants_more,
             R::Final(_) | R::Gone => false,
         }
     }
@@ -320,12 +436,13 @@ impl StateMachine for Keygen {
             R::Round2(_) => 2,
             R::Round3(_) => 3,
             R::Round4(_) => 4,
-            R::Final(_) | R::Gone => 5,
+            R::Round5(_) => 5,
+            R::Final(_) | R::Gone => 6,
         }
     }
 
     fn total_rounds(&self) -> Option<u16> {
-        Some(4)
+        Some(5)
     }
 
     fn party_ind(&self) -> u16 {
@@ -344,6 +461,7 @@ impl crate::dkg::traits::RoundBlame for Keygen {
         let store2_blame = self.msgs2.as_ref().map(|s| s.blame()).unwrap_or_default();
         let store3_blame = self.msgs3.as_ref().map(|s| s.blame()).unwrap_or_default();
         let store4_blame = self.msgs4.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store5_blame = self.msgs5.as_ref().map(|s| s.blame()).unwrap_or_default();
 
         let default = (0, vec![]);
         match &self.round {
@@ -352,6 +470,7 @@ impl crate::dkg::traits::RoundBlame for Keygen {
             R::Round2(_) => store2_blame,
             R::Round3(_) => store3_blame,
             R::Round4(_) => store4_blame,
+            R::Round5(_) => store5_blame,
             R::Final(_) | R::Gone => default,
         }
     }
@@ -365,6 +484,7 @@ impl fmt::Debug for Keygen {
             R::Round2(_) => "2",
             R::Round3(_) => "3",
             R::Round4(_) => "4",
+            R::Round5(_) => "5",
             R::Final(_) => "[Final]",
             R::Gone => "[Gone]",
         };
@@ -384,14 +504,19 @@ impl fmt::Debug for Keygen {
             Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
             None => "[None]".into(),
         };
+        let msgs5 = match self.msgs5.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
         write!(
             f,
-            "{{Keygen at round={} msgs1={} msgs2={} msgs3={} msgs4={} queue=[len={}]}}",
+            "{{Keygen at round={} msgs1={} msgs2={} msgs3={} msgs4={} msgs5={} queue=[len={}]}}",
             current_round,
             msgs1,
             msgs2,
             msgs3,
             msgs4,
+            msgs5,
             self.msgs_queue.len()
         )
     }
@@ -405,6 +530,7 @@ enum R {
     Round2(Round2),
     Round3(Round3),
     Round4(Round4),
+    Round5(Round5),
     Final(LocalKey<Secp256k1>),
     Gone,
 }
@@ -421,8 +547,9 @@ pub struct ProtocolMessage(M);
 enum M {
     Round1(KeyGenBroadcastMessage1),
     Round2(KeyGenDecommitMessage1),
-    Round3((VerifiableSS<Secp256k1>, Scalar<Secp256k1>)),
-    Round4(DLogProof<Secp256k1, Sha256>),
+    Round3((VerifiableSS<Secp256k1>, Vec<Scalar<Secp256k1>>)),
+    Round4(DLogProof),
+    Round5(KeyGenConfirmation),
 }
 
 // Error
@@ -446,10 +573,20 @@ pub enum Error {
     /// Party index `i` is not in range `[1; n]`
     #[error("party index is not in range [1; n]")]
     InvalidPartyIndex,
+    /// Access structure is malformed
+    #[error("invalid access structure: {0}")]
+    InvalidAccessStructure(#[source] AccessStructureError),
 
     /// Received message didn't pass pre-validation
     #[error("received message didn't pass pre-validation: {0}")]
     HandleMessage(#[source] StoreErr),
+    /// Party `sender` sent a message with invalid curve point
+    #[error("party {sender} sent invalid message: {reason}")]
+    InvalidMessage {
+        sender: u16,
+        #[source]
+        reason: InvalidValue,
+    },
     /// Received message which we didn't expect to receive now (e.g. message from previous round)
     #[error(
         "didn't expect to receive message from round {msg_round} (being at round {current_round})"
@@ -471,6 +608,18 @@ impl IsCritical for Error {
     }
 }
 
+impl Blame for Error {
+    fn culprits(&self) -> Vec<u16> {
+        match self {
+            Error::InvalidMessage { sender, .. } => vec![*sender],
+            Error::ProceedRound(ProceedError::Round5TranscriptMismatch { parties }) => {
+                parties.clone()
+            }
+            _ => vec![],
+        }
+    }
+}
+
 impl From<InternalError> for Error {
     fn from(err: InternalError) -> Self {
         Self::InternalError(err)
@@ -491,16 +640,20 @@ mod private {
 
 #[cfg(test)]
 pub mod test {
+    use rand::rngs::{OsRng, StdRng};
+    use rand::SeedableRng;
     use round_based::dev::Simulation;
 
     use super::*;
 
+    pub const SESSION_ID: SessionId = [0x42; 32];
+
     pub fn simulate_keygen(t: u16, n: u16) -> Vec<LocalKey<Secp256k1>> {
         let mut simulation = Simulation::new();
         simulation.enable_benchmarks(true);
 
         for i in 1..=n {
-            simulation.add_party(Keygen::new(i, t, n).unwrap());
+            simulation.add_party(Keygen::new(SESSION_ID, i, t, n, OsRng).unwrap());
         }
 
         let keys = simulation.run().unwrap();
@@ -525,4 +678,98 @@ pub mod test {
     fn simulate_keygen_t2_n3() {
         simulate_keygen(2, 3);
     }
+
+    pub fn simulate_keygen_with_access_structure(
+        access_structure: AccessStructure,
+    ) -> Vec<LocalKey<Secp256k1>> {
+        let mut simulation = Simulation::new();
+
+        for i in 1..=access_structure.parties() {
+            simulation.add_party(
+                Keygen::with_access_structure(SESSION_ID, i, access_structure.clone(), OsRng)
+                    .unwrap(),
+            );
+        }
+
+        simulation.run().unwrap()
+    }
+
+    #[test]
+    fn simulate_keygen_weighted() {
+        let access_structure = AccessStructure::weighted(2, &[2, 1, 1]).unwrap();
+        let keys = simulate_keygen_with_access_structure(access_structure);
+        assert_eq!(keys[0].shared_keys.extra_x_i.len(), 1);
+        assert!(keys[1].shared_keys.extra_x_i.is_empty());
+    }
+
+    #[test]
+    fn simulate_keygen_hierarchical() {
+        let access_structure = AccessStructure::hierarchical(1, &[0, 1, 1]).unwrap();
+        let keys = simulate_keygen_with_access_structure(access_structure);
+        assert!(keys
+            .iter()
+            .all(|key| key.public_key() == keys[0].public_key()));
+    }
+
+    #[test]
+    fn parties_agree_on_short_authentication_string() {
+        let keys = simulate_keygen(1, 3);
+        let sas = keys[0].short_authentication_string();
+        assert!(keys
+            .iter()
+            .all(|key| key.short_authentication_string() == sas));
+        assert_eq!(sas.len(), 7);
+    }
+
+    #[test]
+    fn proofs_from_another_session_are_rejected() {
+        let mut simulation = Simulation::new();
+        simulation.add_party(Keygen::new([1; 32], 1, 1, 3, OsRng).unwrap());
+        for i in 2..=3 {
+            simulation.add_party(Keygen::new(SESSION_ID, i, 1, 3, OsRng).unwrap());
+        }
+        assert!(simulation.run().is_err());
+    }
+
+    #[test]
+    fn keygen_uses_pre_params() {
+        let pre_params = PreParams::generate(&mut OsRng);
+        let ek = pre_params.ek.clone();
+        let n_tilde = pre_params.dlog_setup.N_tilde.clone();
+
+        let mut simulation = Simulation::new();
+        simulation.add_party(
+            Keygen::new(SESSION_ID, 1, 1, 2, OsRng)
+                .unwrap()
+                .with_pre_params(pre_params),
+        );
+        simulation.add_party(Keygen::new(SESSION_ID, 2, 1, 2, OsRng).unwrap());
+        let keys = simulation.run().unwrap();
+
+        assert_eq!(keys[1].pail_key_vec[0], ek);
+        assert_eq!(keys[1].h1_h2_n_tilde_vec[0].N, n_tilde);
+    }
+
+    /// Runs keygen where party `i` draws randomness from `StdRng` seeded with `[seed + i; 32]`
+    pub fn simulate_keygen_seeded(t: u16, n: u16, seed: u8) -> Vec<LocalKey<Secp256k1>> {
+        let mut simulation = Simulation::new();
+        for i in 1..=n {
+            let rng = StdRng::from_seed([seed.wrapping_add(i as u8); 32]);
+            simulation.add_party(Keygen::new(SESSION_ID, i, t, n, rng).unwrap());
+        }
+        simulation.run().unwrap()
+    }
+
+    #[test]
+    fn keygen_is_reproducible_from_seed() {
+        let keys1 = simulate_keygen_seeded(1, 2, 0);
+        let keys2 = simulate_keygen_seeded(1, 2, 0);
+        for (k1, k2) in keys1.iter().zip(&keys2) {
+            assert_eq!(k1.transcript_hash(), k2.transcript_hash());
+            assert_eq!(k1.shared_keys.x_i, k2.shared_keys.x_i);
+        }
+
+        let keys3 = simulate_keygen_seeded(1, 2, 1);
+        assert_ne!(keys1[0].public_key(), keys3[0].public_key());
+    }
 }
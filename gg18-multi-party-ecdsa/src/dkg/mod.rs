@@ -1,3 +1,5 @@
 pub mod keygen;
+pub mod pre_params;
 pub mod rounds;
-pub mod traits;
\ No newline at end of file
+pub mod sas;
+pub mod traits;
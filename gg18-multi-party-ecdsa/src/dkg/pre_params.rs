@@ -0,0 +1,251 @@
+//! Paillier keys and `N_tilde` setup generated ahead of keygen
+//!
+//! Prime search in [Round0](super::rounds::Round0) dominates keygen time on phones. An app can
+//! fill a [PreParamsPool] while it sits idle, keep it encrypted on disk, and pass one
+//! [PreParams] to [Keygen::with_pre_params](super::keygen::Keygen::with_pre_params) (or
+//! [KeyImport::with_pre_params](crate::key_import::import::KeyImport::with_pre_params)) when
+//! the ceremony starts. Round 0 then only samples a secret and computes proofs.
+//!
+//! Pre-params must never be used in two ceremonies: take them out of the pool and store the
+//! re-encrypted pool _before_ starting keygen.
+//!
+//! Encrypted pool is a json object with aes-128-ctr encrypted list of pre-params and Keccak256
+//! MAC computed over iv and ciphertext.
+
+use std::fmt;
+
+use aes::Aes128;
+use ctr::cipher::{NewCipher, StreamCipher};
+use paillier::{DecryptionKey, EncryptionKey};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::common::party_i::{DLogSetup, Redacted};
+use crate::utilities::sampling::{paillier_keypair, PAILLIER_KEY_SIZE};
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+const POOL_VERSION: u8 = 1;
+
+/// Secrets of Round 0 that take prime search to generate
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PreParams {
+    pub ek: EncryptionKey,
+    pub dk: DecryptionKey,
+    pub dlog_setup: DLogSetup,
+}
+
+impl PreParams {
+    /// Generates Paillier key pair and `N_tilde` setup, the same way keygen does
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let (ek, dk) = paillier_keypair(rng, PAILLIER_KEY_SIZE);
+        let dlog_setup = DLogSetup::generate(rng);
+        Self { ek, dk, dlog_setup }
+    }
+}
+
+impl fmt::Debug for PreParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PreParams")
+            .field("ek", &self.ek)
+            .field("dk", &Redacted)
+            .field("dlog_setup", &self.dlog_setup)
+            .finish()
+    }
+}
+
+impl Drop for PreParams {
+    fn drop(&mut self) {
+        self.dk.p.zeroize();
+        self.dk.q.zeroize();
+    }
+}
+
+/// Key that encrypts [PreParamsPool]
+///
+/// First half is aes-128 key, second half is MAC key. Apps are expected to keep it in a platform
+/// keystore.
+pub struct PoolKey([u8; 32]);
+
+impl PoolKey {
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut key = [0u8; 32];
+        rng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    /// Returns error if `bytes` is not 32 bytes long
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PoolError> {
+        if bytes.len() != 32 {
+            return Err(PoolError::InvalidKeyLength);
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(bytes);
+        Ok(Self(key))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    fn mac(&self, iv: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+        let mut mac = [0u8; 32];
+        mac.copy_from_slice(
+            &Keccak256::new()
+                .chain(&self.0[16..])
+                .chain(iv)
+                .chain(ciphertext)
+                .finalize(),
+        );
+        mac
+    }
+
+    fn apply_keystream(&self, iv: &[u8], data: &mut [u8]) {
+        Aes128Ctr::new(self.0[..16].into(), iv.into()).apply_keystream(data);
+    }
+}
+
+impl Drop for PoolKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for PoolKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("PoolKey").field(&Redacted).finish()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedPool {
+    version: u8,
+    iv: String,
+    ciphertext: String,
+    mac: String,
+}
+
+/// Pre-params generated ahead of time
+#[derive(Debug, Default)]
+pub struct PreParamsPool {
+    params: Vec<PreParams>,
+}
+
+impl PreParamsPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of pre-params in the pool
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    pub fn push(&mut self, pre_params: PreParams) {
+        self.params.push(pre_params)
+    }
+
+    /// Removes pre-params from the pool, returns `None` if pool is empty
+    pub fn take(&mut self) -> Option<PreParams> {
+        self.params.pop()
+    }
+
+    /// Generates pre-params until the pool contains at least `size` of them
+    ///
+    /// Takes a few seconds per pre-params on desktop and much longer on phones, so it's meant
+    /// to be run in background.
+    pub fn fill<R: RngCore + CryptoRng>(&mut self, rng: &mut R, size: usize) {
+        while self.params.len() < size {
+            self.params.push(PreParams::generate(rng))
+        }
+    }
+
+    /// Serializes and encrypts the pool
+    pub fn encrypt<R: RngCore + CryptoRng>(&self, rng: &mut R, key: &PoolKey) -> Vec<u8> {
+        let mut iv = [0u8; 16];
+        rng.fill_bytes(&mut iv);
+        let mut ciphertext = serde_json::to_vec(&self.params).expect("serialize pre-params");
+        key.apply_keystream(&iv, &mut ciphertext);
+        let mac = key.mac(&iv, &ciphertext);
+
+        let encrypted = EncryptedPool {
+            version: POOL_VERSION,
+            iv: hex::encode(iv),
+            ciphertext: hex::encode(ciphertext),
+            mac: hex::encode(mac),
+        };
+        serde_json::to_vec(&encrypted).expect("serialize encrypted pool")
+    }
+
+    /// Decrypts a pool produced by [encrypt](Self::encrypt)
+    pub fn decrypt(encrypted: &[u8], key: &PoolKey) -> Result<Self, PoolError> {
+        let encrypted: EncryptedPool =
+            serde_json::from_slice(encrypted).map_err(|_| PoolError::MalformedPool)?;
+        if encrypted.version != POOL_VERSION {
+            return Err(PoolError::UnsupportedVersion(encrypted.version));
+        }
+        let decode = |s: &str| hex::decode(s).map_err(|_| PoolError::MalformedPool);
+        let iv = decode(&encrypted.iv)?;
+        let ciphertext = decode(&encrypted.ciphertext)?;
+        let mac = decode(&encrypted.mac)?;
+        if iv.len() != 16 {
+            return Err(PoolError::MalformedPool);
+        }
+        if !bool::from(key.mac(&iv, &ciphertext)[..].ct_eq(&mac[..])) {
+            return Err(PoolError::WrongKey);
+        }
+        let mut plaintext = Zeroizing::new(ciphertext);
+        key.apply_keystream(&iv, &mut plaintext);
+
+        let params = serde_json::from_slice(&plaintext).map_err(|_| PoolError::MalformedPool)?;
+        Ok(Self { params })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PoolError {
+    #[error("pool key must be 32 bytes long")]
+    InvalidKeyLength,
+    #[error("encrypted pool is malformed")]
+    MalformedPool,
+    #[error("pool version {0} is not supported")]
+    UnsupportedVersion(u8),
+    #[error("pool is encrypted with another key or was tampered with")]
+    WrongKey,
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn pool_roundtrip() {
+        let key = PoolKey::generate(&mut OsRng);
+        let mut pool = PreParamsPool::new();
+        pool.fill(&mut OsRng, 2);
+        let n_tildes: Vec<_> = pool.params.iter().map(|p| p.dlog_setup.N_tilde.clone()).collect();
+
+        let encrypted = pool.encrypt(&mut OsRng, &key);
+        let mut decrypted = PreParamsPool::decrypt(&encrypted, &key).unwrap();
+        assert_eq!(decrypted.len(), 2);
+        assert_eq!(decrypted.take().unwrap().dlog_setup.N_tilde, n_tildes[1]);
+        assert_eq!(decrypted.take().unwrap().dlog_setup.N_tilde, n_tildes[0]);
+        assert!(decrypted.take().is_none());
+
+        let another_key = PoolKey::generate(&mut OsRng);
+        assert!(matches!(
+            PreParamsPool::decrypt(&encrypted, &another_key),
+            Err(PoolError::WrongKey)
+        ));
+    }
+}
@@ -1,4 +1,5 @@
-use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use std::convert::TryFrom;
+
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::elliptic::curves::{secp256_k1::Secp256k1, Curve, Point, Scalar};
 
@@ -6,30 +7,55 @@ use round_based::containers::push::Push;
 use round_based::containers::{self, BroadcastMsgs, P2PMsgs, Store};
 use round_based::Msg;
 
+use crate::common::access_structure::AccessStructure;
 use crate::common::party_i::{
-    KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Keys, Parameters, SharedKeys
+    DLogSetup, KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Keys, LocalKeyShare, Parameters,
+    SharedKeys,
 };
+use crate::common::transcript::{ProofContext, SessionId};
+use crate::utilities::zk_dlog::DLogProof;
 
 use crate::common::{ ErrorType};
-use sha2::Sha256;
+use super::pre_params::PreParams;
+use super::sas::{self, SasEmoji};
+use sha2::{Digest, Sha256};
 use serde::{Deserialize, Serialize};
 
 use paillier::EncryptionKey;
+use rand::{CryptoRng, RngCore};
 use thiserror::Error;
+use zk_paillier::zkproofs::DLogStatement;
 
 pub struct Round0 {
+    pub session_id: SessionId,
     pub party_i: u16,
     pub t: u16,
     pub n: u16,
+    pub access_structure: AccessStructure,
+    pub pre_params: Option<PreParams>,
 }
 
 impl Round0 {
-    pub fn proceed<O>(self, mut output: O) -> Result<Round1>
+    pub fn proceed<R, O>(self, rng: &mut R, mut output: O) -> Result<Round1>
     where
+        R: RngCore + CryptoRng,
         O: Push<Msg<KeyGenBroadcastMessage1>>,
     {
-        let party_keys = Keys::create(self.party_i as u16);
-        let (bc_i, decom_i) = party_keys.phase1_broadcast_phase3_proof_of_correct_key();
+        let ctx = ProofContext::new(self.session_id, self.party_i);
+        let (party_keys, dlog_setup) = match &self.pre_params {
+            Some(pre) => (
+                Keys::create_with_paillier_key(rng, pre.ek.clone(), pre.dk.clone(), self.party_i)
+                    .map_err(|_| ProceedError::Round0InvalidPreParams)?,
+                pre.dlog_setup.clone(),
+            ),
+            None => (Keys::create(rng, self.party_i), DLogSetup::generate(rng)),
+        };
+        let (bc_i, decom_i) = party_keys
+            .phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2(
+                rng,
+                &ctx,
+                &dlog_setup,
+            );
 
         output.push(Msg {
             sender: self.party_i,
@@ -37,12 +63,15 @@ impl Round0 {
             body: bc_i.clone(),
         });
         Ok(Round1 {
+            ctx,
             keys: party_keys,
+            dlog_setup,
             bc1: bc_i,
             decom1: decom_i,
             party_i: self.party_i,
             t: self.t,
             n: self.n,
+            access_structure: self.access_structure,
         })
     }
     pub fn is_expensive(&self) -> bool {
@@ -51,12 +80,15 @@ impl Round0 {
 }
 
 pub struct Round1 {
+    ctx: ProofContext,
     keys: Keys,
+    dlog_setup: DLogSetup,
     bc1: KeyGenBroadcastMessage1,
     decom1: KeyGenDecommitMessage1,
     party_i: u16,
     t: u16,
     n: u16,
+    access_structure: AccessStructure,
 }
 
 impl Round1 {
@@ -74,13 +106,16 @@ impl Round1 {
             body: self.decom1.clone(),
         });
         Ok(Round2 {
+            ctx: self.ctx,
             keys: self.keys,
+            dlog_setup: self.dlog_setup,
             received_comm: input.into_vec_including_me(self.bc1),
             decom: self.decom1,
 
             party_i: self.party_i,
             t: self.t,
             n: self.n,
+            access_structure: self.access_structure,
         })
     }
     pub fn is_expensive(&self) -> bool {
@@ -92,33 +127,34 @@ impl Round1 {
 }
 
 pub struct Round2 {
+    ctx: ProofContext,
     keys: Keys,
+    dlog_setup: DLogSetup,
     received_comm: Vec<KeyGenBroadcastMessage1>,
     decom: KeyGenDecommitMessage1,
 
     party_i: u16,
     t: u16,
     n: u16,
+    access_structure: AccessStructure,
 }
 
 impl Round2 {
-    pub fn proceed<O>(
+    pub fn proceed<R, O>(
         self,
+        rng: &mut R,
         input: BroadcastMsgs<KeyGenDecommitMessage1>,
         mut output: O,
     ) -> Result<Round3>
     where
-        O: Push<Msg<(VerifiableSS<Secp256k1>, Scalar<Secp256k1>)>>,
+        R: RngCore + CryptoRng,
+        O: Push<Msg<(VerifiableSS<Secp256k1>, Vec<Scalar<Secp256k1>>)>>,
     {
-        let params = Parameters {
-            threshold: self.t,
-            share_count: self.n,
-        };
         let received_decom = input.into_vec_including_me(self.decom);
 
         let vss_result = self.keys
-        .phase1_verify_com_phase3_verify_correct_key_phase2_distribute(
-            &params, &received_decom, &self.received_comm,
+        .phase1_verify_com_phase3_verify_correct_key_phase2_distribute_shares(
+            rng, &self.ctx, &self.access_structure, &received_decom, &self.received_comm,
         )
         .map_err(ProceedError::Round2VerifyCommitments)?;
 
@@ -135,9 +171,11 @@ impl Round2 {
         }
 
         Ok(Round3 {
+            ctx: self.ctx,
             keys: self.keys,
+            dlog_setup: self.dlog_setup,
 
-            y_vec: received_decom.into_iter().map(|d| d.y_i).collect(),
+            y_vec: received_decom.into_iter().map(|d| d.y_i.into_point()).collect(),
             bc_vec: self.received_comm,
 
             own_vss: vss_result.0.clone(),
@@ -146,6 +184,7 @@ impl Round2 {
             party_i: self.party_i,
             t: self.t,
             n: self.n,
+            access_structure: self.access_structure,
         })
     }
     pub fn is_expensive(&self) -> bool {
@@ -157,32 +196,33 @@ impl Round2 {
 }
 
 pub struct Round3 {
+    ctx: ProofContext,
     keys: Keys,
+    dlog_setup: DLogSetup,
 
     y_vec: Vec<Point<Secp256k1>>,
     bc_vec: Vec<KeyGenBroadcastMessage1>,
 
     own_vss: VerifiableSS<Secp256k1>,
-    own_share: Scalar<Secp256k1>,
+    own_share: Vec<Scalar<Secp256k1>>,
 
     party_i: u16,
     t: u16,
     n: u16,
+    access_structure: AccessStructure,
 }
 
 impl Round3 {
-    pub fn proceed<O>(
+    pub fn proceed<R, O>(
         self,
-        input: P2PMsgs<(VerifiableSS<Secp256k1>, Scalar<Secp256k1>)>,
+        rng: &mut R,
+        input: P2PMsgs<(VerifiableSS<Secp256k1>, Vec<Scalar<Secp256k1>>)>,
         mut output: O,
     ) -> Result<Round4>
     where
-        O: Push<Msg<DLogProof<Secp256k1, Sha256>>>,
+        R: RngCore + CryptoRng,
+        O: Push<Msg<DLogProof>>,
     {
-        let params = Parameters {
-            threshold: self.t,
-            share_count: self.n,
-        };
         let (vss_schemes, party_shares): (Vec<_>, Vec<_>) = input
             .into_vec_including_me((self.own_vss, self.own_share))
             .into_iter()
@@ -190,8 +230,10 @@ impl Round3 {
 
         let (shared_keys, dlog_proof) = self
             .keys
-            .phase2_verify_vss_construct_keypair_phase3_pok_dlog(
-                &params,
+            .phase2_verify_shares_construct_keypair_phase3_pok_dlog(
+                rng,
+                &self.ctx,
+                &self.access_structure,
                 &self.y_vec,
                 &party_shares,
                 &vss_schemes,
@@ -206,7 +248,9 @@ impl Round3 {
         });
 
         Ok(Round4 {
-            keys: self.keys.clone(),
+            ctx: self.ctx,
+            keys: self.keys,
+            dlog_setup: self.dlog_setup,
             y_vec: self.y_vec.clone(),
             bc_vec: self.bc_vec,
             shared_keys,
@@ -216,6 +260,7 @@ impl Round3 {
             party_i: self.party_i,
             t: self.t,
             n: self.n,
+            access_structure: self.access_structure,
         })
     }
     pub fn is_expensive(&self) -> bool {
@@ -224,29 +269,36 @@ impl Round3 {
     pub fn expects_messages(
         i: u16,
         n: u16,
-    ) -> Store<P2PMsgs<(VerifiableSS<Secp256k1>, Scalar<Secp256k1>)>> {
+    ) -> Store<P2PMsgs<(VerifiableSS<Secp256k1>, Vec<Scalar<Secp256k1>>)>> {
         containers::P2PMsgsStore::new(i, n)
     }
 }
 
 pub struct Round4 {
+    ctx: ProofContext,
     keys: Keys,
+    dlog_setup: DLogSetup,
     y_vec: Vec<Point<Secp256k1>>,
     bc_vec: Vec<KeyGenBroadcastMessage1>,
     shared_keys: SharedKeys,
-    own_dlog_proof: DLogProof<Secp256k1, Sha256>,
+    own_dlog_proof: DLogProof,
     vss_vec: Vec<VerifiableSS<Secp256k1>>,
 
     party_i: u16,
     t: u16,
     n: u16,
+    access_structure: AccessStructure,
 }
 
 impl Round4 {
-    pub fn proceed(
+    pub fn proceed<O>(
         self,
-        input: BroadcastMsgs<DLogProof<Secp256k1, Sha256>>,
-    ) -> Result<LocalKey<Secp256k1>> {
+        input: BroadcastMsgs<DLogProof>,
+        mut output: O,
+    ) -> Result<Round5>
+    where
+        O: Push<Msg<KeyGenConfirmation>>,
+    {
         let params = Parameters {
             threshold: self.t,
             share_count: self.n,
@@ -254,6 +306,7 @@ impl Round4 {
         let dlog_proofs = input.into_vec_including_me(self.own_dlog_proof.clone());
 
         Keys::verify_dlog_proofs(
+            &self.ctx,
             &params, 
             &dlog_proofs, 
             &self.y_vec
@@ -264,6 +317,11 @@ impl Round4 {
         let paillier_key_vec = (0..params.share_count)
             .map(|i| self.bc_vec[i as usize].e.clone())
             .collect::<Vec<EncryptionKey>>();
+        let h1_h2_n_tilde_vec = self
+            .bc_vec
+            .iter()
+            .map(|bc1| bc1.dlog_statement.clone())
+            .collect::<Vec<DLogStatement>>();
 
         let (head, tail) = self.y_vec.split_at(1);
         let y_sum = tail.iter().fold(head[0].clone(), |acc, x| acc + x);
@@ -275,21 +333,70 @@ impl Round4 {
             vss_vec: self.vss_vec,
             pail_key_vec: paillier_key_vec,
             y_sum_s: y_sum,
+            access_structure: self.access_structure,
+            dlog_setup: Some(self.dlog_setup),
+            h1_h2_n_tilde_vec,
         };
 
-        Ok(local_key)
+        let confirmation = KeyGenConfirmation {
+            transcript_hash: local_key.transcript_hash(),
+        };
+        output.push(Msg {
+            sender: self.party_i,
+            receiver: None,
+            body: confirmation.clone(),
+        });
 
+        Ok(Round5 {
+            local_key,
+            own_confirmation: confirmation,
+        })
     }
     pub fn is_expensive(&self) -> bool {
         true
     }
-    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<DLogProof<Secp256k1, Sha256>>> {
+    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<DLogProof>> {
+        containers::BroadcastMsgsStore::new(i, n)
+    }
+}
+
+/// Hash of public keygen transcript broadcasted at the final round
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyGenConfirmation {
+    pub transcript_hash: [u8; 32],
+}
+
+/// Makes sure that every party ended up with the same public key data
+pub struct Round5 {
+    local_key: LocalKey<Secp256k1>,
+    own_confirmation: KeyGenConfirmation,
+}
+
+impl Round5 {
+    pub fn proceed(self, input: BroadcastMsgs<KeyGenConfirmation>) -> Result<LocalKey<Secp256k1>> {
+        let mismatched: Vec<u16> = input
+            .into_vec_including_me(self.own_confirmation.clone())
+            .iter()
+            .enumerate()
+            .filter(|(_, confirmation)| **confirmation != self.own_confirmation)
+            .map(|(i, _)| i as u16 + 1)
+            .collect();
+        if !mismatched.is_empty() {
+            return Err(ProceedError::Round5TranscriptMismatch { parties: mismatched });
+        }
+        Ok(self.local_key)
+    }
+    pub fn is_expensive(&self) -> bool {
+        false
+    }
+    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<KeyGenConfirmation>> {
         containers::BroadcastMsgsStore::new(i, n)
     }
 }
 
 /// Local secret obtained by party after [keygen](super::Keygen) protocol is completed
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(try_from = "SerializedLocalKey<E>")]
 pub struct LocalKey<E: Curve> {
     pub keys: Keys,
     pub shared_keys: SharedKeys,
@@ -297,6 +404,51 @@ pub struct LocalKey<E: Curve> {
     pub vss_vec: Vec<VerifiableSS<Secp256k1>>,
     pub pail_key_vec: Vec<EncryptionKey>,
     pub y_sum_s: Point<E>,
+    /// Which sets of parties are able to sign (plain `t`-of-`n` for keys created before it was
+    /// recorded)
+    pub access_structure: AccessStructure,
+    /// Party's own `N_tilde`, `h1`, `h2` setup (absent in keys created before it was introduced)
+    pub dlog_setup: Option<DLogSetup>,
+    /// `N_tilde`, `h1`, `h2` of every party
+    pub h1_h2_n_tilde_vec: Vec<DLogStatement>,
+}
+
+/// [LocalKey] as it's deserialized, fields introduced after the first release are optional
+#[derive(Deserialize)]
+struct SerializedLocalKey<E: Curve> {
+    keys: Keys,
+    shared_keys: SharedKeys,
+    party_num_int: u16,
+    vss_vec: Vec<VerifiableSS<Secp256k1>>,
+    pail_key_vec: Vec<EncryptionKey>,
+    y_sum_s: Point<E>,
+    #[serde(default)]
+    access_structure: Option<AccessStructure>,
+    #[serde(default)]
+    dlog_setup: Option<DLogSetup>,
+    #[serde(default)]
+    h1_h2_n_tilde_vec: Vec<DLogStatement>,
+}
+
+impl<E: Curve> TryFrom<SerializedLocalKey<E>> for LocalKey<E> {
+    type Error = &'static str;
+    fn try_from(local_key: SerializedLocalKey<E>) -> Result<Self, Self::Error> {
+        let access_structure = match local_key.access_structure {
+            Some(access_structure) => access_structure,
+            None => AccessStructure::of_legacy_key(&local_key.vss_vec)?,
+        };
+        Ok(LocalKey {
+            keys: local_key.keys,
+            shared_keys: local_key.shared_keys,
+            party_num_int: local_key.party_num_int,
+            vss_vec: local_key.vss_vec,
+            pail_key_vec: local_key.pail_key_vec,
+            y_sum_s: local_key.y_sum_s,
+            access_structure,
+            dlog_setup: local_key.dlog_setup,
+            h1_h2_n_tilde_vec: local_key.h1_h2_n_tilde_vec,
+        })
+    }
 }
 
 impl LocalKey<Secp256k1> {
@@ -304,6 +456,67 @@ impl LocalKey<Secp256k1> {
     pub fn public_key(&self) -> Point<Secp256k1> {
         self.y_sum_s.clone()
     }
+
+    /// Hash of public data that every party of the key must agree on: public key, commitments,
+    /// access structure, Paillier keys and `N_tilde`, `h1`, `h2` of every party
+    pub fn transcript_hash(&self) -> [u8; 32] {
+        let public_data = serde_json::to_vec(&(
+            &self.y_sum_s,
+            &self.vss_vec,
+            &self.access_structure,
+            &self.pail_key_vec,
+            &self.h1_h2_n_tilde_vec,
+        ))
+        .expect("serialization of public key data never fails");
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(
+            &Sha256::new()
+                .chain(b"gg18-keygen-transcript")
+                .chain(&public_data)
+                .finalize(),
+        );
+        hash
+    }
+
+    /// Short authentication string derived from [transcript hash](Self::transcript_hash)
+    ///
+    /// Parties of the key should compare it out loud (e.g. on a group call) before funding the
+    /// address: matching strings mean that everyone ended up with the same key.
+    pub fn short_authentication_string(&self) -> Vec<SasEmoji> {
+        sas::emoji(&self.transcript_hash()).to_vec()
+    }
+}
+
+impl From<LocalKey<Secp256k1>> for LocalKeyShare {
+    fn from(local_key: LocalKey<Secp256k1>) -> Self {
+        LocalKeyShare {
+            party_keys: local_key.keys,
+            shared_keys: local_key.shared_keys,
+            party_id: local_key.party_num_int,
+            vss_scheme_vec: local_key.vss_vec,
+            paillier_key_vector: local_key.pail_key_vec,
+            y_sum: local_key.y_sum_s,
+            access_structure: local_key.access_structure,
+            dlog_setup: local_key.dlog_setup,
+            h1_h2_n_tilde_vec: local_key.h1_h2_n_tilde_vec,
+        }
+    }
+}
+
+impl From<LocalKeyShare> for LocalKey<Secp256k1> {
+    fn from(key_share: LocalKeyShare) -> Self {
+        LocalKey {
+            keys: key_share.party_keys,
+            shared_keys: key_share.shared_keys,
+            party_num_int: key_share.party_id,
+            vss_vec: key_share.vss_scheme_vec,
+            pail_key_vec: key_share.paillier_key_vector,
+            y_sum_s: key_share.y_sum,
+            access_structure: key_share.access_structure,
+            dlog_setup: key_share.dlog_setup,
+            h1_h2_n_tilde_vec: key_share.h1_h2_n_tilde_vec,
+        }
+    }
 }
 
 // Errors
@@ -316,10 +529,89 @@ type Result<T> = std::result::Result<T, ProceedError>;
 /// every message was received and pre-validated).
 #[derive(Debug, Error)]
 pub enum ProceedError {
+    #[error("round 0: pre-params contain malformed paillier key")]
+    Round0InvalidPreParams,
     #[error("round 2: verify commitments: {0:?}")]
     Round2VerifyCommitments(ErrorType),
     #[error("round 3: verify vss construction: {0:?}")]
     Round3VerifyVssConstruct(ErrorType),
     #[error("round 4: verify dlog proof: {0:?}")]
     Round4VerifyDLogProof(ErrorType),
-}
\ No newline at end of file
+    #[error("round 5: parties {parties:?} ended up with different key")]
+    Round5TranscriptMismatch { parties: Vec<u16> },
+}
+#[cfg(test)]
+mod test {
+    use round_based::containers::MessageStore;
+
+    use super::*;
+    use crate::dkg::keygen::test::simulate_keygen;
+
+    /// Serializes `key` in the format of the first release: without access structure and
+    /// `N_tilde` setup
+    fn baseline_format<T: Serialize>(key: &T, new_fields: &[&str]) -> serde_json::Value {
+        let mut json = serde_json::to_value(key).unwrap();
+        let fields = json.as_object_mut().unwrap();
+        for field in new_fields {
+            assert!(fields.remove(*field).is_some(), "{} is missing", field);
+        }
+        json
+    }
+
+    #[test]
+    fn baseline_keys_are_loaded_as_threshold_keys() {
+        let keys = simulate_keygen(1, 3);
+        let new_fields = ["access_structure", "dlog_setup", "h1_h2_n_tilde_vec"];
+
+        let json = baseline_format(&keys[0], &new_fields);
+        let mut fields: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        fields.sort_unstable();
+        assert_eq!(
+            fields,
+            ["keys", "pail_key_vec", "party_num_int", "shared_keys", "vss_vec", "y_sum_s"]
+        );
+        let local_key: LocalKey<Secp256k1> = serde_json::from_value(json).unwrap();
+        assert_eq!(local_key.access_structure, AccessStructure::threshold(1, 3));
+        assert_eq!(local_key.y_sum_s, keys[0].y_sum_s);
+
+        let json = baseline_format(&LocalKeyShare::from(keys[1].clone()), &new_fields);
+        let key_share: LocalKeyShare = serde_json::from_value(json).unwrap();
+        assert_eq!(key_share.access_structure, AccessStructure::threshold(1, 3));
+        assert!(!key_share.has_dlog_setup());
+
+        let mut json = baseline_format(&keys[2], &new_fields);
+        json["vss_vec"] = serde_json::json!([]);
+        assert!(serde_json::from_value::<LocalKey<Secp256k1>>(json).is_err());
+    }
+
+    #[test]
+    fn round5_detects_transcript_mismatch() {
+        let keys = simulate_keygen(1, 3);
+        let mut forged = keys[2].clone();
+        forged.y_sum_s = &forged.y_sum_s + Point::generator().to_point();
+
+        let own_confirmation = KeyGenConfirmation {
+            transcript_hash: keys[0].transcript_hash(),
+        };
+        let mut store = Round5::expects_messages(1, 3);
+        for (sender, key) in [(2, &keys[1]), (3, &forged)].iter() {
+            store
+                .push_msg(Msg {
+                    sender: *sender,
+                    receiver: None,
+                    body: KeyGenConfirmation {
+                        transcript_hash: key.transcript_hash(),
+                    },
+                })
+                .unwrap();
+        }
+        let round5 = Round5 {
+            local_key: keys[0].clone(),
+            own_confirmation,
+        };
+        match round5.proceed(store.finish().unwrap()) {
+            Err(ProceedError::Round5TranscriptMismatch { parties }) => assert_eq!(parties, vec![3u16]),
+            _ => panic!("forged transcript must be detected"),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Short authentication string
+//!
+//! Encodes first 42 bits of a hash as 7 emoji taken from the same 64-emoji table that Matrix uses
+//! for device verification, so people can read it out loud and compare.
+
+use std::fmt;
+
+/// Emoji together with its name (to be pronounced)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SasEmoji {
+    pub symbol: &'static str,
+    pub name: &'static str,
+}
+
+impl fmt::Display for SasEmoji {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.symbol, self.name)
+    }
+}
+
+/// Encodes first 42 bits of `hash` as 7 emoji
+pub fn emoji(hash: &[u8; 32]) -> [SasEmoji; 7] {
+    let bits = hash[..6]
+        .iter()
+        .fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte));
+    let mut result = [EMOJI[0]; 7];
+    for (i, emoji) in result.iter_mut().enumerate() {
+        let index = (bits >> (42 - 6 * i)) & 0x3f;
+        *emoji = EMOJI[index as usize];
+    }
+    result
+}
+
+macro_rules! sas_emoji {
+    ($($symbol:literal $name:literal),* $(,)?) => {
+        [$(SasEmoji { symbol: $symbol, name: $name }),*]
+    };
+}
+
+const EMOJI: [SasEmoji; 64] = sas_emoji![
+    "🐶" "Dog", "🐱" "Cat", "🦁" "Lion", "🐎" "Horse",
+    "🦄" "Unicorn", "🐷" "Pig", "🐘" "Elephant", "🐰" "Rabbit",
+    "🐼" "Panda", "🐓" "Rooster", "🐧" "Penguin", "🐢" "Turtle",
+    "🐟" "Fish", "🐙" "Octopus", "🦋" "Butterfly", "🌷" "Flower",
+    "🌳" "Tree", "🌵" "Cactus", "🍄" "Mushroom", "🌏" "Globe",
+    "🌙" "Moon", "☁️" "Cloud", "🔥" "Fire", "🍌" "Banana",
+    "🍎" "Apple", "🍓" "Strawberry", "🌽" "Corn", "🍕" "Pizza",
+    "🎂" "Cake", "❤️" "Heart", "😀" "Smiley", "🤖" "Robot",
+    "🎩" "Hat", "👓" "Glasses", "🔧" "Spanner", "🎅" "Santa",
+    "👍" "Thumbs Up", "☂️" "Umbrella", "⌛" "Hourglass", "⏰" "Clock",
+    "🎁" "Gift", "💡" "Light Bulb", "📕" "Book", "✏️" "Pencil",
+    "📎" "Paperclip", "✂️" "Scissors", "🔒" "Lock", "🔑" "Key",
+    "🔨" "Hammer", "☎️" "Telephone", "🏁" "Flag", "🚂" "Train",
+    "🚲" "Bicycle", "✈️" "Aeroplane", "🚀" "Rocket", "🏆" "Trophy",
+    "⚽" "Ball", "🎸" "Guitar", "🎺" "Trumpet", "🔔" "Bell",
+    "⚓" "Anchor", "🎧" "Headphones", "📁" "Folder", "📌" "Pin",
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn takes_6_bits_per_emoji() {
+        // 0b000000_000001_000010_000011_000100_000101_111111 followed by 6 unused bits
+        let mut hash = [0u8; 32];
+        let bits: u64 = (1 << 30) | (2 << 24) | (3 << 18) | (4 << 12) | (5 << 6) | 63;
+        hash[..6].copy_from_slice(&(bits << 6).to_be_bytes()[2..]);
+        let names: Vec<_> = emoji(&hash).iter().map(|e| e.name).collect();
+        assert_eq!(names, ["Dog", "Cat", "Lion", "Horse", "Unicorn", "Pig", "Pin"]);
+    }
+}
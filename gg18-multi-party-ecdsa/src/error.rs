@@ -0,0 +1,176 @@
+//! Errors of the high-level API: [cli](crate::cli) protocols and top-level functions
+//!
+//! Every error has a stable numeric [code](Error::code), so that bindings (FFI) can report it
+//! in a structured way. Codes are grouped by the hundreds:
+//!
+//! | Code | Error |
+//! |------|-------|
+//! | 100  | [Config](Error::Config) |
+//! | 201  | [Group](Error::Group): [NotInGroup](GroupError::NotInGroup) |
+//! | 202  | [Group](Error::Group): [GroupSize](GroupError::GroupSize) |
+//! | 203  | [Group](Error::Group): [AccessStructureSize](GroupError::AccessStructureSize) |
+//! | 204  | [Group](Error::Group): [WeightsAndLevels](GroupError::WeightsAndLevels) |
+//! | 205  | [Group](Error::Group): [InvalidAccessStructure](GroupError::InvalidAccessStructure) |
+//! | 206  | [Group](Error::Group): [UntrustedParty](GroupError::UntrustedParty) |
+//! | 300  | [Transport](Error::Transport) |
+//! | 400  | [ProtocolAborted](Error::ProtocolAborted) |
+//! | 500  | [Storage](Error::Storage) |
+//! | 501  | [MissingDLogSetup](Error::MissingDLogSetup) |
+//! | 600  | [Cancelled](Error::Cancelled) |
+//! | 900  | [Internal](Error::Internal) |
+
+use std::error::Error as StdError;
+
+use mpc_over_signal::ComputationAborted;
+use thiserror::Error;
+
+use crate::async_runtime;
+pub use crate::config::GroupError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Configuration or arguments are invalid (e.g. malformed certificate, key or message)
+    #[error("invalid configuration: {0:#}")]
+    Config(anyhow::Error),
+    /// Group doesn't match `t`, `n` or this party
+    #[error(transparent)]
+    Group(#[from] GroupError),
+    /// Signal Server is unreachable, or connection to it was lost
+    #[error("transport: {0:#}")]
+    Transport(anyhow::Error),
+    /// Protocol was aborted because of invalid or missing messages, or another party left it
+    ///
+    /// `culprits` lists indexes (in range `[1; n]`) of parties who caused the abort. It's empty
+    /// if failure can't be attributed to certain parties.
+    #[error("protocol aborted (culprits: {culprits:?}): {reason}")]
+    ProtocolAborted {
+        culprits: Vec<u16>,
+        #[source]
+        reason: Box<dyn StdError + Send + Sync>,
+    },
+    /// Reading or writing device secrets, local key or pre-params failed
+    #[error("storage: {0:#}")]
+    Storage(anyhow::Error),
+    /// Local key doesn't have `N_tilde`, `h1`, `h2` setup of the parties, so it can't be used
+    /// for signing (e.g. key was imported from ZenGo key store)
+    #[error("local key is missing N_tilde, h1, h2 setup required for signing")]
+    MissingDLogSetup,
+    /// Execution was cancelled by caller, see [async_runtime::cancellation]
+    #[error("cancelled")]
+    Cancelled,
+    /// A bug in protocol implementation
+    #[error("internal error: {0:#}")]
+    Internal(anyhow::Error),
+}
+
+impl Error {
+    /// Stable numeric code of the error, see [module level docs](self)
+    pub fn code(&self) -> u32 {
+        match self {
+            Error::Config(_) => 100,
+            Error::Group(GroupError::NotInGroup) => 201,
+            Error::Group(GroupError::GroupSize { .. }) => 202,
+            Error::Group(GroupError::AccessStructureSize { .. }) => 203,
+            Error::Group(GroupError::WeightsAndLevels) => 204,
+            Error::Group(GroupError::InvalidAccessStructure(_)) => 205,
+            Error::Group(GroupError::UntrustedParty(_)) => 206,
+            Error::Transport(_) => 300,
+            Error::ProtocolAborted { .. } => 400,
+            Error::Storage(_) => 500,
+            Error::MissingDLogSetup => 501,
+            Error::Cancelled => 600,
+            Error::Internal(_) => 900,
+        }
+    }
+
+    /// Parties to blame for the error, empty for errors other than
+    /// [ProtocolAborted](Error::ProtocolAborted)
+    pub fn culprits(&self) -> &[u16] {
+        match self {
+            Error::ProtocolAborted { culprits, .. } => culprits,
+            _ => &[],
+        }
+    }
+}
+
+/// Error of a protocol state machine that may point at misbehaving parties
+pub trait Blame {
+    /// Indexes (in range `[1; n]`) of parties who caused the error
+    fn culprits(&self) -> Vec<u16>;
+}
+
+impl<E> From<async_runtime::Error<E, anyhow::Error, anyhow::Error>> for Error
+where
+    E: Blame + StdError + Send + Sync + 'static,
+{
+    fn from(err: async_runtime::Error<E, anyhow::Error, anyhow::Error>) -> Self {
+        use async_runtime::Error as AsyncError;
+        match err {
+            AsyncError::Recv(err) => match err.downcast::<ComputationAborted>() {
+                Ok(aborted) => Error::ProtocolAborted {
+                    culprits: vec![aborted.party],
+                    reason: Box::new(aborted),
+                },
+                Err(err) => Error::Transport(err),
+            },
+            AsyncError::Send(err) => Error::Transport(err),
+            AsyncError::RecvEof => {
+                Error::Transport(anyhow::anyhow!("connection closed before protocol is completed"))
+            }
+            AsyncError::HandleIncoming(err)
+            | AsyncError::RoundTimeout(err)
+            | AsyncError::Proceed(err)
+            | AsyncError::Finish(err) => Error::ProtocolAborted {
+                culprits: err.culprits(),
+                reason: Box::new(err),
+            },
+            AsyncError::ProceedPanicked(err) => Error::Internal(err.into()),
+            AsyncError::StateGone => Error::Internal(anyhow::anyhow!("state machine is gone")),
+            AsyncError::Cancelled => Error::Cancelled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Error)]
+    #[error("party 2 misbehaved")]
+    struct Misbehaved;
+
+    impl Blame for Misbehaved {
+        fn culprits(&self) -> Vec<u16> {
+            vec![2]
+        }
+    }
+
+    #[test]
+    fn protocol_errors_keep_culprits() {
+        let err: Error = async_runtime::Error::<_, anyhow::Error, anyhow::Error>::Proceed(
+            Misbehaved,
+        )
+        .into();
+        assert_eq!(err.code(), 400);
+        assert_eq!(err.culprits(), &[2]);
+
+        let err: Error =
+            async_runtime::Error::<Misbehaved, anyhow::Error, anyhow::Error>::RecvEof.into();
+        assert_eq!(err.code(), 300);
+        assert!(err.culprits().is_empty());
+
+        let err: Error = async_runtime::Error::<Misbehaved, anyhow::Error, anyhow::Error>::Recv(
+            ComputationAborted { party: 3 }.into(),
+        )
+        .into();
+        assert_eq!(err.code(), 400);
+        assert_eq!(err.culprits(), &[3]);
+
+        let err: Error =
+            async_runtime::Error::<Misbehaved, anyhow::Error, anyhow::Error>::Cancelled.into();
+        assert_eq!(err.code(), 600);
+    }
+}
@@ -0,0 +1,29 @@
+//! Conversion of local keys to and from formats of other GG18 implementations
+//!
+//! * [zengo] — `keys.store` file written by upstream ZenGo `gg18_keygen_client`
+//! * [tss_lib] — `LocalPartySaveData` of Binance tss-lib (ECDSA)
+//!
+//! Converted keys can be used right away, no need to re-run keygen.
+
+use thiserror::Error;
+
+use crate::common::access_structure::AccessStructureError;
+
+pub mod tss_lib;
+pub mod zengo;
+
+#[derive(Debug, Error)]
+pub enum InteropError {
+    #[error("format supports plain t-of-n threshold keys only")]
+    UnsupportedAccessStructure,
+    #[error("key doesn't contain N-tilde setup, it was created before it was introduced")]
+    MissingDLogSetup,
+    #[error("key is malformed: {0}")]
+    MalformedKey(&'static str),
+    #[error("share id {0} doesn't fit into u16")]
+    ShareIdOutOfRange(String),
+    #[error("public shares don't lie on a polynomial of degree t")]
+    InconsistentPublicShares,
+    #[error("invalid access structure: {0}")]
+    InvalidAccessStructure(#[source] AccessStructureError),
+}
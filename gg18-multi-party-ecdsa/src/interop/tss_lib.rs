@@ -0,0 +1,588 @@
+//! Binance tss-lib `LocalPartySaveData` (ECDSA keygen output)
+//!
+//! tss-lib serializes `*big.Int` values as bare JSON numbers of arbitrary length, they're
+//! represented by [TssBigInt]. Share ids `Ks` become share slots of the [AccessStructure], so
+//! they must fit into `u16`.
+//!
+//! tss-lib doesn't save Feldman commitments, only public shares `BigXj` of every party. On import,
+//! commitments to the joint sharing polynomial are interpolated out of public shares and stored as
+//! the first element of `vss_vec` (the rest commit to zero polynomials). tss-lib doesn't save the
+//! threshold either, so it has to be provided.
+
+use std::convert::TryFrom;
+use std::iter;
+
+use curv::arithmetic::traits::*;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use curv::BigInt;
+use paillier::{DecryptionKey, EncryptionKey};
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::value::RawValue;
+use zk_paillier::zkproofs::DLogStatement;
+
+use super::InteropError;
+use crate::common::access_structure::{AccessStructure, ShareSlot};
+use crate::common::party_i::{DLogSetup, Keys, LocalKeyShare, SharedKeys};
+use crate::dkg::rounds::LocalKey;
+use crate::utilities::paillier_crt::PrecomputedDecryptionKey;
+
+/// Integer serialized as a bare JSON number, the way Go serializes `*big.Int`
+///
+/// Serialization is supported by `serde_json` only.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TssBigInt(pub BigInt);
+
+impl Serialize for TssBigInt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RawValue::from_string(self.0.to_str_radix(10))
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TssBigInt {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = <Box<RawValue>>::deserialize(deserializer)?;
+        BigInt::from_str_radix(raw.get(), 10)
+            .map(TssBigInt)
+            .map_err(|_| D::Error::custom(format!("expected an integer, got {}", raw.get())))
+    }
+}
+
+impl From<BigInt> for TssBigInt {
+    fn from(n: BigInt) -> Self {
+        TssBigInt(n)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaillierPublicKey {
+    #[serde(rename = "N")]
+    pub n: TssBigInt,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaillierPrivateKey {
+    #[serde(rename = "N")]
+    pub n: TssBigInt,
+    #[serde(rename = "LambdaN")]
+    pub lambda_n: TssBigInt,
+    #[serde(rename = "PhiN")]
+    pub phi_n: TssBigInt,
+    /// Saved by newer versions of tss-lib only
+    #[serde(rename = "P", default, skip_serializing_if = "Option::is_none")]
+    pub p: Option<TssBigInt>,
+    #[serde(rename = "Q", default, skip_serializing_if = "Option::is_none")]
+    pub q: Option<TssBigInt>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ECPoint {
+    /// Saved by newer versions of tss-lib only
+    #[serde(rename = "Curve", default, skip_serializing_if = "Option::is_none")]
+    pub curve: Option<String>,
+    #[serde(rename = "Coords")]
+    pub coords: [TssBigInt; 2],
+}
+
+/// tss-lib `keygen.LocalPartySaveData`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LocalPartySaveData {
+    #[serde(rename = "PaillierSK")]
+    pub paillier_sk: PaillierPrivateKey,
+    #[serde(rename = "NTildei")]
+    pub n_tilde_i: TssBigInt,
+    #[serde(rename = "H1i")]
+    pub h1_i: TssBigInt,
+    #[serde(rename = "H2i")]
+    pub h2_i: TssBigInt,
+    #[serde(rename = "Alpha")]
+    pub alpha: TssBigInt,
+    #[serde(rename = "Beta")]
+    pub beta: TssBigInt,
+    /// `(p - 1) / 2` where `p` is a safe prime factor of `NTildei`
+    #[serde(rename = "P")]
+    pub p: TssBigInt,
+    /// `(q - 1) / 2` where `q` is a safe prime factor of `NTildei`
+    #[serde(rename = "Q")]
+    pub q: TssBigInt,
+    #[serde(rename = "Xi")]
+    pub x_i: TssBigInt,
+    #[serde(rename = "ShareID")]
+    pub share_id: TssBigInt,
+    #[serde(rename = "Ks")]
+    pub ks: Vec<TssBigInt>,
+    #[serde(rename = "NTildej")]
+    pub n_tilde_j: Vec<TssBigInt>,
+    #[serde(rename = "H1j")]
+    pub h1_j: Vec<TssBigInt>,
+    #[serde(rename = "H2j")]
+    pub h2_j: Vec<TssBigInt>,
+    #[serde(rename = "BigXj")]
+    pub big_x_j: Vec<ECPoint>,
+    #[serde(rename = "PaillierPKs")]
+    pub paillier_pks: Vec<PaillierPublicKey>,
+    #[serde(rename = "ECDSAPub")]
+    pub ecdsa_pub: ECPoint,
+}
+
+/// Converts [LocalKey] into tss-lib save data
+///
+/// Every party must hold a single share (no weighted or hierarchical structures), and the key must
+/// carry `N_tilde`, `h1`, `h2` setup of every party.
+pub fn to_tss_lib(local_key: &LocalKey<Secp256k1>) -> Result<LocalPartySaveData, InteropError> {
+    let access_structure = &local_key.access_structure;
+    let n = usize::from(access_structure.parties());
+    let dlog_setup = local_key
+        .dlog_setup
+        .as_ref()
+        .ok_or(InteropError::MissingDLogSetup)?;
+    if local_key.h1_h2_n_tilde_vec.len() != n {
+        return Err(InteropError::MissingDLogSetup);
+    }
+    if local_key.vss_vec.len() != n || local_key.pail_key_vec.len() != n {
+        return Err(InteropError::MalformedKey(
+            "number of commitments or paillier keys doesn't match number of parties",
+        ));
+    }
+    let slots = access_structure
+        .slots
+        .iter()
+        .map(|party_slots| match party_slots.as_slice() {
+            [slot @ ShareSlot { order: 0, .. }] => Ok(*slot),
+            _ => Err(InteropError::UnsupportedAccessStructure),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let joint_commitments = access_structure.joint_commitments(&local_key.vss_vec);
+    let big_x_j = slots
+        .iter()
+        .map(|slot| {
+            access_structure
+                .slot_commitment(&joint_commitments, slot)
+                .ok_or(InteropError::MalformedKey(
+                    "number of commitments doesn't match threshold",
+                ))
+                .and_then(|x_j| to_ec_point(&x_j))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let own_slot = slots
+        .get(usize::from(local_key.party_num_int).wrapping_sub(1))
+        .ok_or(InteropError::MalformedKey("party index is out of range"))?;
+
+    let dk = &local_key.keys.dk;
+    let one = BigInt::one();
+    let (p_minus_1, q_minus_1) = (&dk.p - &one, &dk.q - &one);
+    let phi_n = &p_minus_1 * &q_minus_1;
+    let lambda_n = &phi_n / gcd(&p_minus_1, &q_minus_1);
+    let two = BigInt::from(2);
+
+    Ok(LocalPartySaveData {
+        paillier_sk: PaillierPrivateKey {
+            n: TssBigInt(&dk.p * &dk.q),
+            lambda_n: TssBigInt(lambda_n),
+            phi_n: TssBigInt(phi_n),
+            p: Some(TssBigInt(dk.p.clone())),
+            q: Some(TssBigInt(dk.q.clone())),
+        },
+        n_tilde_i: TssBigInt(dlog_setup.N_tilde.clone()),
+        h1_i: TssBigInt(dlog_setup.h1.clone()),
+        h2_i: TssBigInt(dlog_setup.h2.clone()),
+        alpha: TssBigInt(dlog_setup.alpha.clone()),
+        beta: TssBigInt(dlog_setup.beta.clone()),
+        p: TssBigInt((&dlog_setup.p - &one) / &two),
+        q: TssBigInt((&dlog_setup.q - &one) / &two),
+        x_i: TssBigInt(local_key.shared_keys.x_i.to_bigint()),
+        share_id: TssBigInt(BigInt::from(u64::from(own_slot.index))),
+        ks: slots
+            .iter()
+            .map(|slot| TssBigInt(BigInt::from(u64::from(slot.index))))
+            .collect(),
+        n_tilde_j: local_key
+            .h1_h2_n_tilde_vec
+            .iter()
+            .map(|s| TssBigInt(s.N.clone()))
+            .collect(),
+        h1_j: local_key
+            .h1_h2_n_tilde_vec
+            .iter()
+            .map(|s| TssBigInt(s.g.clone()))
+            .collect(),
+        h2_j: local_key
+            .h1_h2_n_tilde_vec
+            .iter()
+            .map(|s| TssBigInt(s.ni.clone()))
+            .collect(),
+        big_x_j,
+        paillier_pks: local_key
+            .pail_key_vec
+            .iter()
+            .map(|ek| PaillierPublicKey {
+                n: TssBigInt(ek.n.clone()),
+            })
+            .collect(),
+        ecdsa_pub: to_ec_point(&local_key.y_sum_s)?,
+    })
+}
+
+/// Converts tss-lib save data of `t`-of-`n` key into [LocalKey]
+///
+/// tss-lib doesn't keep party's keygen contribution `u_i`, it's not needed for signing, so
+/// `keys.u_i` and `keys.y_i` are set to zero.
+pub fn from_tss_lib(
+    save_data: LocalPartySaveData,
+    t: u16,
+) -> Result<LocalKey<Secp256k1>, InteropError> {
+    let n = save_data.ks.len();
+    let lengths_match = [
+        save_data.n_tilde_j.len(),
+        save_data.h1_j.len(),
+        save_data.h2_j.len(),
+        save_data.big_x_j.len(),
+        save_data.paillier_pks.len(),
+    ]
+    .iter()
+    .all(|&len| len == n);
+    if !lengths_match {
+        return Err(InteropError::MalformedKey(
+            "lengths of per-party vectors don't match",
+        ));
+    }
+    let share_count =
+        u16::try_from(n).map_err(|_| InteropError::MalformedKey("too many parties"))?;
+
+    let share_ids = save_data
+        .ks
+        .iter()
+        .map(share_id_to_u16)
+        .collect::<Result<Vec<_>, _>>()?;
+    let own_share_id = share_id_to_u16(&save_data.share_id)?;
+    let own_position = share_ids
+        .iter()
+        .position(|&k| k == own_share_id)
+        .ok_or(InteropError::MalformedKey("own share id is not listed in Ks"))?;
+    let access_structure = AccessStructure::new(
+        t,
+        share_ids
+            .iter()
+            .map(|&index| vec![ShareSlot { index, order: 0 }])
+            .collect(),
+    )
+    .map_err(InteropError::InvalidAccessStructure)?;
+
+    let big_x_j = save_data
+        .big_x_j
+        .iter()
+        .map(from_ec_point)
+        .collect::<Result<Vec<_>, _>>()?;
+    let y = from_ec_point(&save_data.ecdsa_pub)?;
+
+    // restore commitments to the joint polynomial and check that every public share lies on it
+    let threshold = usize::from(t);
+    let xs: Vec<Scalar<Secp256k1>> = share_ids[..=threshold]
+        .iter()
+        .map(|&k| Scalar::from(k))
+        .collect();
+    let joint_commitments = interpolate_commitments(&xs, &big_x_j[..=threshold]);
+    let public_shares_are_consistent = access_structure
+        .slots
+        .iter()
+        .zip(&big_x_j)
+        .all(|(slots, x_j)| {
+            access_structure.slot_commitment(&joint_commitments, &slots[0]) == Some(x_j.clone())
+        });
+    if !public_shares_are_consistent {
+        return Err(InteropError::InconsistentPublicShares);
+    }
+    if joint_commitments[0] != y {
+        return Err(InteropError::MalformedKey(
+            "public key doesn't match public shares",
+        ));
+    }
+    let x_i = Scalar::<Secp256k1>::from_bigint(&save_data.x_i.0);
+    if Point::generator() * &x_i != big_x_j[own_position] {
+        return Err(InteropError::MalformedKey(
+            "secret share doesn't match public share",
+        ));
+    }
+
+    let vss_vec = iter::once(joint_commitments)
+        .chain(iter::repeat(vec![Point::zero(); threshold + 1]))
+        .take(n)
+        .map(|commitments| VerifiableSS {
+            parameters: ShamirSecretSharing {
+                threshold: t,
+                share_count,
+            },
+            commitments,
+        })
+        .collect();
+
+    let dk = paillier_decryption_key(&save_data.paillier_sk)?;
+    let ek = EncryptionKey {
+        nn: &save_data.paillier_sk.n.0 * &save_data.paillier_sk.n.0,
+        n: save_data.paillier_sk.n.0.clone(),
+    };
+    if save_data.paillier_pks[own_position].n != save_data.paillier_sk.n {
+        return Err(InteropError::MalformedKey(
+            "paillier key doesn't match own public key",
+        ));
+    }
+
+    let one = BigInt::one();
+    let two = BigInt::from(2);
+    let dlog_setup = DLogSetup {
+        N_tilde: save_data.n_tilde_i.0,
+        h1: save_data.h1_i.0,
+        h2: save_data.h2_i.0,
+        alpha: save_data.alpha.0,
+        beta: save_data.beta.0,
+        p: &save_data.p.0 * &two + &one,
+        q: &save_data.q.0 * &two + &one,
+    };
+    let h1_h2_n_tilde_vec = save_data
+        .n_tilde_j
+        .into_iter()
+        .zip(save_data.h1_j)
+        .zip(save_data.h2_j)
+        .map(|((n_tilde, h1), h2)| DLogStatement {
+            N: n_tilde.0,
+            g: h1.0,
+            ni: h2.0,
+        })
+        .collect();
+    let party_num_int = own_position as u16 + 1;
+
+    Ok(LocalKey {
+        keys: Keys {
+            u_i: Scalar::zero(),
+            y_i: Point::zero(),
+            dk,
+            ek,
+            party_index: party_num_int,
+        },
+        shared_keys: SharedKeys {
+            y: y.clone(),
+            x_i,
+            extra_x_i: vec![],
+        },
+        party_num_int,
+        vss_vec,
+        pail_key_vec: save_data
+            .paillier_pks
+            .into_iter()
+            .map(|pk| EncryptionKey {
+                nn: &pk.n.0 * &pk.n.0,
+                n: pk.n.0,
+            })
+            .collect(),
+        y_sum_s: y,
+        access_structure,
+        dlog_setup: Some(dlog_setup),
+        h1_h2_n_tilde_vec,
+    })
+}
+
+impl LocalKeyShare {
+    /// Same as [to_tss_lib], but takes [LocalKeyShare]
+    pub fn to_tss_lib(&self) -> Result<LocalPartySaveData, InteropError> {
+        to_tss_lib(&LocalKey::from(self.clone()))
+    }
+
+    /// Same as [from_tss_lib], but produces [LocalKeyShare]
+    pub fn from_tss_lib(save_data: LocalPartySaveData, t: u16) -> Result<Self, InteropError> {
+        from_tss_lib(save_data, t).map(LocalKeyShare::from)
+    }
+}
+
+fn share_id_to_u16(share_id: &TssBigInt) -> Result<u16, InteropError> {
+    let share_id = share_id.0.to_str_radix(10);
+    match share_id.parse::<u16>() {
+        Ok(k) if k != 0 => Ok(k),
+        _ => Err(InteropError::ShareIdOutOfRange(share_id)),
+    }
+}
+
+fn to_ec_point(point: &Point<Secp256k1>) -> Result<ECPoint, InteropError> {
+    match (point.x_coord(), point.y_coord()) {
+        (Some(x), Some(y)) => Ok(ECPoint {
+            curve: Some("secp256k1".to_owned()),
+            coords: [TssBigInt(x), TssBigInt(y)],
+        }),
+        _ => Err(InteropError::MalformedKey("point at infinity")),
+    }
+}
+
+fn from_ec_point(point: &ECPoint) -> Result<Point<Secp256k1>, InteropError> {
+    if matches!(&point.curve, Some(curve) if curve != "secp256k1") {
+        return Err(InteropError::MalformedKey("point is not on secp256k1"));
+    }
+    Point::from_coords(&point.coords[0].0, &point.coords[1].0)
+        .map_err(|_| InteropError::MalformedKey("point is not on secp256k1"))
+}
+
+/// Restores Paillier primes, older tss-lib versions save only `N` and `PhiN`
+fn paillier_decryption_key(
+    sk: &PaillierPrivateKey,
+) -> Result<PrecomputedDecryptionKey, InteropError> {
+    let n = &sk.n.0;
+    let (p, q) = match (&sk.p, &sk.q) {
+        (Some(p), Some(q)) => (p.0.clone(), q.0.clone()),
+        _ => {
+            // p + q = N - phi(N) + 1, p - q = sqrt((p + q)^2 - 4N)
+            let two = BigInt::from(2);
+            let sum = n - &sk.phi_n.0 + BigInt::one();
+            let discriminant = &sum * &sum - BigInt::from(4) * n;
+            if discriminant < BigInt::zero() {
+                return Err(InteropError::MalformedKey("paillier PhiN doesn't match N"));
+            }
+            let diff = isqrt(&discriminant);
+            ((&sum + &diff) / &two, (&sum - &diff) / &two)
+        }
+    };
+    if &(&p * &q) != n || p <= BigInt::one() || q <= BigInt::one() {
+        return Err(InteropError::MalformedKey("paillier primes don't match N"));
+    }
+    PrecomputedDecryptionKey::new(DecryptionKey { p, q })
+        .map_err(|_| InteropError::MalformedKey("paillier primes aren't distinct primes"))
+}
+
+/// Commitments to coefficients of a polynomial given its values in exponent at `xs`
+fn interpolate_commitments(
+    xs: &[Scalar<Secp256k1>],
+    points: &[Point<Secp256k1>],
+) -> Vec<Point<Secp256k1>> {
+    let mut commitments = vec![Point::zero(); xs.len()];
+    for (j, (x_j, point)) in xs.iter().zip(points).enumerate() {
+        // coefficients of Lagrange basis polynomial prod_{i != j} (x - x_i) / (x_j - x_i)
+        let mut basis = vec![Scalar::<Secp256k1>::from(1u16)];
+        let mut denominator = Scalar::<Secp256k1>::from(1u16);
+        for x_i in xs.iter().enumerate().filter(|(i, _)| *i != j).map(|(_, x_i)| x_i) {
+            let mut next = vec![Scalar::zero(); basis.len() + 1];
+            for (k, b) in basis.iter().enumerate() {
+                next[k + 1] = &next[k + 1] + b;
+                next[k] = &next[k] - &(b * x_i);
+            }
+            basis = next;
+            denominator = denominator * (x_j - x_i);
+        }
+        let denominator_inv = denominator
+            .invert()
+            .expect("share ids are distinct, so denominator is non-zero");
+        for (commitment, b) in commitments.iter_mut().zip(&basis) {
+            *commitment = &*commitment + point * &(b * &denominator_inv);
+        }
+    }
+    commitments
+}
+
+fn gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while b != BigInt::zero() {
+        let r = &a % &b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+fn isqrt(n: &BigInt) -> BigInt {
+    if n <= &BigInt::one() {
+        return n.clone();
+    }
+    let two = BigInt::from(2);
+    let mut x = n.clone();
+    loop {
+        let y = (&x + n / &x) / &two;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::OsRng;
+    use round_based::dev::Simulation;
+
+    use super::*;
+    use crate::dkg::keygen::{test::SESSION_ID, Keygen};
+    use crate::key_recovery::reconstruct;
+
+    fn simulate_keygen(t: u16, n: u16) -> Vec<LocalKey<Secp256k1>> {
+        let mut simulation = Simulation::new();
+        for i in 1..=n {
+            simulation.add_party(Keygen::new(SESSION_ID, i, t, n, OsRng).unwrap());
+        }
+        simulation.run().unwrap()
+    }
+
+    #[test]
+    fn tss_lib_save_data_roundtrip() {
+        let keys = simulate_keygen(1, 3);
+
+        let mut restored = vec![];
+        for key in &keys {
+            let save_data = to_tss_lib(key).unwrap();
+            let json = serde_json::to_string(&save_data).unwrap();
+            assert!(json.contains(&format!("\"ShareID\":{},", key.party_num_int)));
+
+            let parsed: LocalPartySaveData = serde_json::from_str(&json).unwrap();
+            let key2 = from_tss_lib(parsed, 1).unwrap();
+
+            assert_eq!(key2.party_num_int, key.party_num_int);
+            assert_eq!(key2.shared_keys.x_i, key.shared_keys.x_i);
+            assert_eq!(key2.y_sum_s, key.y_sum_s);
+            assert_eq!(key2.pail_key_vec, key.pail_key_vec);
+            assert_eq!(key2.keys.dk.p, key.keys.dk.p);
+            assert_eq!(key2.keys.dk.q, key.keys.dk.q);
+            assert_eq!(key2.access_structure, key.access_structure);
+            assert_eq!(
+                key2.access_structure.joint_commitments(&key2.vss_vec),
+                key.access_structure.joint_commitments(&key.vss_vec)
+            );
+            let dlog_setup = key2.dlog_setup.as_ref().unwrap();
+            assert_eq!(dlog_setup.p, key.dlog_setup.as_ref().unwrap().p);
+            assert_eq!(
+                BigInt::mod_pow(&dlog_setup.h1, &dlog_setup.alpha, &dlog_setup.N_tilde),
+                dlog_setup.h2
+            );
+            restored.push(key2);
+        }
+
+        let secret = reconstruct(&restored[1..]).unwrap();
+        assert_eq!(Point::generator() * secret, keys[0].y_sum_s);
+    }
+
+    #[test]
+    fn paillier_primes_are_restored_from_phi() {
+        let keys = simulate_keygen(1, 2);
+        let mut save_data = to_tss_lib(&keys[0]).unwrap();
+        save_data.paillier_sk.p = None;
+        save_data.paillier_sk.q = None;
+
+        let key = from_tss_lib(save_data, 1).unwrap();
+        let mut primes = [key.keys.dk.p.clone(), key.keys.dk.q.clone()];
+        let mut expected = [keys[0].keys.dk.p.clone(), keys[0].keys.dk.q.clone()];
+        primes.sort();
+        expected.sort();
+        assert_eq!(primes, expected);
+    }
+
+    #[test]
+    fn tampered_public_share_is_rejected() {
+        let keys = simulate_keygen(1, 3);
+        let mut save_data = to_tss_lib(&keys[0]).unwrap();
+        save_data.big_x_j[2] = to_ec_point(&Point::generator().to_point()).unwrap();
+        assert!(matches!(
+            from_tss_lib(save_data, 1),
+            Err(InteropError::InconsistentPublicShares)
+        ));
+    }
+}
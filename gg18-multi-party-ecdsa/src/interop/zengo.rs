@@ -0,0 +1,174 @@
+//! Upstream ZenGo multi-party-ecdsa GG18 key store
+//!
+//! `gg18_keygen_client` saves a tuple `(party_keys, shared_keys, party_id, vss_scheme_vec,
+//! paillier_key_vec, y_sum)` serialized as JSON. Our [Keys] and [SharedKeys] have the same
+//! serialization, so conversion boils down to restoring (or dropping) the access structure.
+//! Upstream keys don't carry `N_tilde`, `h1`, `h2` setup, which signing needs for range proofs,
+//! so imported keys can be exported again or reconstructed, but can't be used for signing:
+//! [OfflineStage](crate::signing::sign::OfflineStage) rejects them with
+//! [MissingDLogSetup](crate::signing::sign::Error::MissingDLogSetup) before any message is sent.
+
+use std::convert::TryFrom;
+
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point};
+use paillier::EncryptionKey;
+
+use super::InteropError;
+use crate::common::access_structure::AccessStructure;
+use crate::common::party_i::{Keys, LocalKeyShare, SharedKeys};
+use crate::dkg::rounds::LocalKey;
+
+/// Content of upstream `keys.store` file
+pub type ZenGoKeyStore = (
+    Keys,
+    SharedKeys,
+    u16,
+    Vec<VerifiableSS<Secp256k1>>,
+    Vec<EncryptionKey>,
+    Point<Secp256k1>,
+);
+
+/// Converts upstream key store into [LocalKey]
+pub fn from_zengo(key_store: ZenGoKeyStore) -> Result<LocalKey<Secp256k1>, InteropError> {
+    let (keys, shared_keys, party_id, vss_vec, pail_key_vec, y_sum) = key_store;
+
+    let n = vss_vec.len();
+    let t = vss_vec
+        .first()
+        .ok_or(InteropError::MalformedKey("vss_scheme_vec is empty"))?
+        .parameters
+        .threshold;
+    let params_are_consistent = vss_vec.iter().all(|vss| {
+        vss.parameters.threshold == t
+            && usize::from(vss.parameters.share_count) == n
+            && vss.commitments.len() == usize::from(t) + 1
+    });
+    if !params_are_consistent {
+        return Err(InteropError::MalformedKey("inconsistent vss parameters"));
+    }
+    if pail_key_vec.len() != n {
+        return Err(InteropError::MalformedKey(
+            "number of paillier keys doesn't match number of parties",
+        ));
+    }
+    if party_id == 0 || usize::from(party_id) > n || keys.party_index != party_id {
+        return Err(InteropError::MalformedKey("party index is out of range"));
+    }
+    if !shared_keys.extra_x_i.is_empty() {
+        return Err(InteropError::UnsupportedAccessStructure);
+    }
+    let access_structure = AccessStructure::threshold(t, party_count(n)?);
+    access_structure
+        .validate()
+        .map_err(InteropError::InvalidAccessStructure)?;
+
+    Ok(LocalKey {
+        keys,
+        shared_keys,
+        party_num_int: party_id,
+        vss_vec,
+        pail_key_vec,
+        y_sum_s: y_sum,
+        access_structure,
+        dlog_setup: None,
+        h1_h2_n_tilde_vec: vec![],
+    })
+}
+
+fn party_count(n: usize) -> Result<u16, InteropError> {
+    u16::try_from(n).map_err(|_| InteropError::MalformedKey("too many parties"))
+}
+
+/// Converts [LocalKey] into upstream key store
+///
+/// Only plain `t`-of-`n` keys can be converted. `N_tilde`, `h1`, `h2` setup is dropped.
+pub fn to_zengo(local_key: LocalKey<Secp256k1>) -> Result<ZenGoKeyStore, InteropError> {
+    if !local_key.access_structure.is_plain_threshold() {
+        return Err(InteropError::UnsupportedAccessStructure);
+    }
+    Ok((
+        local_key.keys,
+        local_key.shared_keys,
+        local_key.party_num_int,
+        local_key.vss_vec,
+        local_key.pail_key_vec,
+        local_key.y_sum_s,
+    ))
+}
+
+impl LocalKeyShare {
+    /// Same as [from_zengo], but produces [LocalKeyShare]
+    pub fn from_zengo(key_store: ZenGoKeyStore) -> Result<Self, InteropError> {
+        from_zengo(key_store).map(LocalKeyShare::from)
+    }
+
+    /// Same as [to_zengo], but takes [LocalKeyShare]
+    pub fn to_zengo(self) -> Result<ZenGoKeyStore, InteropError> {
+        to_zengo(LocalKey::from(self))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use curv::BigInt;
+    use rand::rngs::OsRng;
+    use round_based::dev::Simulation;
+
+    use super::*;
+    use crate::dkg::keygen::{test::SESSION_ID, Keygen};
+    use crate::signing::sign::{self, OfflineStage};
+
+    #[test]
+    fn zengo_key_store_roundtrip() {
+        let mut simulation = Simulation::new();
+        for i in 1..=3 {
+            simulation.add_party(Keygen::new(SESSION_ID, i, 1, 3, OsRng).unwrap());
+        }
+        let keys = simulation.run().unwrap();
+
+        for key in keys {
+            let key_store = to_zengo(key.clone()).unwrap();
+            let json = serde_json::to_string(&key_store).unwrap();
+            let parsed: ZenGoKeyStore = serde_json::from_str(&json).unwrap();
+            let restored = from_zengo(parsed).unwrap();
+
+            assert_eq!(restored.party_num_int, key.party_num_int);
+            assert_eq!(restored.shared_keys.x_i, key.shared_keys.x_i);
+            assert_eq!(restored.y_sum_s, key.y_sum_s);
+            assert_eq!(restored.vss_vec, key.vss_vec);
+            assert_eq!(restored.access_structure, key.access_structure);
+        }
+    }
+
+    #[test]
+    fn imported_key_is_rejected_by_signing() {
+        let mut simulation = Simulation::new();
+        for i in 1..=3 {
+            simulation.add_party(Keygen::new(SESSION_ID, i, 1, 3, OsRng).unwrap());
+        }
+        let keys = simulation.run().unwrap();
+
+        let imported = LocalKeyShare::from_zengo(to_zengo(keys[0].clone()).unwrap()).unwrap();
+        assert!(!imported.has_dlog_setup());
+        let signing = OfflineStage::new(SESSION_ID, 1, 1, imported, BigInt::from(42), OsRng);
+        assert!(matches!(signing, Err(sign::Error::MissingDLogSetup)));
+    }
+
+    #[test]
+    fn weighted_key_is_not_exported() {
+        let access_structure = AccessStructure::weighted(2, &[2, 1, 1]).unwrap();
+        let mut simulation = Simulation::new();
+        for i in 1..=3 {
+            simulation.add_party(
+                Keygen::with_access_structure(SESSION_ID, i, access_structure.clone(), OsRng)
+                    .unwrap(),
+            );
+        }
+        let keys = simulation.run().unwrap();
+        assert!(matches!(
+            to_zengo(keys[0].clone()),
+            Err(InteropError::UnsupportedAccessStructure)
+        ));
+    }
+}
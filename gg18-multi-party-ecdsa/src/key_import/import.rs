@@ -0,0 +1,537 @@
+//! Trusted-dealer import of an existing key
+//!
+//! Moves a legacy single-key wallet under MPC without changing its address. One party of the
+//! group (the dealer) holds the existing secp256k1 secret. It splits the secret into
+//! Feldman-verifiable shares and sends every party its share over the transport. Every party
+//! generates its own Paillier keys and verifies its share against the broadcasted commitments.
+//!
+//! Parties finish with the same [LocalKey] structure that [Keygen](crate::dkg::keygen::Keygen)
+//! produces, so it can be used in [signing](crate::signing::sign) right away. The dealer should
+//! destroy the original secret once import is completed.
+
+use std::fmt;
+use std::mem::replace;
+use std::time::Duration;
+
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use round_based::containers::{
+    push::{Push, PushExt},
+    *,
+};
+use rand::{CryptoRng, RngCore};
+use round_based::{IsCritical, Msg, StateMachine};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::common::transcript::SessionId;
+use crate::dkg::pre_params::PreParams;
+use crate::error::Blame;
+use crate::utilities::sampling::BoxedRng;
+
+use private::InternalError;
+pub use super::rounds::{DealerShare, ImportBroadcastMessage1, ProceedError};
+pub use crate::dkg::rounds::LocalKey;
+use super::rounds::{Round0, Round1, Round2};
+
+/// Key import protocol state machine
+pub struct KeyImport {
+    round: R,
+
+    msgs1: Option<Store<BroadcastMsgs<ImportBroadcastMessage1>>>,
+    msgs2: Option<Store<P2PMsgs<Option<DealerShare>>>>,
+
+    msgs_queue: Vec<Msg<ProtocolMessage>>,
+
+    party_i: u16,
+    party_n: u16,
+
+    rng: BoxedRng,
+}
+
+impl KeyImport {
+    /// Constructs a party of key import protocol
+    ///
+    /// Takes `session_id`, party index `i` (in range `[1; n]`), threshold value `t`, total number
+    /// of parties `n` and index of the `dealer`. Dealer must provide `secret` being imported,
+    /// other parties must not. Parties may provide `expected_public_key` (public key of imported
+    /// wallet) to make sure that dealer shares the right key. `session_id` has the same meaning
+    /// as in [Keygen::new](crate::dkg::keygen::Keygen::new), and so does `rng`.
+    ///
+    /// Returns error if:
+    /// * `n` is less than 2, returns [Error::TooFewParties]
+    /// * `t` is not in range `[1; n-1]`, returns [Error::InvalidThreshold]
+    /// * `i` is not in range `[1; n]`, returns [Error::InvalidPartyIndex]
+    /// * `dealer` is not in range `[1; n]`, returns [Error::InvalidDealerIndex]
+    /// * `secret` is provided by non-dealer or not provided by dealer, or it's zero,
+    ///   returns [Error::InvalidSecret]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<Rng>(
+        session_id: SessionId,
+        i: u16,
+        t: u16,
+        n: u16,
+        dealer: u16,
+        secret: Option<Scalar<Secp256k1>>,
+        expected_public_key: Option<Point<Secp256k1>>,
+        rng: Rng,
+    ) -> Result<Self>
+    where
+        Rng: RngCore + CryptoRng + Send + 'static,
+    {
+        if n < 2 {
+            return Err(Error::TooFewParties);
+        }
+        if t == 0 || t >= n {
+            return Err(Error::InvalidThreshold);
+        }
+        if i == 0 || i > n {
+            return Err(Error::InvalidPartyIndex);
+        }
+        if dealer == 0 || dealer > n {
+            return Err(Error::InvalidDealerIndex);
+        }
+        match &secret {
+            Some(secret) if i == dealer && !secret.is_zero() => (),
+            None if i != dealer => (),
+            _ => return Err(Error::InvalidSecret),
+        }
+        let mut state = Self {
+            round: R::Round0(Round0 {
+                session_id,
+                party_i: i,
+                t,
+                n,
+                dealer,
+                secret,
+                expected_public_key,
+                pre_params: None,
+            }),
+
+            msgs1: Some(Round1::expects_messages(i, n)),
+            msgs2: Some(Round2::expects_messages(i, n)),
+
+            msgs_queue: vec![],
+
+            party_i: i,
+            party_n: n,
+
+            rng: Box::new(rng),
+        };
+
+        state.proceed_round(false)?;
+        Ok(state)
+    }
+
+    /// Makes the party use pre-generated Paillier key and `N_tilde` setup, see
+    /// [Keygen::with_pre_params](crate::dkg::keygen::Keygen::with_pre_params)
+    pub fn with_pre_params(mut self, pre_params: PreParams) -> Self {
+        if let R::Round0(round) = &mut self.round {
+            round.pre_params = Some(pre_params);
+        }
+        self
+    }
+
+    /// Proceeds round state if it received enough messages and if it's cheap to compute or
+    /// `may_block == true`
+    fn proceed_round(&mut self, may_block: bool) -> Result<()> {
+        let store1_wants_more = self.msgs1.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+
+        let next_state: R;
+        let try_again: bool = match replace(&mut self.round, R::Gone) {
+            R::Round0(round) if !round.is_expensive() || may_block => {
+                next_state = round
+                    .proceed(&mut self.rng, gmap_queue(&mut self.msgs_queue, M::Round1))
+                    .map(R::Round1)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round0(_) => {
+                next_state = s;
+                false
+            }
+            R::Round1(round) if !store1_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs1.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs, gmap_queue(&mut self.msgs_queue, M::Round2))
+                    .map(R::Round2)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round1(_) => {
+                next_state = s;
+                false
+            }
+            R::Round2(round) if !store2_wants_more && (!round.is_expensive() || may_block) => {
+                let store = self.msgs2.take().ok_or(InternalError::StoreGone)?;
+                let msgs = store
+                    .finish()
+                    .map_err(InternalError::RetrieveRoundMessages)?;
+                next_state = round
+                    .proceed(msgs)
+                    .map(R::Final)
+                    .map_err(Error::ProceedRound)?;
+                true
+            }
+            s @ R::Round2(_) => {
+                next_state = s;
+                false
+            }
+            s @ R::Final(_) | s @ R::Gone => {
+                next_state = s;
+                false
+            }
+        };
+
+        self.round = next_state;
+        if try_again {
+            self.proceed_round(may_block)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Wraps round messages into [ProtocolMessage]
+fn gmap_queue<'a, T, F>(
+    queue: &'a mut Vec<Msg<ProtocolMessage>>,
+    mut f: F,
+) -> impl Push<Msg<T>> + 'a
+where
+    F: FnMut(T) -> M + 'a,
+{
+    queue.gmap(move |m: Msg<T>| m.map_body(|m| ProtocolMessage(f(m))))
+}
+
+impl StateMachine for KeyImport {
+    type MessageBody = ProtocolMessage;
+    type Err = Error;
+    type Output = LocalKey<Secp256k1>;
+
+    fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+        let current_round = self.current_round();
+
+        match msg.body {
+            ProtocolMessage(M::Round1(m)) => {
+                let store = self
+                    .msgs1
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 1,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
+            ProtocolMessage(M::Round2(m)) => {
+                let store = self
+                    .msgs2
+                    .as_mut()
+                    .ok_or(Error::ReceivedOutOfOrderMessage {
+                        current_round,
+                        msg_round: 2,
+                    })?;
+                store
+                    .push_msg(Msg {
+                        sender: msg.sender,
+                        receiver: msg.receiver,
+                        body: m,
+                    })
+                    .map_err(Error::HandleMessage)?;
+                self.proceed_round(false)
+            }
+        }
+    }
+
+    fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+        &mut self.msgs_queue
+    }
+
+    fn wants_to_proceed(&self) -> bool {
+        let store1_wants_more = self.msgs1.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+        let store2_wants_more = self.msgs2.as_ref().map(|s| s.wants_more()).unwrap_or(false);
+
+        match &self.round {
+            R::Round0(_) => true,
+            R::Round1(_) => !store1_wants_more,
+            R::Round2(_) => !store2_wants_more,
+            R::Final(_) | R::Gone => false,
+        }
+    }
+
+    fn proceed(&mut self) -> Result<()> {
+        self.proceed_round(true)
+    }
+
+    fn round_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    fn round_timeout_reached(&mut self) -> Self::Err {
+        panic!("no timeout was set")
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.round, R::Final(_))
+    }
+
+    fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+        match self.round {
+            R::Final(_) => (),
+            R::Gone => return Some(Err(Error::DoublePickOutput)),
+            _ => return None,
+        }
+
+        match replace(&mut self.round, R::Gone) {
+            R::Final(result) => Some(Ok(result)),
+            _ => unreachable!("guaranteed by match expression above"),
+        }
+    }
+
+    fn current_round(&self) -> u16 {
+        match &self.round {
+            R::Round0(_) => 0,
+            R::Round1(_) => 1,
+            R::Round2(_) => 2,
+            R::Final(_) | R::Gone => 3,
+        }
+    }
+
+    fn total_rounds(&self) -> Option<u16> {
+        Some(2)
+    }
+
+    fn party_ind(&self) -> u16 {
+        self.party_i
+    }
+
+    fn parties(&self) -> u16 {
+        self.party_n
+    }
+}
+
+impl crate::dkg::traits::RoundBlame for KeyImport {
+    /// Returns number of unwilling parties and a vector of their party indexes.
+    fn round_blame(&self) -> (u16, Vec<u16>) {
+        let store1_blame = self.msgs1.as_ref().map(|s| s.blame()).unwrap_or_default();
+        let store2_blame = self.msgs2.as_ref().map(|s| s.blame()).unwrap_or_default();
+
+        let default = (0, vec![]);
+        match &self.round {
+            R::Round0(_) => default,
+            R::Round1(_) => store1_blame,
+            R::Round2(_) => store2_blame,
+            R::Final(_) | R::Gone => default,
+        }
+    }
+}
+
+impl fmt::Debug for KeyImport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            R::Round0(_) => "0",
+            R::Round1(_) => "1",
+            R::Round2(_) => "2",
+            R::Final(_) => "[Final]",
+            R::Gone => "[Gone]",
+        };
+        let msgs1 = match self.msgs1.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
+        let msgs2 = match self.msgs2.as_ref() {
+            Some(msgs) => format!("[{}/{}]", msgs.messages_received(), msgs.messages_total()),
+            None => "[None]".into(),
+        };
+        write!(
+            f,
+            "{{KeyImport at round={} msgs1={} msgs2={} queue=[len={}]}}",
+            current_round,
+            msgs1,
+            msgs2,
+            self.msgs_queue.len()
+        )
+    }
+}
+
+// Rounds
+
+enum R {
+    Round0(Round0),
+    Round1(Round1),
+    Round2(Round2),
+    Final(LocalKey<Secp256k1>),
+    Gone,
+}
+
+// Messages
+
+/// Protocol message which parties send on wire
+///
+/// Hides actual messages structure so it could be changed without breaking semver policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolMessage(M);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum M {
+    Round1(ImportBroadcastMessage1),
+    Round2(Option<DealerShare>),
+}
+
+// Error
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Error type of key import protocol
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Round proceeding resulted in error
+    #[error("proceed round: {0}")]
+    ProceedRound(#[source] ProceedError),
+
+    /// Too few parties (`n < 2`)
+    #[error("at least 2 parties are required for key import")]
+    TooFewParties,
+    /// Threshold value `t` is not in range `[1; n-1]`
+    #[error("threshold is not in range [1; n-1]")]
+    InvalidThreshold,
+    /// Party index `i` is not in range `[1; n]`
+    #[error("party index is not in range [1; n]")]
+    InvalidPartyIndex,
+    /// Dealer index is not in range `[1; n]`
+    #[error("dealer index is not in range [1; n]")]
+    InvalidDealerIndex,
+    /// Secret must be non-zero and provided by the dealer only
+    #[error("secret must be non-zero and provided by the dealer only")]
+    InvalidSecret,
+
+    /// Received message didn't pass pre-validation
+    #[error("received message didn't pass pre-validation: {0}")]
+    HandleMessage(#[source] StoreErr),
+    /// Received message which we didn't expect to receive now (e.g. message from previous round)
+    #[error(
+        "didn't expect to receive message from round {msg_round} (being at round {current_round})"
+    )]
+    ReceivedOutOfOrderMessage { current_round: u16, msg_round: u16 },
+    /// [KeyImport::pick_output] called twice
+    #[error("pick_output called twice")]
+    DoublePickOutput,
+
+    /// Some internal assertions were failed, which is a bug
+    #[doc(hidden)]
+    #[error("internal error: {0:?}")]
+    InternalError(InternalError),
+}
+
+impl IsCritical for Error {
+    fn is_critical(&self) -> bool {
+        true
+    }
+}
+
+impl Blame for Error {
+    /// Errors caused by the dealer (missing or invalid commitments and shares) aren't attributed,
+    /// as the dealer is known to every party anyway
+    fn culprits(&self) -> Vec<u16> {
+        match self {
+            Error::ProceedRound(ProceedError::Round1VerifyCorrectKey { party })
+            | Error::ProceedRound(ProceedError::Round1VerifyDLogSetup { party })
+            | Error::ProceedRound(ProceedError::Round1UnexpectedCommitments { party }) => {
+                vec![*party]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+impl From<InternalError> for Error {
+    fn from(err: InternalError) -> Self {
+        Self::InternalError(err)
+    }
+}
+
+mod private {
+    #[derive(Debug)]
+    #[non_exhaustive]
+    pub enum InternalError {
+        /// [Messages store](super::MessageStore) reported that it received all messages it wanted to receive,
+        /// but refused to return message container
+        RetrieveRoundMessages(super::StoreErr),
+        #[doc(hidden)]
+        StoreGone,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::OsRng;
+    use round_based::dev::Simulation;
+
+    use super::*;
+    use crate::dkg::keygen::test::SESSION_ID;
+
+    fn simulate_import(t: u16, n: u16, dealer: u16) -> (Scalar<Secp256k1>, Vec<LocalKey<Secp256k1>>) {
+        let secret = Scalar::<Secp256k1>::random();
+        let public_key = Point::generator() * &secret;
+
+        let mut simulation = Simulation::new();
+        for i in 1..=n {
+            let (secret, expected) = if i == dealer {
+                (Some(secret.clone()), None)
+            } else {
+                (None, Some(public_key.clone()))
+            };
+            simulation.add_party(
+                KeyImport::new(SESSION_ID, i, t, n, dealer, secret, expected, OsRng).unwrap(),
+            );
+        }
+
+        (secret, simulation.run().unwrap())
+    }
+
+    #[test]
+    fn simulate_import_t1_n3() {
+        let (secret, keys) = simulate_import(1, 3, 2);
+        let public_key = Point::generator() * &secret;
+        for key in &keys {
+            assert_eq!(key.public_key(), public_key);
+            assert_eq!(
+                Point::generator() * &key.shared_keys.x_i,
+                crate::common::party_i::Keys::get_commitments_to_xi(&key.vss_vec)
+                    [usize::from(key.party_num_int - 1)]
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_expected_public_key_is_rejected() {
+        let secret = Scalar::<Secp256k1>::random();
+        let mut simulation = Simulation::new();
+        simulation
+            .add_party(KeyImport::new(SESSION_ID, 1, 1, 2, 1, Some(secret), None, OsRng).unwrap());
+        simulation.add_party(
+            KeyImport::new(
+                SESSION_ID,
+                2,
+                1,
+                2,
+                1,
+                None,
+                Some(Point::generator().to_point()),
+                OsRng,
+            )
+            .unwrap(),
+        );
+        assert!(simulation.run().is_err());
+    }
+}
@@ -0,0 +1,366 @@
+use curv::arithmetic::traits::*;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use curv::BigInt;
+use paillier::EncryptionKey;
+use rand::{CryptoRng, RngCore};
+use round_based::containers::push::Push;
+use round_based::containers::{self, BroadcastMsgs, P2PMsgs, Store};
+use round_based::Msg;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zk_paillier::zkproofs::{DLogStatement, NiCorrectKeyProof};
+
+use crate::common::access_structure::AccessStructure;
+use crate::common::party_i::{DLogSetup, Keys, SharedKeys};
+use crate::common::transcript::{ProofContext, SessionId};
+use crate::dkg::pre_params::PreParams;
+use crate::dkg::rounds::LocalKey;
+use crate::utilities::paillier_crt::PrecomputedDecryptionKey;
+use crate::utilities::sampling::{paillier_keypair, sample_scalar, PAILLIER_KEY_SIZE};
+use crate::utilities::zk_composite_dlog::CompositeDLogProof;
+
+/// Message broadcasted by every party at round 1
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImportBroadcastMessage1 {
+    pub e: EncryptionKey,
+    pub dlog_statement: DLogStatement,
+    pub correct_key_proof: NiCorrectKeyProof,
+    pub composite_dlog_proof_base_h1: CompositeDLogProof,
+    pub composite_dlog_proof_base_h2: CompositeDLogProof,
+    /// Feldman commitments to the dealt key, set by the dealer only
+    pub dealer_commitments: Option<Vec<VerifiableSS<Secp256k1>>>,
+}
+
+/// Shares delivered by the dealer to a single party at round 2
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DealerShare {
+    /// Additive part of the key attributed to the recipient
+    pub u_i: Scalar<Secp256k1>,
+    /// Recipient's share of every additive part
+    pub shares: Vec<Scalar<Secp256k1>>,
+}
+
+/// Secret material generated by the dealer out of the imported key
+///
+/// Imported key `x` is split into `n` additive parts `u_1 + ... + u_n = x`, every part is then
+/// shared via Feldman VSS. That way parties end up with exactly the same [LocalKey] structure as
+/// if they carried out [Keygen](crate::dkg::keygen::Keygen) with contributions `u_i`.
+struct Dealing {
+    u_vec: Vec<Scalar<Secp256k1>>,
+    vss_vec: Vec<VerifiableSS<Secp256k1>>,
+    shares: Vec<Vec<Scalar<Secp256k1>>>,
+}
+
+impl Dealing {
+    fn deal<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        secret: &Scalar<Secp256k1>,
+        t: u16,
+        n: u16,
+    ) -> Self {
+        let mut u_vec: Vec<Scalar<Secp256k1>> = (1..n).map(|_| sample_scalar(rng)).collect();
+        let u_sum: Scalar<Secp256k1> = u_vec.iter().sum();
+        u_vec.push(secret - &u_sum);
+
+        let access_structure = AccessStructure::threshold(t, n);
+        let (vss_vec, shares): (Vec<_>, Vec<_>) = u_vec
+            .iter()
+            .map(|u_i| {
+                let (vss, shares) = access_structure.share(rng, u_i);
+                (vss, shares.into_iter().flatten().collect::<Vec<_>>())
+            })
+            .unzip();
+        Self {
+            u_vec,
+            vss_vec,
+            shares,
+        }
+    }
+
+    fn share_of(&self, party_i: u16) -> DealerShare {
+        let i = usize::from(party_i - 1);
+        DealerShare {
+            u_i: self.u_vec[i].clone(),
+            shares: self.shares.iter().map(|s| s[i].clone()).collect(),
+        }
+    }
+}
+
+pub struct Round0 {
+    pub session_id: SessionId,
+    pub party_i: u16,
+    pub t: u16,
+    pub n: u16,
+    pub dealer: u16,
+    pub secret: Option<Scalar<Secp256k1>>,
+    pub expected_public_key: Option<Point<Secp256k1>>,
+    pub pre_params: Option<PreParams>,
+}
+
+impl Round0 {
+    pub fn proceed<R, O>(self, rng: &mut R, mut output: O) -> Result<Round1>
+    where
+        R: RngCore + CryptoRng,
+        O: Push<Msg<ImportBroadcastMessage1>>,
+    {
+        let ctx = ProofContext::new(self.session_id, self.party_i);
+        let (ek, dk, dlog_setup) = match &self.pre_params {
+            Some(pre) => {
+                let dk = PrecomputedDecryptionKey::new(pre.dk.clone())
+                    .map_err(|_| ProceedError::Round0InvalidPreParams)?;
+                (pre.ek.clone(), dk, pre.dlog_setup.clone())
+            }
+            None => {
+                let (ek, dk) = paillier_keypair(rng, PAILLIER_KEY_SIZE);
+                let dk = PrecomputedDecryptionKey::new(dk)
+                    .expect("generated paillier primes are distinct");
+                (ek, dk, DLogSetup::generate(rng))
+            }
+        };
+        let correct_key_proof =
+            NiCorrectKeyProof::proof(&dk, Some(BigInt::from_bytes(&ctx.salt())));
+        let (composite_dlog_proof_base_h1, composite_dlog_proof_base_h2) =
+            dlog_setup.prove(rng, &ctx);
+
+        let dealing = self
+            .secret
+            .as_ref()
+            .map(|secret| Dealing::deal(rng, secret, self.t, self.n));
+        let expected_public_key = match &self.secret {
+            Some(secret) => Some(Point::generator() * secret),
+            None => self.expected_public_key,
+        };
+
+        let bc1 = ImportBroadcastMessage1 {
+            e: ek.clone(),
+            dlog_statement: dlog_setup.statement(),
+            correct_key_proof,
+            composite_dlog_proof_base_h1,
+            composite_dlog_proof_base_h2,
+            dealer_commitments: dealing.as_ref().map(|d| d.vss_vec.clone()),
+        };
+        output.push(Msg {
+            sender: self.party_i,
+            receiver: None,
+            body: bc1.clone(),
+        });
+
+        Ok(Round1 {
+            ctx,
+            dk,
+            ek,
+            dlog_setup,
+            bc1,
+            dealing,
+            expected_public_key,
+            party_i: self.party_i,
+            t: self.t,
+            n: self.n,
+            dealer: self.dealer,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+}
+
+pub struct Round1 {
+    ctx: ProofContext,
+    dk: PrecomputedDecryptionKey,
+    ek: EncryptionKey,
+    dlog_setup: DLogSetup,
+    bc1: ImportBroadcastMessage1,
+    dealing: Option<Dealing>,
+    expected_public_key: Option<Point<Secp256k1>>,
+
+    party_i: u16,
+    t: u16,
+    n: u16,
+    dealer: u16,
+}
+
+impl Round1 {
+    pub fn proceed<O>(
+        self,
+        input: BroadcastMsgs<ImportBroadcastMessage1>,
+        mut output: O,
+    ) -> Result<Round2>
+    where
+        O: Push<Msg<Option<DealerShare>>>,
+    {
+        let bc_vec = input.into_vec_including_me(self.bc1);
+
+        for (i, bc) in bc_vec.iter().enumerate() {
+            let party = i as u16 + 1;
+            let party_ctx = self.ctx.of_party(party);
+            if bc
+                .correct_key_proof
+                .verify(&bc.e, &party_ctx.salt())
+                .is_err()
+            {
+                return Err(ProceedError::Round1VerifyCorrectKey { party });
+            }
+            if !DLogSetup::verify_proofs(
+                &party_ctx,
+                &bc.dlog_statement,
+                &bc.composite_dlog_proof_base_h1,
+                &bc.composite_dlog_proof_base_h2,
+            ) {
+                return Err(ProceedError::Round1VerifyDLogSetup { party });
+            }
+            if party != self.dealer && bc.dealer_commitments.is_some() {
+                return Err(ProceedError::Round1UnexpectedCommitments { party });
+            }
+        }
+
+        let vss_vec = bc_vec[usize::from(self.dealer - 1)]
+            .dealer_commitments
+            .clone()
+            .ok_or(ProceedError::Round1MissingCommitments)?;
+        let well_formed = vss_vec.len() == usize::from(self.n)
+            && vss_vec.iter().all(|vss| {
+                vss.parameters.threshold == self.t
+                    && vss.parameters.share_count == self.n
+                    && vss.commitments.len() == usize::from(self.t) + 1
+            });
+        if !well_formed {
+            return Err(ProceedError::Round1InvalidCommitments);
+        }
+        let y: Point<Secp256k1> = vss_vec.iter().map(|vss| &vss.commitments[0]).sum();
+        if let Some(expected) = &self.expected_public_key {
+            if *expected != y {
+                return Err(ProceedError::Round1PublicKeyMismatch);
+            }
+        }
+
+        for j in (1..=self.n).filter(|&j| j != self.party_i) {
+            output.push(Msg {
+                sender: self.party_i,
+                receiver: Some(j),
+                body: self.dealing.as_ref().map(|d| d.share_of(j)),
+            });
+        }
+        let own_share = self.dealing.as_ref().map(|d| d.share_of(self.party_i));
+
+        Ok(Round2 {
+            dk: self.dk,
+            ek: self.ek,
+            dlog_setup: self.dlog_setup,
+            h1_h2_n_tilde_vec: bc_vec.iter().map(|bc| bc.dlog_statement.clone()).collect(),
+            pail_key_vec: bc_vec.into_iter().map(|bc| bc.e).collect(),
+            vss_vec,
+            y,
+            own_share,
+            party_i: self.party_i,
+            t: self.t,
+            n: self.n,
+            dealer: self.dealer,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<ImportBroadcastMessage1>> {
+        containers::BroadcastMsgsStore::new(i, n)
+    }
+}
+
+pub struct Round2 {
+    dk: PrecomputedDecryptionKey,
+    ek: EncryptionKey,
+    dlog_setup: DLogSetup,
+    pail_key_vec: Vec<EncryptionKey>,
+    h1_h2_n_tilde_vec: Vec<DLogStatement>,
+    vss_vec: Vec<VerifiableSS<Secp256k1>>,
+    y: Point<Secp256k1>,
+    own_share: Option<DealerShare>,
+
+    party_i: u16,
+    t: u16,
+    n: u16,
+    dealer: u16,
+}
+
+impl Round2 {
+    pub fn proceed(self, input: P2PMsgs<Option<DealerShare>>) -> Result<LocalKey<Secp256k1>> {
+        let dealer_share = input
+            .into_vec_including_me(self.own_share)
+            .swap_remove(usize::from(self.dealer - 1))
+            .ok_or(ProceedError::Round2MissingShare)?;
+
+        // verify received share against broadcasted commitments
+        let share_is_valid = dealer_share.shares.len() == usize::from(self.n)
+            && self
+                .vss_vec
+                .iter()
+                .zip(&dealer_share.shares)
+                .all(|(vss, share)| vss.validate_share(share, self.party_i).is_ok())
+            && Point::generator() * &dealer_share.u_i
+                == self.vss_vec[usize::from(self.party_i - 1)].commitments[0];
+        if !share_is_valid {
+            return Err(ProceedError::Round2InvalidShare);
+        }
+
+        let x_i: Scalar<Secp256k1> = dealer_share.shares.iter().sum();
+        let keys = Keys {
+            y_i: Point::generator() * &dealer_share.u_i,
+            u_i: dealer_share.u_i,
+            dk: self.dk,
+            ek: self.ek,
+            party_index: self.party_i,
+        };
+
+        Ok(LocalKey {
+            keys,
+            shared_keys: SharedKeys {
+                y: self.y.clone(),
+                x_i,
+                extra_x_i: vec![],
+            },
+            party_num_int: self.party_i,
+            vss_vec: self.vss_vec,
+            pail_key_vec: self.pail_key_vec,
+            y_sum_s: self.y,
+            access_structure: AccessStructure::threshold(self.t, self.n),
+            dlog_setup: Some(self.dlog_setup),
+            h1_h2_n_tilde_vec: self.h1_h2_n_tilde_vec,
+        })
+    }
+    pub fn is_expensive(&self) -> bool {
+        true
+    }
+    pub fn expects_messages(i: u16, n: u16) -> Store<P2PMsgs<Option<DealerShare>>> {
+        containers::P2PMsgsStore::new(i, n)
+    }
+}
+
+// Errors
+
+type Result<T> = std::result::Result<T, ProceedError>;
+
+/// Proceeding protocol error
+///
+/// Subset of [import errors](enum@super::import::Error) that can occur at protocol proceeding
+/// (i.e. after every message was received and pre-validated).
+#[derive(Debug, Error)]
+pub enum ProceedError {
+    #[error("round 0: pre-params contain malformed paillier key")]
+    Round0InvalidPreParams,
+    #[error("round 1: party {party} sent invalid paillier key proof")]
+    Round1VerifyCorrectKey { party: u16 },
+    #[error("round 1: party {party} sent invalid h1, h2 proofs")]
+    Round1VerifyDLogSetup { party: u16 },
+    #[error("round 1: party {party} is not a dealer, but sent commitments")]
+    Round1UnexpectedCommitments { party: u16 },
+    #[error("round 1: dealer didn't send commitments")]
+    Round1MissingCommitments,
+    #[error("round 1: dealer's commitments are malformed")]
+    Round1InvalidCommitments,
+    #[error("round 1: dealt key doesn't match expected public key")]
+    Round1PublicKeyMismatch,
+    #[error("round 2: dealer didn't send a share")]
+    Round2MissingShare,
+    #[error("round 2: dealer's share doesn't match commitments")]
+    Round2InvalidShare,
+}
@@ -0,0 +1,291 @@
+//! Encodings of the reconstructed private key
+//!
+//! * raw hex — 32 bytes big-endian
+//! * [WIF] — Bitcoin Wallet Import Format (compressed public key)
+//! * [Keystore] — Ethereum Web3 Secret Storage (version 3): scrypt + aes-128-ctr
+//!
+//! [WIF]: https://en.bitcoin.it/wiki/Wallet_import_format
+
+use std::str::FromStr;
+
+use aes::Aes128;
+use ctr::cipher::{NewCipher, StreamCipher};
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use curv::BigInt;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use thiserror::Error;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+/// Scrypt cost parameter of produced keystores (`n = 2^18`), the same as geth uses by default
+const SCRYPT_LOG_N: u8 = 18;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+const SCRYPT_DKLEN: usize = 32;
+
+/// Format of exported key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Hex,
+    Wif,
+    Keystore,
+}
+
+impl FromStr for ExportFormat {
+    type Err = ExportError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(ExportFormat::Hex),
+            "wif" => Ok(ExportFormat::Wif),
+            "keystore" => Ok(ExportFormat::Keystore),
+            _ => Err(ExportError::UnknownFormat(s.to_owned())),
+        }
+    }
+}
+
+/// Bitcoin network the WIF key is intended for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+}
+
+impl Network {
+    fn wif_prefix(self) -> u8 {
+        match self {
+            Network::Mainnet => 0x80,
+            Network::Testnet => 0xef,
+        }
+    }
+}
+
+/// Private key as 32 bytes big-endian
+pub fn to_bytes(secret: &Scalar<Secp256k1>) -> [u8; 32] {
+    let bytes = secret.to_bigint().to_bytes();
+    let mut padded = [0u8; 32];
+    padded[32 - bytes.len()..].copy_from_slice(&bytes);
+    padded
+}
+
+/// Hex-encoded private key
+pub fn to_hex(secret: &Scalar<Secp256k1>) -> String {
+    hex::encode(to_bytes(secret))
+}
+
+/// Private key in Wallet Import Format (corresponding to compressed public key)
+pub fn to_wif(secret: &Scalar<Secp256k1>, network: Network) -> String {
+    let mut payload = Vec::with_capacity(38);
+    payload.push(network.wif_prefix());
+    payload.extend_from_slice(&to_bytes(secret));
+    payload.push(0x01);
+    let checksum = Sha256::digest(&Sha256::digest(&payload));
+    payload.extend_from_slice(&checksum[..4]);
+    bs58::encode(payload).into_string()
+}
+
+/// Ethereum address corresponding to the key
+pub fn eth_address(public_key: &Point<Secp256k1>) -> [u8; 20] {
+    let uncompressed = public_key.to_bytes(false);
+    let hash = Keccak256::digest(&uncompressed[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// Web3 Secret Storage (version 3)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u8,
+    pub id: String,
+    pub address: String,
+    pub crypto: KeystoreCrypto,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeystoreCrypto {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    pub ciphertext: String,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    pub mac: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CipherParams {
+    pub iv: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub dklen: usize,
+    pub n: u32,
+    pub r: u32,
+    pub p: u32,
+    pub salt: String,
+}
+
+impl Keystore {
+    /// Encrypts private key with a password
+    pub fn encrypt(secret: &Scalar<Secp256k1>, password: &[u8]) -> Result<Self, ExportError> {
+        Self::encrypt_with_cost(secret, password, SCRYPT_LOG_N)
+    }
+
+    fn encrypt_with_cost(
+        secret: &Scalar<Secp256k1>,
+        password: &[u8],
+        log_n: u8,
+    ) -> Result<Self, ExportError> {
+        let mut salt = [0u8; 32];
+        let mut iv = [0u8; 16];
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut iv);
+        OsRng.fill_bytes(&mut id);
+        // uuid version 4
+        id[6] = (id[6] & 0x0f) | 0x40;
+        id[8] = (id[8] & 0x3f) | 0x80;
+
+        let derived_key = derive_key(password, &salt, log_n, SCRYPT_R, SCRYPT_P)?;
+        let mut ciphertext = to_bytes(secret).to_vec();
+        Aes128Ctr::new(derived_key[..16].into(), iv[..].into()).apply_keystream(&mut ciphertext);
+        let mac = keystore_mac(&derived_key, &ciphertext);
+
+        let id = hex::encode(id);
+        Ok(Keystore {
+            version: 3,
+            id: format!(
+                "{}-{}-{}-{}-{}",
+                &id[..8],
+                &id[8..12],
+                &id[12..16],
+                &id[16..20],
+                &id[20..]
+            ),
+            address: hex::encode(eth_address(&(Point::generator() * secret))),
+            crypto: KeystoreCrypto {
+                cipher: "aes-128-ctr".into(),
+                cipherparams: CipherParams {
+                    iv: hex::encode(iv),
+                },
+                ciphertext: hex::encode(ciphertext),
+                kdf: "scrypt".into(),
+                kdfparams: KdfParams {
+                    dklen: SCRYPT_DKLEN,
+                    n: 1 << log_n,
+                    r: SCRYPT_R,
+                    p: SCRYPT_P,
+                    salt: hex::encode(salt),
+                },
+                mac: hex::encode(mac),
+            },
+        })
+    }
+
+    /// Decrypts private key with a password
+    pub fn decrypt(&self, password: &[u8]) -> Result<Scalar<Secp256k1>, ExportError> {
+        let crypto = &self.crypto;
+        if self.version != 3
+            || crypto.cipher != "aes-128-ctr"
+            || crypto.kdf != "scrypt"
+            || crypto.kdfparams.dklen != SCRYPT_DKLEN
+            || !crypto.kdfparams.n.is_power_of_two()
+        {
+            return Err(ExportError::UnsupportedKeystore);
+        }
+        let decode = |s: &str| hex::decode(s).map_err(|_| ExportError::MalformedKeystore);
+        let salt = decode(&crypto.kdfparams.salt)?;
+        let iv = decode(&crypto.cipherparams.iv)?;
+        let mut plaintext = decode(&crypto.ciphertext)?;
+        let mac = decode(&crypto.mac)?;
+        if iv.len() != 16 || plaintext.len() != 32 {
+            return Err(ExportError::MalformedKeystore);
+        }
+
+        let log_n = crypto.kdfparams.n.trailing_zeros() as u8;
+        let derived_key = derive_key(
+            password,
+            &salt,
+            log_n,
+            crypto.kdfparams.r,
+            crypto.kdfparams.p,
+        )?;
+        if keystore_mac(&derived_key, &plaintext)[..] != mac[..] {
+            return Err(ExportError::WrongPassword);
+        }
+        Aes128Ctr::new(derived_key[..16].into(), iv[..].into()).apply_keystream(&mut plaintext);
+
+        let secret = BigInt::from_bytes(&plaintext);
+        if secret == BigInt::zero() || &secret >= Scalar::<Secp256k1>::group_order() {
+            return Err(ExportError::MalformedKeystore);
+        }
+        Ok(Scalar::from_bigint(&secret))
+    }
+}
+
+fn derive_key(
+    password: &[u8],
+    salt: &[u8],
+    log_n: u8,
+    r: u32,
+    p: u32,
+) -> Result<[u8; SCRYPT_DKLEN], ExportError> {
+    let params =
+        scrypt::Params::new(log_n, r, p).map_err(|_| ExportError::UnsupportedKeystore)?;
+    let mut derived_key = [0u8; SCRYPT_DKLEN];
+    scrypt::scrypt(password, salt, &params, &mut derived_key)
+        .map_err(|_| ExportError::UnsupportedKeystore)?;
+    Ok(derived_key)
+}
+
+fn keystore_mac(derived_key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut mac = [0u8; 32];
+    mac.copy_from_slice(&Keccak256::new().chain(&derived_key[16..32]).chain(ciphertext).finalize());
+    mac
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("unknown export format {0:?}, expected one of: hex, wif, keystore")]
+    UnknownFormat(String),
+    #[error("keystore uses unsupported cipher or kdf parameters")]
+    UnsupportedKeystore,
+    #[error("keystore is malformed")]
+    MalformedKeystore,
+    #[error("wrong keystore password")]
+    WrongPassword,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wif_matches_test_vector() {
+        // https://en.bitcoin.it/wiki/Wallet_import_format
+        let secret = Scalar::<Secp256k1>::from_bigint(
+            &BigInt::from_hex("0C28FCA386C7A227600B2FE50B7CAE11EC86D3BF1FBE471BE89827E19D72AA1D")
+                .unwrap(),
+        );
+        assert_eq!(
+            to_wif(&secret, Network::Mainnet),
+            "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617"
+        );
+    }
+
+    #[test]
+    fn keystore_roundtrip() {
+        let secret = Scalar::<Secp256k1>::random();
+        // low scrypt cost to keep the test fast
+        let keystore = Keystore::encrypt_with_cost(&secret, b"correct horse", 10).unwrap();
+        assert_eq!(keystore.decrypt(b"correct horse").unwrap(), secret);
+        assert!(matches!(
+            keystore.decrypt(b"battery staple"),
+            Err(ExportError::WrongPassword)
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+//! Emergency offline reconstruction of the full private key
+//!
+//! Intended for disaster recovery only: it brings together `t+1` local key shares on a single
+//! (preferably air-gapped) machine and interpolates the private key, which defeats the whole point
+//! of threshold signing. Reconstructed key must be handled with extreme care.
+//!
+//! Before interpolating, every share is checked to belong to the same key (`y_sum_s`, `vss_vec`
+//! and access structure must coincide) and to be consistent with Feldman commitments. Interpolated
+//! secret is checked to match the public key.
+
+use std::iter;
+
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use thiserror::Error;
+
+use crate::common::access_structure::AccessStructureError;
+use crate::dkg::rounds::LocalKey;
+
+pub mod export;
+
+/// Reconstructs the private key out of local key shares of an authorized set of parties
+///
+/// Returns secret `x` such that `g^x` equals to the public key of the shares.
+pub fn reconstruct(keys: &[LocalKey<Secp256k1>]) -> Result<Scalar<Secp256k1>, RecoveryError> {
+    let first = keys.first().ok_or(RecoveryError::NoKeys)?;
+
+    for (i, key) in keys.iter().enumerate() {
+        let party = key.party_num_int;
+        if key.y_sum_s != first.y_sum_s
+            || key.vss_vec != first.vss_vec
+            || key.access_structure != first.access_structure
+        {
+            return Err(RecoveryError::MismatchedKey { party });
+        }
+        if keys[..i].iter().any(|k| k.party_num_int == party) {
+            return Err(RecoveryError::DuplicatedParty { party });
+        }
+        if party == 0 || party > first.access_structure.parties() {
+            return Err(RecoveryError::UnknownParty { party });
+        }
+    }
+
+    let access_structure = &first.access_structure;
+    if first.vss_vec.len() != usize::from(access_structure.parties()) {
+        return Err(RecoveryError::InconsistentCommitments);
+    }
+    let joint_commitments = access_structure.joint_commitments(&first.vss_vec);
+    if joint_commitments[0] != first.y_sum_s {
+        return Err(RecoveryError::InconsistentCommitments);
+    }
+
+    // every share must be consistent with commitments
+    for key in keys {
+        let party = key.party_num_int;
+        let slots = &access_structure.slots[usize::from(party - 1)];
+        let shares: Vec<&Scalar<Secp256k1>> = iter::once(&key.shared_keys.x_i)
+            .chain(&key.shared_keys.extra_x_i)
+            .collect();
+        let valid = slots.len() == shares.len()
+            && slots.iter().zip(&shares).all(|(slot, &share)| {
+                access_structure
+                    .slot_commitment(&joint_commitments, slot)
+                    .map(|c| c == Point::generator() * share)
+                    .unwrap_or(false)
+            });
+        if !valid {
+            return Err(RecoveryError::InvalidShare { party });
+        }
+    }
+
+    let s: Vec<u16> = keys.iter().map(|k| k.party_num_int - 1).collect();
+    let coefficients = access_structure
+        .reconstruction_coefficients(&s)
+        .map_err(RecoveryError::NotEnoughShares)?;
+    let secret: Scalar<Secp256k1> = keys
+        .iter()
+        .zip(&coefficients)
+        .flat_map(|(key, lambda)| {
+            iter::once(&key.shared_keys.x_i)
+                .chain(&key.shared_keys.extra_x_i)
+                .zip(lambda)
+                .map(|(share, l)| share * l)
+        })
+        .sum();
+
+    if Point::generator() * &secret != first.y_sum_s {
+        return Err(RecoveryError::PublicKeyMismatch);
+    }
+    Ok(secret)
+}
+
+#[derive(Debug, Error)]
+pub enum RecoveryError {
+    #[error("no key shares were given")]
+    NoKeys,
+    #[error("key share of party {party} belongs to a different key")]
+    MismatchedKey { party: u16 },
+    #[error("key share of party {party} is given twice")]
+    DuplicatedParty { party: u16 },
+    #[error("party {party} is not a member of the key's access structure")]
+    UnknownParty { party: u16 },
+    #[error("commitments don't match the public key")]
+    InconsistentCommitments,
+    #[error("key share of party {party} doesn't match commitments")]
+    InvalidShare { party: u16 },
+    #[error("given key shares are not enough to reconstruct the key: {0}")]
+    NotEnoughShares(#[source] AccessStructureError),
+    #[error("reconstructed key doesn't match the public key")]
+    PublicKeyMismatch,
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::OsRng;
+    use round_based::dev::Simulation;
+
+    use super::*;
+    use crate::common::access_structure::AccessStructure;
+    use crate::dkg::keygen::{test::SESSION_ID, Keygen};
+
+    fn simulate_keygen(access_structure: AccessStructure) -> Vec<LocalKey<Secp256k1>> {
+        let mut simulation = Simulation::new();
+        for i in 1..=access_structure.parties() {
+            simulation.add_party(
+                Keygen::with_access_structure(SESSION_ID, i, access_structure.clone(), OsRng)
+                    .unwrap(),
+            );
+        }
+        simulation.run().unwrap()
+    }
+
+    #[test]
+    fn reconstructs_threshold_key() {
+        let keys = simulate_keygen(AccessStructure::threshold(1, 3));
+        for s in &[[0, 1], [0, 2], [1, 2]] {
+            let subset: Vec<_> = s.iter().map(|&i| keys[i].clone()).collect();
+            let secret = reconstruct(&subset).unwrap();
+            assert_eq!(Point::generator() * &secret, keys[0].y_sum_s);
+        }
+        assert!(matches!(
+            reconstruct(&keys[..1]),
+            Err(RecoveryError::NotEnoughShares(_))
+        ));
+    }
+
+    #[test]
+    fn reconstructs_hierarchical_key() {
+        let keys = simulate_keygen(AccessStructure::hierarchical(2, &[0, 0, 1, 1]).unwrap());
+        let secret = reconstruct(&keys[1..]).unwrap();
+        assert_eq!(Point::generator() * &secret, keys[0].y_sum_s);
+    }
+
+    #[test]
+    fn rejects_shares_of_different_keys() {
+        let keys_a = simulate_keygen(AccessStructure::threshold(1, 2));
+        let keys_b = simulate_keygen(AccessStructure::threshold(1, 2));
+        assert!(matches!(
+            reconstruct(&[keys_a[0].clone(), keys_b[1].clone()]),
+            Err(RecoveryError::MismatchedKey { party: 2 })
+        ));
+
+        let mut tampered = keys_a.clone();
+        tampered[1].shared_keys.x_i = Scalar::random();
+        assert!(matches!(
+            reconstruct(&tampered),
+            Err(RecoveryError::InvalidShare { party: 2 })
+        ));
+    }
+}
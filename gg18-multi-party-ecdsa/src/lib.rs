@@ -1,92 +1,325 @@
+pub mod async_runtime;
 pub mod common;
+pub mod config;
 pub mod dkg;
+pub mod error;
+pub mod interop;
+pub mod key_import;
+pub mod key_recovery;
 pub mod signing;
 pub mod utilities;
 pub mod cli;
 
-use anyhow::{ anyhow, bail, ensure, Context, Result};
-use cli::{keygen_run, signal_client, read_group};
-use structopt::StructOpt;
+use anyhow::{ anyhow, bail, ensure, Context};
+use cli::{import_run, keygen_run, message_to_sign, read_group, scalar_hex, sign_run, verify_run};
+use futures::channel::oneshot;
 use futures::{StreamExt};
+use serde_json::json;
 use std::{fs, vec::Vec};
 use mpc_over_signal::{DeviceStore, Group, ParticipantIdentity, SignalClient};
+use rand::rngs::OsRng;
 
-use crate::dkg::keygen::Keygen;
+use crate::async_runtime::{CancelSignal, Progress};
+use crate::config::{check_group, GroupError, KeygenConfig, MpcConfig};
+use crate::dkg::rounds::LocalKey;
+use crate::dkg::pre_params::{PoolKey, PreParamsPool};
+use crate::error::{Error, Result};
 use curv::{
+    elliptic::curves::secp256_k1::Secp256k1,
     BigInt,
 };
 use crate::common::party_i::{LocalKeyShare, Params};
 use crate::signing::sign::OfflineStage;
 
-#[derive(StructOpt, Debug)]
-pub struct SignalServer {
-    /// Signal Server URL
-    ///
-    /// Allows overriding Signal Server URL in case if you're using own-hosted Signal Server
-    #[structopt(
-        long = "signal-host",
-        default_value = "https://textsecure-service.whispersystems.org/",
-        display_order = 21
-    )]
-    pub host: String,
-    /// Path to PEM-encoded certificate
-    ///
-    /// Sets root of trust in establishing secure connection to server. By default, original
-    /// Signal Server certificate is used.
-    #[structopt(long = "signal-cert", display_order = 22)]
-    pub certificate: Option<String>,
-}
+/// Runs keygen of `t`-of-`n` key with parties listed in `group_byte_vec`
+///
+/// Any `t`+1 parties will be able to sign, `keygen_config` may request weighted or hierarchical
+/// key instead. `t`, `n` and the group are validated before connecting to Signal, mismatch is
+/// reported as [Error::Group]. Returns local key share serialized to json.
+///
+/// `session` is a nonce all parties agree on for this run (e.g. generated by the initiator and
+/// handed out along with the group), it must be fresh for every run, retries included. Session
+/// already used by this device is rejected with [Error::Config].
+///
+/// `on_progress` is notified about every [Progress] of the protocol, it must not block. Once
+/// `cancel` is resolved, other parties are told that this party left, and [Error::Cancelled] is
+/// returned.
+#[allow(clippy::too_many_arguments)]
+pub async fn keygen(
+    config: &MpcConfig,
+    device_secrets: DeviceStore,
+    group_byte_vec: Vec<u8>,
+    session: &[u8],
+    t: u16,
+    n: u16,
+    keygen_config: Option<KeygenConfig>,
+    on_progress: impl FnMut(Progress) + Send + 'static,
+    cancel: CancelSignal,
+) -> Result<String> {
+    let access_structure = keygen_config.unwrap_or_default().access_structure(t, n)?;
+    let (group, me, my_ind) = open_group(&device_secrets, group_byte_vec, n).await?;
 
-#[derive(StructOpt, Debug)]
-pub struct SecretsFile {
-    /// Path to file containing sensitive information like secrets keys and tokens
-    ///
-    /// Keep it in secret! Information in this file can be used to perform actions on behalf of
-    /// your account: send, receive messages, edit profile info, etc.
-    #[structopt(
-        long = "secrets-file",
-        default_value = "secrets.json",
-        display_order = 20
-    )]
-    pub path: String,
+    keygen_run(
+        config,
+        device_secrets,
+        group,
+        session,
+        me,
+        my_ind,
+        access_structure,
+        None,
+        on_progress,
+        cancel,
+    )
+    .await
 }
 
-//MPC CONSTANTS
-const THRESHOLD:u16 = 1;
-const PARTIES:u16 = 3;
+/// Generates Paillier keys and `N_tilde` setups until encrypted `pool` holds `size` of them
+///
+/// Meant to be called while the app is idle, see [dkg::pre_params]. Empty `pool` starts a new
+/// one. `pool_key` must be 32 bytes long. Returns the re-encrypted pool.
+pub fn fill_pre_params_pool(pool: &[u8], pool_key: &[u8], size: usize) -> Result<Vec<u8>> {
+    let key = PoolKey::from_bytes(pool_key).map_err(|e| Error::Config(e.into()))?;
+    let mut pool = open_pre_params_pool(pool, &key)?;
+    pool.fill(&mut OsRng, size);
+    Ok(pool.encrypt(&mut OsRng, &key))
+}
 
-pub async fn keygen(secrets_byte_vec: Vec<u8>, group_byte_vec: Vec<u8>) -> Result<String> {
-    
-    let device_secrets = DeviceStore::from_byte_vec(secrets_byte_vec)
-        .await
-        .context("read device from file")?;
-    let me = device_secrets.read().await.me();
+/// Number of pre-params left in encrypted `pool`
+pub fn pre_params_pool_size(pool: &[u8], pool_key: &[u8]) -> Result<usize> {
+    let key = PoolKey::from_bytes(pool_key).map_err(|e| Error::Config(e.into()))?;
+    Ok(open_pre_params_pool(pool, &key)?.len())
+}
 
-    let group = read_group(group_byte_vec).context("read group")?;
-    let my_ind = match group.party_index(&me.addr) {
-        Some(i) => i,
-        None => bail!("group must contain this party too"),
-    };
-    
-    ensure!(
-        group.parties_count() == PARTIES,
-        "protocol expected to have {} parties (from `-n` option), but group file contains {} parties",
-        PARTIES, group.parties_count()
+/// Takes one pre-params out of encrypted `pool`
+///
+/// Returns the remaining pool and taken pre-params (encrypted with the same key) to pass to
+/// [keygen_with_pre_params]. Remaining pool must be stored before keygen starts, so the same
+/// pre-params are never used twice.
+pub fn take_pre_params(pool: &[u8], pool_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let key = PoolKey::from_bytes(pool_key).map_err(|e| Error::Config(e.into()))?;
+    let mut pool = open_pre_params_pool(pool, &key)?;
+    let mut taken = PreParamsPool::new();
+    taken.push(
+        pool.take()
+            .context("pre-params pool is empty")
+            .map_err(Error::Config)?,
     );
-    ensure!(PARTIES > 1, "at least two parties required");
-    ensure!(
-        PARTIES >= THRESHOLD,
-        "threshold value is more than number of parties"
-    );
-    
-    let keygen_json = keygen_run(
-        device_secrets.clone(),
+    Ok((pool.encrypt(&mut OsRng, &key), taken.encrypt(&mut OsRng, &key)))
+}
+
+/// Same as [keygen], but uses pre-params obtained from [take_pre_params] instead of searching
+/// for primes once all parties joined
+#[allow(clippy::too_many_arguments)]
+pub async fn keygen_with_pre_params(
+    config: &MpcConfig,
+    device_secrets: DeviceStore,
+    group_byte_vec: Vec<u8>,
+    session: &[u8],
+    t: u16,
+    n: u16,
+    keygen_config: Option<KeygenConfig>,
+    pre_params: &[u8],
+    pool_key: &[u8],
+    on_progress: impl FnMut(Progress) + Send + 'static,
+    cancel: CancelSignal,
+) -> Result<String> {
+    let access_structure = keygen_config.unwrap_or_default().access_structure(t, n)?;
+    let key = PoolKey::from_bytes(pool_key).map_err(|e| Error::Config(e.into()))?;
+    let pre_params = PreParamsPool::decrypt(pre_params, &key)
+        .context("decrypt pre-params")
+        .map_err(Error::Storage)?
+        .take()
+        .context("no pre-params given")
+        .map_err(Error::Config)?;
+    let (group, me, my_ind) = open_group(&device_secrets, group_byte_vec, n).await?;
+
+    keygen_run(
+        config,
+        device_secrets,
+        group,
+        session,
+        me,
+        my_ind,
+        access_structure,
+        Some(pre_params),
+        on_progress,
+        cancel,
+    )
+    .await
+}
+
+fn open_pre_params_pool(pool: &[u8], key: &PoolKey) -> Result<PreParamsPool> {
+    if pool.is_empty() {
+        Ok(PreParamsPool::new())
+    } else {
+        PreParamsPool::decrypt(pool, key)
+            .context("decrypt pre-params pool")
+            .map_err(Error::Storage)
+    }
+}
+/// Shares existing private key of `dealer` between `n` parties of `group_byte_vec` as `t`-of-`n`
+/// key
+///
+/// Only the dealer provides `secret_key`, other parties may provide expected `public_key`.
+/// `session` is handled the same way as in [keygen].
+#[allow(clippy::too_many_arguments)]
+pub async fn import_key(
+    config: &MpcConfig,
+    device_secrets: DeviceStore,
+    group_byte_vec: Vec<u8>,
+    session: &[u8],
+    t: u16,
+    n: u16,
+    dealer: u16,
+    secret_key: Option<String>,
+    public_key: Option<String>,
+    on_progress: impl FnMut(Progress) + Send + 'static,
+    cancel: CancelSignal,
+) -> Result<String> {
+    KeygenConfig::default().access_structure(t, n)?;
+    let (group, me, my_ind) = open_group(&device_secrets, group_byte_vec, n).await?;
+
+    import_run(
+        config,
+        device_secrets,
         group,
+        session,
         me,
         my_ind,
-        THRESHOLD,
-        PARTIES,
+        t,
+        dealer,
+        secret_key.as_deref(),
+        public_key.as_deref(),
+        None,
+        on_progress,
+        cancel,
+    )
+    .await
+}
+
+/// Links a new device named `device_name` to Signal account, returns its serialized secrets
+///
+/// `on_provisioning_url` receives the url once it's known, it should be shown as QR code to be
+/// scanned by Signal app on the phone. Linking finishes once the code is scanned.
+pub async fn login(
+    config: &MpcConfig,
+    device_name: String,
+    on_provisioning_url: impl FnOnce(String),
+) -> Result<Vec<u8>> {
+    let signal_client = config.signal_server.client().map_err(Error::Config)?;
+    let (provision_url_tx, provision_url) = oneshot::channel();
+    let (device, ()) = futures::future::join(
+        signal_client.login(&mut OsRng, provision_url_tx, device_name),
+        async {
+            // if provisioning fails, sender is dropped and the error is returned by login
+            if let Ok(url) = provision_url.await {
+                on_provisioning_url(url.to_string())
+            }
+        },
     )
     .await;
-    keygen_json
-}
\ No newline at end of file
+    let device = device
+        .context("signing in failed")
+        .map_err(Error::Transport)?;
+    write_device(&DeviceStore::new(device)).await
+}
+
+/// Visit card of the device: its Signal address and identity key serialized to json
+///
+/// Other parties put visit cards of each other into the group.
+pub async fn me(device_secrets: &DeviceStore) -> Result<String> {
+    let me = device_secrets.read().await.me();
+    serde_json::to_string(&me)
+        .context("serialize visit card")
+        .map_err(Error::Internal)
+}
+
+/// Signs `message` with every party of `group_byte_vec`, using local key obtained from [keygen]
+///
+/// Message is hex-encoded hash, it's parsed the same way as by `gg18 sign`. Returns json
+/// with `public_key`, `signature` (`r` and `s` concatenated), `r`, `s` and `recid`. Session,
+/// progress and cancellation are handled the same way as in [keygen].
+#[allow(clippy::too_many_arguments)]
+pub async fn sign(
+    config: &MpcConfig,
+    device_secrets: DeviceStore,
+    group_byte_vec: Vec<u8>,
+    session: &[u8],
+    local_key: Vec<u8>,
+    message: &str,
+    on_progress: impl FnMut(Progress) + Send + 'static,
+    cancel: CancelSignal,
+) -> Result<String> {
+    let local_key: LocalKey<Secp256k1> = serde_json::from_slice(&local_key)
+        .context("parse local key")
+        .map_err(Error::Storage)?;
+    let public_key = local_key.public_key();
+    let me = device_secrets.read().await.me();
+    let group = read_group(group_byte_vec)
+        .context("read group")
+        .map_err(Error::Config)?;
+    let my_ind = group.party_index(&me.addr).ok_or(GroupError::NotInGroup)?;
+
+    let signature = sign_run(
+        config,
+        device_secrets,
+        group,
+        session,
+        me,
+        my_ind,
+        LocalKeyShare::from(local_key),
+        message_to_sign(message),
+        on_progress,
+        cancel,
+    )
+    .await?;
+
+    let (r, s) = (scalar_hex(&signature.r), scalar_hex(&signature.s));
+    Ok(json!({
+        "public_key": hex::encode(public_key.to_bytes(true).as_ref()),
+        "signature": format!("{}{}", r, s),
+        "r": r,
+        "s": s,
+        "recid": signature.recid,
+    })
+    .to_string())
+}
+
+/// Checks whether `signature` returned by [sign] matches `message` and `public_key`
+pub fn verify(public_key: &str, signature: &str, message: &str) -> Result<bool> {
+    verify_run(public_key, signature, &message_to_sign(message)).map_err(Error::Config)
+}
+
+/// Parses device secrets obtained from [login]
+///
+/// Every protocol changes device secrets, caller must persist them once the call is finished.
+/// [DeviceStore::subscribe] notifies about the changes while it's running.
+pub async fn read_device(secrets_byte_vec: Vec<u8>) -> Result<DeviceStore> {
+    DeviceStore::from_byte_vec(secrets_byte_vec)
+        .await
+        .context("read device from file")
+        .map_err(Error::Storage)
+}
+
+/// Serializes device secrets in the format accepted by [read_device]
+pub async fn write_device(device_secrets: &DeviceStore) -> Result<Vec<u8>> {
+    device_secrets
+        .to_byte_vec()
+        .await
+        .map_err(Error::Storage)
+}
+
+/// Parses group, checks that it has `n` parties including this device
+async fn open_group(
+    device_secrets: &DeviceStore,
+    group_byte_vec: Vec<u8>,
+    n: u16,
+) -> Result<(Group, ParticipantIdentity, u16)> {
+    let me = device_secrets.read().await.me();
+    let group = read_group(group_byte_vec)
+        .context("read group")
+        .map_err(Error::Config)?;
+    let my_ind = check_group(&group, &me, n)?;
+    Ok((group, me, my_ind))
+}
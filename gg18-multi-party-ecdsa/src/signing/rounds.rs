@@ -1,24 +1,26 @@
 #![allow(non_snake_case)]
 
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 //use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
 //use curv::BigInt;
-use sha2::Sha256;
 
 use round_based::containers::push::Push;
 use round_based::containers::{self, BroadcastMsgs, P2PMsgs, Store};
 use round_based::Msg;
 
 use crate::utilities::mta::{MessageA, MessageB};
+use crate::common::access_structure::{AccessStructure, AccessStructureError};
+use crate::common::parallel;
+use crate::common::transcript::{ProofContext, SessionId};
 use crate::common::ErrorType;
+use crate::utilities::zk_dlog::DLogProof;
+use crate::utilities::zk_homo_elgamal::HomoELGamalProof;
 
 use curv::{
-    cryptographic_primitives::{
-        proofs::sigma_correct_homomorphic_elgamal_enc::HomoELGamalProof,
-        proofs::sigma_dlog::DLogProof, secret_sharing::feldman_vss::VerifiableSS,
-    },
+    cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS,
     elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar},
     BigInt,
 };
@@ -26,10 +28,11 @@ use curv::{
 use crate::common::party_i::{
     Keys, LocalSignature, PartyPrivate, Phase5ADecom1, Phase5Com1, Phase5Com2, Phase5DDecom2,
     SharedKeys, SignBroadcastPhase1, SignDecommitPhase1, SignKeys, SignatureRecid, //check_sig,
-    LocalKeyShare,
+    LocalKeyShare, PartialSignature,
 };
 
 use paillier::EncryptionKey;
+use zk_paillier::zkproofs::DLogStatement;
 
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -42,15 +45,16 @@ pub struct GammaI(pub MessageB);
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WI(pub MessageB);
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct DeltaI(Scalar<Secp256k1>);
+pub struct DeltaI(pub Scalar<Secp256k1>);
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RDash(Point<Secp256k1>);
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SI(pub Point<Secp256k1>);
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct HEGProof(pub HomoELGamalProof<Secp256k1, Sha256>);
+pub struct HEGProof(pub HomoELGamalProof);
 
 pub struct Round0 {
+    pub session_id: SessionId,
     pub party_num_int: u16,
     pub threshold: u16,
     /// Party local secret share
@@ -69,6 +73,8 @@ impl Round0 {
         let vss = self.local_key_share.vss_scheme_vec;
         let pai_k = self.local_key_share.paillier_key_vector;
         let ys = self.local_key_share.y_sum;
+        let access_structure = self.local_key_share.access_structure;
+        let h1_h2_n_tilde_vec = self.local_key_share.h1_h2_n_tilde_vec;
 
         output.push(Msg {
             sender: self.party_num_int,
@@ -77,6 +83,7 @@ impl Round0 {
         });
 
         let round1 = Round1 {
+            ctx: ProofContext::new(self.session_id, self.party_num_int),
             party_num_int: self.party_num_int,
             threshold: self.threshold,
             message_bn: self.message_bn,
@@ -86,6 +93,8 @@ impl Round0 {
             vss_scheme_vec: vss,
             paillier_key_vector: pai_k,
             y_sum: ys,
+            access_structure,
+            h1_h2_n_tilde_vec,
         };
 
         Ok(round1)
@@ -97,6 +106,7 @@ impl Round0 {
 }
 
 pub struct Round1 {
+    ctx: ProofContext,
     party_num_int: u16,
     threshold: u16,
     message_bn: BigInt,
@@ -106,37 +116,62 @@ pub struct Round1 {
     vss_scheme_vec: Vec<VerifiableSS<Secp256k1>>,
     paillier_key_vector: Vec<EncryptionKey>,
     y_sum: Point<Secp256k1>,
+    access_structure: AccessStructure,
+    h1_h2_n_tilde_vec: Vec<DLogStatement>,
 }
 
 impl Round1 {
-    pub fn proceed<O>(
+    pub fn proceed<R, O>(
         self,
+        rng: &mut R,
         input: BroadcastMsgs<u16>,
         mut output: O,
     ) -> Result<Round2>
     where
+        R: RngCore + CryptoRng,
         O: Push<Msg<(MessageA, SignBroadcastPhase1)>>,
     {
         let signers_vec: Vec<_> = input.into_vec_including_me(self.party_id.clone());
-        let private = PartyPrivate::set_private(self.party_keys.clone(), self.shared_keys);
-        println!("signers_vec = {:?}", signers_vec);//erwin_debug
-        let sign_keys = SignKeys::create(
+        let keygen_n = self.paillier_key_vector.len().min(self.h1_h2_n_tilde_vec.len());
+        for (party, &index) in (1..).zip(&signers_vec) {
+            if usize::from(index) >= keygen_n {
+                return Err(Error::Round1InvalidSigner { party, index });
+            }
+        }
+        let private = PartyPrivate::set_private(&self.party_keys, &self.shared_keys);
+        let sign_keys = SignKeys::create_with_access_structure(
+            rng,
             &private,
-            &self.vss_scheme_vec[usize::from(signers_vec[usize::from(self.party_num_int - 1)])],
-            signers_vec[usize::from(self.party_num_int - 1)],       
+            &self.access_structure,
+            signers_vec[usize::from(self.party_num_int - 1)],
             &signers_vec,
-        );
+        )
+        .map_err(Error::Round1AccessStructure)?;
         //////////////////////////////////////////////////////////////////////////////
-        let (phase1_com, phase1_decom) = sign_keys.phase1_broadcast();
-        let (m_a_k, _) = MessageA::a(&sign_keys.k_i, &self.party_keys.ek, &[]);
+        let (phase1_com, phase1_decom) = sign_keys.phase1_broadcast(rng);
+        // proofs that k_i is small, one per counterparty made with its h1, h2, N_tilde: MtA
+        // responses leak Bob's secrets to Alice otherwise
+        let counterparty_statements: Vec<DLogStatement> =
+            other_parties(self.threshold, self.party_num_int)
+                .map(|j| usize::from(signers_vec[usize::from(j - 1)]))
+                .map(|keygen_i| self.h1_h2_n_tilde_vec[keygen_i].clone())
+                .collect();
+        let (m_a_k, _) = MessageA::a_with_own_key(
+            rng,
+            &self.ctx,
+            &sign_keys.k_i,
+            &self.party_keys.dk,
+            &counterparty_statements,
+        );
 
         output.push(Msg {
             sender: self.party_num_int,
             receiver: None,
-            body: (m_a_k, phase1_com.clone()),
+            body: (m_a_k.clone(), phase1_com.clone()),
         });
 
         Ok(Round2 {
+            ctx: self.ctx,
             party_num_int: self.party_num_int,
             threshold: self.threshold,
             message_bn: self.message_bn,
@@ -144,6 +179,9 @@ impl Round1 {
             vss_scheme_vec: self.vss_scheme_vec,
             paillier_key_vector: self.paillier_key_vector,
             y_sum: self.y_sum,
+            access_structure: self.access_structure,
+            h1_h2_n_tilde_vec: self.h1_h2_n_tilde_vec,
+            m_a_k,
             phase1_com,
             signers_vec,
             sign_keys,
@@ -164,6 +202,7 @@ impl Round1 {
 }
 
 pub struct Round2 {
+    ctx: ProofContext,
     party_num_int: u16,
     threshold: u16,
     message_bn: BigInt,
@@ -171,6 +210,9 @@ pub struct Round2 {
     vss_scheme_vec: Vec<VerifiableSS<Secp256k1>>,
     paillier_key_vector: Vec<EncryptionKey>,
     y_sum: Point<Secp256k1>,
+    access_structure: AccessStructure,
+    h1_h2_n_tilde_vec: Vec<DLogStatement>,
+    m_a_k: MessageA,
     phase1_com: SignBroadcastPhase1,
     signers_vec: Vec<u16>,
     sign_keys: SignKeys,
@@ -178,12 +220,14 @@ pub struct Round2 {
 }
 
 impl Round2 {
-    pub fn proceed<O>(
+    pub fn proceed<R, O>(
         self,
+        rng: &mut R,
         input: BroadcastMsgs<(MessageA, SignBroadcastPhase1)>,
         mut output: O,
     ) -> Result<Round3>
     where
+        R: RngCore + CryptoRng,
         O: Push<Msg<(GammaI, WI)>>,
     {
         let (m_a_vec, bc_vec): (Vec<_>, Vec<_>) = input
@@ -194,37 +238,62 @@ impl Round2 {
         //bc_vec.push(self.phase1_com.clone());
         //assert_eq!(self.signers_vec.len(), bc_vec.len());
 
-        //println!("m_a_vec = {:?}", m_a_vec);//erwin_debug
-        //println!("bc_vec = {:?}", bc_vec);//erwin_debug
         //////////////////////////////////////////////////////////////////////////////
-        let mut m_b_gamma_send_vec: Vec<MessageB> = Vec::new();
-        let mut beta_vec: Vec<Scalar<Secp256k1>> = Vec::new();
-        let mut m_b_w_send_vec: Vec<MessageB> = Vec::new();
-        let mut ni_vec: Vec<Scalar<Secp256k1>> = Vec::new();
-        
-        let mut j = 0;
-        for i in 1..self.threshold + 2 {
-            if i != self.party_num_int {
-                let (m_b_gamma, beta_gamma, _, _) = MessageB::b(
+        // Alice's range proofs meant for us are made with our h1, h2, N_tilde
+        let me = usize::from(self.signers_vec[usize::from(self.party_num_int - 1)]);
+        let my_dlog_statement = std::slice::from_ref(&self.h1_h2_n_tilde_vec[me]);
+
+        // counterparty index and position of its message in m_a_vec
+        let counterparties: Vec<(u16, usize)> = (1..self.threshold + 2)
+            .filter(|&i| i != self.party_num_int)
+            .zip(0..)
+            .collect();
+        let rngs = parallel::fork_rngs(rng, counterparties.len());
+        let mta_results = parallel::try_map(
+            counterparties.into_iter().zip(rngs).collect(),
+            |((i, j), mut rng)| {
+                let alice = usize::from(self.signers_vec[usize::from(i - 1)]);
+                let alice_ek = &self.paillier_key_vector[alice];
+                let m_a = alice_message_for(&m_a_vec[j], i, self.party_num_int)
+                    .ok_or(crate::common::Error::InvalidKey)
+                    .and_then(|m_a| {
+                        m_a.verify(&self.ctx.of_party(i), alice_ek, my_dlog_statement)
+                    })
+                    .map_err(|error| Error::Round2MtA { party: i, error })?;
+                // MtA for gamma_i, MtAwc for w_i: Alice checks that we used w_i behind g^w_i
+                let (m_b_gamma, beta_gamma) = MessageB::b_with_range_proof(
+                    &mut rng,
+                    &self.ctx,
                     &self.sign_keys.gamma_i,
-                    &self.paillier_key_vector[usize::from(self.signers_vec[usize::from(i - 1)])],
-                    m_a_vec[j].clone(),
-                    &[],
+                    alice_ek,
+                    &m_a,
+                    &self.h1_h2_n_tilde_vec[alice],
+                    false,
                 )
-                .unwrap();
-                let (m_b_w, beta_wi, _, _) = MessageB::b(
+                .map_err(|error| Error::Round2MtA { party: i, error })?;
+                let (m_b_w, beta_wi) = MessageB::b_with_range_proof(
+                    &mut rng,
+                    &self.ctx,
                     &self.sign_keys.w_i,
-                    &self.paillier_key_vector[usize::from(self.signers_vec[usize::from(i - 1)])],
-                    m_a_vec[j].clone(),
-                    &[],
+                    alice_ek,
+                    &m_a,
+                    &self.h1_h2_n_tilde_vec[alice],
+                    true,
                 )
-                .unwrap();
-                m_b_gamma_send_vec.push(m_b_gamma);
-                m_b_w_send_vec.push(m_b_w);
-                beta_vec.push(beta_gamma);
-                ni_vec.push(beta_wi);
-                j += 1;
-            }
+                .map_err(|error| Error::Round2MtA { party: i, error })?;
+                Ok::<_, Error>((m_b_gamma, m_b_w, beta_gamma, beta_wi))
+            },
+        )?;
+
+        let mut m_b_gamma_send_vec: Vec<MessageB> = Vec::new();
+        let mut beta_vec: Vec<Scalar<Secp256k1>> = Vec::new();
+        let mut m_b_w_send_vec: Vec<MessageB> = Vec::new();
+        let mut ni_vec: Vec<Scalar<Secp256k1>> = Vec::new();
+        for (m_b_gamma, m_b_w, beta_gamma, beta_wi) in mta_results {
+            m_b_gamma_send_vec.push(m_b_gamma);
+            m_b_w_send_vec.push(m_b_w);
+            beta_vec.push(beta_gamma);
+            ni_vec.push(beta_wi);
         }
         
         /*
@@ -243,7 +312,6 @@ impl Round2 {
         let mut j = 0;
         for i in 1..self.threshold + 2 {
             if i != self.party_num_int {
-                //println!("j = {}\n, m_b_gamma_send_vec[j] = {:?}\n, m_b_w_send_vec[j] = {:?}\n\n", j, m_b_gamma_send_vec[j], m_b_w_send_vec[j]);
                 output.push(Msg {
                     sender: self.party_num_int,
                     receiver: Some(i),
@@ -254,12 +322,16 @@ impl Round2 {
         }
 
         Ok(Round3 {
+            ctx: self.ctx,
             party_num_int: self.party_num_int,
             threshold: self.threshold,
             message_bn: self.message_bn,
             party_keys: self.party_keys,
             vss_scheme_vec: self.vss_scheme_vec,
             y_sum: self.y_sum,
+            access_structure: self.access_structure,
+            h1_h2_n_tilde_vec: self.h1_h2_n_tilde_vec,
+            m_a_k: self.m_a_k,
             signers_vec: self.signers_vec,
             sign_keys: self.sign_keys,
             phase1_decom: self.phase1_decom,
@@ -282,12 +354,16 @@ impl Round2 {
 }
 
 pub struct Round3 {
+    ctx: ProofContext,
     party_num_int: u16,
     threshold: u16,
     message_bn: BigInt,
     party_keys: Keys,
     vss_scheme_vec: Vec<VerifiableSS<Secp256k1>>,
     y_sum: Point<Secp256k1>,
+    access_structure: AccessStructure,
+    h1_h2_n_tilde_vec: Vec<DLogStatement>,
+    m_a_k: MessageA,
     signers_vec: Vec<u16>,
     sign_keys: SignKeys,
     phase1_decom: SignDecommitPhase1,
@@ -307,38 +383,58 @@ impl Round3 {
             .map(|(gamma_i, w_i)| (gamma_i.0, w_i.0))
             .unzip();
 
-        let mut alpha_vec: Vec<Scalar<Secp256k1>> = Vec::new();
-        let mut miu_vec: Vec<Scalar<Secp256k1>> = Vec::new();
-    
-        let mut j = 0;
-        let xi_com_vec = Keys::get_commitments_to_xi(&self.vss_scheme_vec);
-
-        for i in 1..self.threshold + 2 {
-            if i != self.party_num_int {
-                let m_b = m_b_gamma_rec_vec[j].clone();
-    
-                let alpha_ij_gamma = m_b
-                    .verify_proofs_get_alpha(&self.party_keys.dk, &self.sign_keys.k_i)
-                    .expect("wrong dlog or m_b");
-                let m_b = m_b_w_rec_vec[j].clone();
-                let alpha_ij_wi = m_b
-                    .verify_proofs_get_alpha(&self.party_keys.dk, &self.sign_keys.k_i)
-                    .expect("wrong dlog or m_b");
-                alpha_vec.push(alpha_ij_gamma.0);
-                miu_vec.push(alpha_ij_wi.0);
-                let g_w_i = Keys::update_commitments_to_xi(
-                    &xi_com_vec[usize::from(self.signers_vec[usize::from(i - 1)])],
-                    &self.vss_scheme_vec[usize::from(self.signers_vec[usize::from(i - 1)])],
+        // Bob's range proofs are made with our h1, h2, N_tilde
+        let me = usize::from(self.signers_vec[usize::from(self.party_num_int - 1)]);
+        let my_dlog_statement = &self.h1_h2_n_tilde_vec[me];
+        let ek = &self.party_keys.ek;
+        let c_a = &self.m_a_k.c;
+
+        // counterparty index and position of its messages in m_b_*_rec_vec
+        let counterparties: Vec<(u16, usize)> = (1..self.threshold + 2)
+            .filter(|&i| i != self.party_num_int)
+            .zip(0..)
+            .collect();
+        let alphas = parallel::try_map(counterparties, |(i, j)| {
+            let bob_ctx = self.ctx.of_party(i);
+            let g_w_i = self
+                .access_structure
+                .public_share(
+                    &self.vss_scheme_vec,
                     self.signers_vec[usize::from(i - 1)],
                     &self.signers_vec,
-                );
-                assert_eq!(m_b.b_proof.pk, g_w_i);
-                j += 1;
+                )
+                .map_err(Error::Round3AccessStructure)?;
+
+            let invalid = |error| Error::Round3MtA { party: i, error };
+
+            let m_b = &m_b_gamma_rec_vec[j];
+            m_b.verify_range_proof(&bob_ctx, c_a, ek, my_dlog_statement, None)
+                .map_err(invalid)?;
+            let alpha_ij_gamma = m_b
+                .verify_proofs_get_alpha(&bob_ctx, &self.party_keys.dk, &self.sign_keys.k_i)
+                .map_err(invalid)?;
+            // MtAwc: Bob used exactly the w_j behind public g^w_j
+            let m_b = &m_b_w_rec_vec[j];
+            if m_b.b_proof.pk != g_w_i {
+                return Err(invalid(crate::common::Error::InvalidKey));
             }
-        }
+            m_b.verify_range_proof(&bob_ctx, c_a, ek, my_dlog_statement, Some(&g_w_i))
+                .map_err(invalid)?;
+            let alpha_ij_wi = m_b
+                .verify_proofs_get_alpha(&bob_ctx, &self.party_keys.dk, &self.sign_keys.k_i)
+                .map_err(invalid)?;
+            Ok((alpha_ij_gamma.0, alpha_ij_wi.0))
+        })?;
+        let (alpha_vec, miu_vec): (Vec<_>, Vec<_>) = alphas.into_iter().unzip();
         //////////////////////////////////////////////////////////////////////////////
-        let delta_i = self.sign_keys.phase2_delta_i(&alpha_vec, &self.beta_vec);
-        let sigma = self.sign_keys.phase2_sigma_i(&miu_vec, &self.ni_vec);
+        let delta_i = self
+            .sign_keys
+            .phase2_delta_i(&alpha_vec, &self.beta_vec)
+            .map_err(Error::Round3Phase2)?;
+        let sigma = self
+            .sign_keys
+            .phase2_sigma_i(&miu_vec, &self.ni_vec)
+            .map_err(Error::Round3Phase2)?;
 
         output.push(Msg {
             sender: self.party_num_int,
@@ -347,6 +443,7 @@ impl Round3 {
         });
 
         Ok(Round4 {
+            ctx: self.ctx,
             party_num_int: self.party_num_int,
             threshold: self.threshold,
             message_bn: self.message_bn,
@@ -370,6 +467,7 @@ impl Round3 {
 }
 
 pub struct Round4 {
+    ctx: ProofContext,
     party_num_int: u16,
     threshold: u16,
     message_bn: BigInt,
@@ -393,7 +491,8 @@ impl Round4 {
     {
         let  delta_vec = input.into_vec_including_me( DeltaI(self.delta_i) ).into_iter().map(|x| x.0).my_unzip();
 
-        let delta_inv = SignKeys::phase3_reconstruct_delta(&delta_vec);
+        let delta_inv = SignKeys::phase3_reconstruct_delta(&delta_vec)
+            .map_err(|_| Error::Round4ZeroDelta)?;
 
 
         output.push(Msg {
@@ -403,6 +502,7 @@ impl Round4 {
         });
 
         Ok(Round5 {
+            ctx: self.ctx,
             party_num_int: self.party_num_int,
             threshold: self.threshold,
             message_bn: self.message_bn,
@@ -426,6 +526,7 @@ impl Round4 {
 }
 
 pub struct Round5 {
+    ctx: ProofContext,
     party_num_int: u16,
     threshold: u16,
     message_bn: BigInt,
@@ -439,38 +540,44 @@ pub struct Round5 {
 }
 
 impl Round5 {
-    pub fn proceed<O>(
+    pub fn proceed<R, O>(
         self,
+        rng: &mut R,
         decommit_round1: BroadcastMsgs<SignDecommitPhase1>,
         mut output: O,
     ) -> Result<Round6>
     where
-        O: Push<Msg<(Phase5Com1,
-                     Phase5ADecom1,
-                     HomoELGamalProof<Secp256k1, Sha256>,
-                     DLogProof<Secp256k1, Sha256>)>>,
+        R: RngCore + CryptoRng,
+        O: Push<Msg<(Phase5Com1, Phase5ADecom1, HomoELGamalProof, DLogProof)>>,
     {
         let decom_vec: Vec<_> = decommit_round1.into_vec();
-        //println!("decom_vec = {:?}\n", decom_vec);//erwin_debug
         let decomm_i = self.phase1_decom.clone();
         let bc_vec_self = self.bc_vec.clone();
 
         let b_proof_vec = (0..self.m_b_gamma_rec_vec.len())
             .map(|i| &self.m_b_gamma_rec_vec[i].b_proof)
-            .collect::<Vec<&DLogProof<Secp256k1, Sha256>>>();
+            .collect::<Vec<&DLogProof>>();
 
+        for (j, party) in other_parties(self.threshold, self.party_num_int).enumerate() {
+            if !SignKeys::phase4_check(b_proof_vec[j], &decom_vec[j], &bc_vec_self[j]) {
+                return Err(Error::Round5Decommitment { party });
+            }
+        }
         let R = SignKeys::phase4(&self.delta_inv, &b_proof_vec, decom_vec, &bc_vec_self)
-            .expect("bad gamma_i decommit");
+            .map_err(Error::Round5Phase4)?;
 
         // adding local g_gamma_i
-        let R = R + decomm_i.g_gamma_i * self.delta_inv;
+        let R = R + decomm_i.g_gamma_i.into_point() * self.delta_inv;
+        if R.is_zero() {
+            return Err(Error::Round5ZeroR);
+        }
 
         let local_sig =
-            LocalSignature::phase5_local_sig(&self.sign_keys.k_i, 
+            LocalSignature::phase5_local_sig(rng, &self.sign_keys.k_i, 
                 &self.message_bn, &R, &self.sigma, &self.y_sum);
 
         let (phase5_com, phase_5a_decom, helgamal_proof, dlog_proof_rho) =
-            local_sig.phase5a_broadcast_5b_zkproof();
+            local_sig.phase5a_broadcast_5b_zkproof(rng, &self.ctx);
 
         output.push(Msg {
             sender: self.party_num_int,
@@ -482,6 +589,7 @@ impl Round5 {
         });
 
         Ok(Round6 {
+            ctx: self.ctx,
             party_num_int: self.party_num_int,
             threshold: self.threshold,
             y_sum: self.y_sum,
@@ -505,28 +613,28 @@ impl Round5 {
 }
 
 pub struct Round6 {
+    ctx: ProofContext,
     party_num_int: u16,
     threshold: u16,
     y_sum: Point<Secp256k1>,
     phase5_com: Phase5Com1,
     phase_5a_decom: Phase5ADecom1,
-    helgamal_proof: HomoELGamalProof<Secp256k1, Sha256>,
-    dlog_proof_rho: DLogProof<Secp256k1, Sha256>,
+    helgamal_proof: HomoELGamalProof,
+    dlog_proof_rho: DLogProof,
     R: Point<Secp256k1>,
     local_sig: LocalSignature, 
     message_bn: BigInt,
 }
 
 impl Round6 {
-    pub fn proceed<O>(
+    pub fn proceed<R, O>(
         self,
-        input: BroadcastMsgs<(Phase5Com1,
-                              Phase5ADecom1,
-                              HomoELGamalProof<Secp256k1, Sha256>,
-                              DLogProof<Secp256k1, Sha256>)>,
+        rng: &mut R,
+        input: BroadcastMsgs<(Phase5Com1, Phase5ADecom1, HomoELGamalProof, DLogProof)>,
         mut output: O,
     ) -> Result<Round7>
     where
+        R: RngCore + CryptoRng,
         O: Push<Msg<(Phase5Com2, Phase5DDecom2)>>,
     {
         let vi = self.phase_5a_decom.clone().V_i;
@@ -544,8 +652,14 @@ impl Round6 {
             .into_iter()
             .unzip4();
         
+        // messages of other parties come ordered by sender index
+        let parties: Vec<u16> = other_parties(self.threshold, self.party_num_int).collect();
+        let contexts: Vec<ProofContext> = parties.iter().map(|&i| self.ctx.of_party(i)).collect();
+
         let (phase5_com2, phase_5d_decom2) = self.local_sig
             .phase5c(
+                rng,
+                &contexts,
                 &phase_5a_decomm_vec,
                 &commit5a_vec,
                 &phase_5a_elgamal_vec,
@@ -553,7 +667,22 @@ impl Round6 {
                 &vi,
                 &self.R,
             )
-            .expect("error phase5");
+            .map_err(|error| {
+                let culprit = (0..parties.len()).find(|&j| {
+                    !LocalSignature::phase5c_check(
+                        &contexts[j],
+                        &phase_5a_decomm_vec[j],
+                        &commit5a_vec[j],
+                        &phase_5a_elgamal_vec[j],
+                        &phase_5a_dlog_vec[j],
+                        &self.R,
+                    )
+                });
+                match culprit {
+                    Some(j) => Error::Round6Decommitment { party: parties[j] },
+                    None => Error::Round6CheckSig(error),
+                }
+            })?;
 
         output.push(Msg {
             sender: self.party_num_int,
@@ -568,6 +697,7 @@ impl Round6 {
 
         Ok(Round7 {
             party_num_int: self.party_num_int,
+            threshold: self.threshold,
             y_sum: self.y_sum,
             local_sig: self.local_sig, 
             message_bn: self.message_bn,
@@ -577,7 +707,7 @@ impl Round6 {
         })
     }
 
-    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<(Phase5Com1, Phase5ADecom1, HomoELGamalProof<Secp256k1, Sha256>, DLogProof<Secp256k1, Sha256>)>> 
+    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<(Phase5Com1, Phase5ADecom1, HomoELGamalProof, DLogProof)>> 
     {
         containers::BroadcastMsgsStore::new(i, n)
     }
@@ -589,6 +719,7 @@ impl Round6 {
 
 pub struct Round7 {
     party_num_int: u16,
+    threshold: u16,
     y_sum: Point<Secp256k1>,
     local_sig: LocalSignature, 
     message_bn: BigInt,
@@ -604,33 +735,43 @@ impl Round7 {
         mut output: O,
     ) -> Result<Round8>
     where
-        O: Push<Msg<Scalar<Secp256k1>>>,
+        O: Push<Msg<PartialSignature>>,
     {
         let (commit5c_vec, decommit5d_vec): (Vec<Phase5Com2>, Vec<Phase5DDecom2>) = input
             .into_vec_including_me((self.phase5_com2, self.phase_5d_decom2))
             .into_iter()
             .unzip();
         
-        let s_i = self.local_sig
+        self.local_sig
             .phase5d(
                 &decommit5d_vec,
                 &commit5c_vec,
                 &self.phase_5a_decomm_vec,
             )
-            .expect("bad com 5d");
+            .map_err(|error| {
+                // messages are ordered by sender index, our own one included
+                let culprit = (0..decommit5d_vec.len()).find(|&j| {
+                    !LocalSignature::phase5d_check(&decommit5d_vec[j], &commit5c_vec[j])
+                });
+                match culprit {
+                    Some(j) => Error::Round7Decommitment { party: j as u16 + 1 },
+                    None => Error::Round7(error),
+                }
+            })?;
 
         output.push(Msg {
             sender: self.party_num_int,
             receiver: None,
-            body: s_i.clone(),
+            body: self.local_sig.partial_signature(),
         });
     
         Ok(Round8 {
-            s_i,
             local_sig: self.local_sig, 
             y_sum: self.y_sum,
             message_bn: self.message_bn,
             party_num_int: self.party_num_int,
+            threshold: self.threshold,
+            phase_5a_decomm_vec: self.phase_5a_decomm_vec,
         })
     }
 
@@ -645,29 +786,44 @@ impl Round7 {
 }
 
 pub struct Round8 {
-    s_i: Scalar<Secp256k1>,
     local_sig: LocalSignature, 
     y_sum: Point<Secp256k1>,
     message_bn: BigInt,
     party_num_int: u16,
+    threshold: u16,
+    /// Decommitments of other parties ordered by sender index, then our own one
+    phase_5a_decomm_vec: Vec<Phase5ADecom1>,
 }
 
 impl Round8 {
     pub fn proceed(
         self,
-        input: BroadcastMsgs<Scalar<Secp256k1>>,
+        input: BroadcastMsgs<PartialSignature>,
     ) -> Result<CompletedOfflineStage> {
 
-        let s_i_vec: Vec<Scalar<Secp256k1>> = input.into_vec();
+        let partial_sig_vec: Vec<PartialSignature> = input.into_vec();
 
+        // every share must open V_i committed in phase 5A, otherwise its sender is to blame
+        let parties = other_parties(self.threshold, self.party_num_int);
+        let senders = parties.zip(&partial_sig_vec).zip(&self.phase_5a_decomm_vec);
+        for ((party, partial), decom) in senders {
+            if !LocalSignature::phase7_check(partial, decom, &self.local_sig.R) {
+                return Err(Error::Round8PartialSignature { party });
+            }
+        }
+
+        let s_i_vec: Vec<Scalar<Secp256k1>> =
+            partial_sig_vec.into_iter().map(|partial| partial.s_i).collect();
         let sig = self.local_sig
             .output_signature(&s_i_vec)
-            .expect("verification failed");
+            .map_err(Error::Round8)?;
 
-        println!("party {:?} Output Signature: \n", self.party_num_int);
-        println!("R: {:?}", sig.r);
-        println!("s: {:?} \n", sig.s);
-        println!("recid: {:?} \n", sig.recid.clone());
+        tracing::event!(
+            tracing::Level::DEBUG,
+            party = self.party_num_int,
+            recid = sig.recid,
+            "Signature is computed"
+        );
 
         // check sig against secp256k1
         //check_sig(&sig.r, &sig.s, &self.message_bn, &self.y_sum);
@@ -678,7 +834,7 @@ impl Round8 {
         })
     }
 
-    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<Scalar<Secp256k1>>> {
+    pub fn expects_messages(i: u16, n: u16) -> Store<BroadcastMsgs<PartialSignature>> {
         containers::BroadcastMsgsStore::new(i, n)
     }
 
@@ -732,20 +888,70 @@ impl Round8 {
 pub enum Error {
     #[error("round 1: {0:?}")]
     Round1(ErrorType),
-    #[error("round 2 stage 3: {0:?}")]
-    Round2Stage3(crate::common::Error),
+    #[error("round 1: party {party} sent keygen index {index} which is out of range")]
+    Round1InvalidSigner { party: u16, index: u16 },
+    #[error("round 1: {0}")]
+    Round1AccessStructure(AccessStructureError),
+    #[error("round 2: party {party} sent invalid MtA message: {error:?}")]
+    Round2MtA {
+        party: u16,
+        error: crate::common::Error,
+    },
     #[error("round 2 stage 4: {0:?}")]
     Round2Stage4(ErrorType),
     #[error("round 3: {0:?}")]
     Round3(ErrorType),
+    #[error("round 3: {0}")]
+    Round3AccessStructure(AccessStructureError),
+    #[error("round 3: party {party} sent invalid MtA response: {error:?}")]
+    Round3MtA {
+        party: u16,
+        error: crate::common::Error,
+    },
+    #[error("round 3: phase 2: {0:?}")]
+    Round3Phase2(crate::common::Error),
+    #[error("round 4: sum of deltas is zero")]
+    Round4ZeroDelta,
     #[error("round 5: {0:?}")]
     Round5(ErrorType),
+    #[error("round 5: party {party} sent invalid gamma_i decommitment")]
+    Round5Decommitment { party: u16 },
+    #[error("round 5: phase 4: {0:?}")]
+    Round5Phase4(crate::common::Error),
+    #[error("round 5: R is the point at infinity")]
+    Round5ZeroR,
     #[error("round 6: verify proof: {0:?}")]
     Round6VerifyProof(ErrorType),
+    #[error("round 6: party {party} sent invalid phase 5A decommitment or proofs")]
+    Round6Decommitment { party: u16 },
     #[error("round 6: check sig: {0:?}")]
     Round6CheckSig(crate::common::Error),
+    #[error("round 7: party {party} sent invalid phase 5D decommitment")]
+    Round7Decommitment { party: u16 },
     #[error("round 7: {0:?}")]
     Round7(crate::common::Error),
+    #[error("round 8: party {party} sent signature share not matching its phase 5A commitment")]
+    Round8PartialSignature { party: u16 },
+    /// Every share matches its commitment, yet the signature doesn't verify, which is a bug
+    #[error("round 8: signature doesn't verify: {0:?}")]
+    Round8(crate::common::Error),
+}
+
+/// Signing indexes of all parties but `me`, in order their messages come
+fn other_parties(threshold: u16, me: u16) -> impl Iterator<Item = u16> {
+    (1..=threshold + 1).filter(move |&i| i != me)
+}
+
+/// Alice's MtA message keeping only the range proof made for `bob`, `None` if it's missing
+///
+/// Alice attaches proofs in order of [other_parties].
+fn alice_message_for(m_a: &MessageA, alice: u16, bob: u16) -> Option<MessageA> {
+    let position = if bob < alice { bob - 1 } else { bob - 2 };
+    let proof = m_a.range_proofs.get(usize::from(position))?;
+    Some(MessageA {
+        c: m_a.c.clone(),
+        range_proofs: vec![proof.clone()],
+    })
 }
 
 trait IteratorExt: Iterator {
@@ -20,28 +20,32 @@
 //! [LocalKey]: super::keygen::LocalKey
 //! [StateMachine]: round_based::StateMachine
 
-use std::mem::replace;
+use std::fmt;
+use std::mem::{replace, take};
 use std::time::Duration;
-use sha2::Sha256;
 
 use round_based::containers::{push::Push, BroadcastMsgs, MessageStore, P2PMsgs, Store, StoreErr};
+use rand::{CryptoRng, RngCore};
 use round_based::{IsCritical, Msg, StateMachine};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::utilities::mta::MessageA;
+use crate::utilities::zk_dlog::DLogProof;
+use crate::utilities::zk_homo_elgamal::HomoELGamalProof;
 
-use curv::{
-    cryptographic_primitives::{
-        proofs::sigma_correct_homomorphic_elgamal_enc::HomoELGamalProof,
-        proofs::sigma_dlog::DLogProof,
-    },
-    elliptic::curves::{secp256_k1::Secp256k1, Scalar}, BigInt
-};
+use curv::BigInt;
+
+use crate::common::transcript::SessionId;
+use crate::common::validation::InvalidValue;
+use crate::error::Blame;
+use crate::utilities::sampling::BoxedRng;
+use paillier::EncryptionKey;
 
+use crate::common::access_structure::AccessStructureError;
 use crate::common::party_i::{
     Phase5ADecom1, Phase5Com1, Phase5Com2, Phase5DDecom2,
-    SignBroadcastPhase1, SignDecommitPhase1, LocalKeyShare,
+    SignBroadcastPhase1, SignDecommitPhase1, LocalKeyShare, PartialSignature,
 };
 
 //mod fmt;
@@ -62,15 +66,24 @@ pub struct OfflineStage {
     msgs5: Option<Store<BroadcastMsgs<SignDecommitPhase1>>>,
     msgs6: Option<Store<BroadcastMsgs<(Phase5Com1,
                                        Phase5ADecom1,
-                                       HomoELGamalProof<Secp256k1, Sha256>,
-                                       DLogProof<Secp256k1, Sha256>)>>>,
+                                       HomoELGamalProof,
+                                       DLogProof)>>>,
     msgs7: Option<Store<BroadcastMsgs<(Phase5Com2, Phase5DDecom2)>>>,
-    msgs8: Option<Store<BroadcastMsgs<Scalar<Secp256k1>>>>,
+    msgs8: Option<Store<BroadcastMsgs<PartialSignature>>>,
 
     msgs_queue: MsgQueue,
 
+    /// Paillier keys of keygen parties, received ciphertexts are validated against them
+    paillier_key_vector: Vec<EncryptionKey>,
+    /// Keygen indexes (starting from 0) of signers, learned from round 1 messages
+    signers: Vec<Option<u16>>,
+    /// Round 2 messages which arrived before round 1 message of the same party
+    pending_msgs2: Vec<Msg<(MessageA, SignBroadcastPhase1)>>,
+
     party_i: u16,
     party_n: u16,
+
+    rng: BoxedRng,
 }
 
 impl OfflineStage {
@@ -83,11 +96,66 @@ impl OfflineStage {
     /// (`s_l[i]` must be an index of party `i` that was used by this party in keygen protocol), and
     /// party local secret share `local_key`.
     ///
+    /// `threshold + 1` is the number of parties taking part in signing. The access structure
+    /// recorded in `local_key_share` is validated here, and whether the actual set of signers is
+    /// authorized is checked at round 1, before any secret-dependent message is sent.
+    ///
+    /// `session_id` must be the same for all signers and unique for every signing session (e.g.
+    /// computation id from `mpc-over-signal`): zero-knowledge proofs are bound to it.
+    ///
+    /// Nonces, MtA masks and proofs are drawn from `rng`. A seeded rng makes the offline stage
+    /// reproducible, use it in tests only.
+    ///
     /// Returns error if given arguments are contradicting.
-    pub fn new(party_num_int: u16, threshold: u16, local_key_share: LocalKeyShare, message_bn: BigInt) -> Result<Self> {
-        
+    pub fn new<Rng>(
+        session_id: SessionId,
+        party_num_int: u16,
+        threshold: u16,
+        local_key_share: LocalKeyShare,
+        message_bn: BigInt,
+        rng: Rng,
+    ) -> Result<Self>
+    where
+        Rng: RngCore + CryptoRng + Send + 'static,
+    {
+        if party_num_int == 0 || party_num_int > threshold + 1 {
+            return Err(Error::InvalidPartyIndex);
+        }
+        let access_structure = &local_key_share.access_structure;
+        access_structure
+            .validate()
+            .map_err(Error::InvalidAccessStructure)?;
+        let keygen_n = usize::from(access_structure.parties());
+        if local_key_share.vss_scheme_vec.len() != keygen_n
+            || local_key_share.paillier_key_vector.len() != keygen_n
+            || local_key_share
+                .vss_scheme_vec
+                .iter()
+                .any(|vss| vss.parameters.threshold != access_structure.threshold)
+        {
+            return Err(Error::InvalidAccessStructure(
+                AccessStructureError::MismatchedKey("number of parties or threshold differ"),
+            ));
+        }
+        if local_key_share.party_id == 0 || usize::from(local_key_share.party_id) > keygen_n {
+            return Err(Error::InvalidSl);
+        }
+        if !local_key_share.has_dlog_setup() {
+            return Err(Error::MissingDLogSetup);
+        }
+
+        let mut signers = vec![None; usize::from(threshold) + 1];
+        signers[usize::from(party_num_int - 1)] = Some(local_key_share.party_id - 1);
+        let paillier_key_vector = local_key_share.paillier_key_vector.clone();
+
         Ok(Self {
-            round: OfflineR::R0(Round0 { party_num_int, threshold, local_key_share, message_bn }),
+            round: OfflineR::R0(Round0 {
+                session_id,
+                party_num_int,
+                threshold,
+                local_key_share,
+                message_bn,
+            }),
 
             msgs1: Some(Round1::expects_messages(party_num_int, threshold+1)),
             msgs2: Some(Round2::expects_messages(party_num_int, threshold+1)),
@@ -100,11 +168,57 @@ impl OfflineStage {
 
             msgs_queue: MsgQueue(vec![]),
 
+            paillier_key_vector,
+            signers,
+            pending_msgs2: vec![],
+
             party_i: party_num_int,
             party_n: threshold+1,
+
+            rng: Box::new(rng),
         })
     }
 
+    /// Own Paillier key, MtA responses are encrypted with it
+    fn own_paillier_key(&self) -> &EncryptionKey {
+        let own_index = self.signers[usize::from(self.party_i - 1)]
+            .expect("own keygen index is set in constructor");
+        &self.paillier_key_vector[usize::from(own_index)]
+    }
+
+    /// Validates MtA message against Paillier key of its sender, postpones it if the sender's
+    /// keygen index isn't known yet
+    fn handle_msg2(&mut self, msg: Msg<(MessageA, SignBroadcastPhase1)>) -> Result<()> {
+        let signer = usize::from(msg.sender)
+            .checked_sub(1)
+            .and_then(|i| self.signers.get(i).copied());
+        match signer {
+            Some(None) => {
+                self.pending_msgs2.push(msg);
+                return Ok(());
+            }
+            Some(Some(signer)) => {
+                let ek = &self.paillier_key_vector[usize::from(signer)];
+                msg.body.0.validate(ek).map_err(|reason| Error::InvalidMessage {
+                    sender: msg.sender,
+                    reason,
+                })?;
+            }
+            // sender is out of range, store will reject the message
+            None => (),
+        }
+
+        let current_round = self.current_round();
+        let store = self
+            .msgs2
+            .as_mut()
+            .ok_or(Error::ReceivedOutOfOrderMessage {
+                current_round,
+                msg_round: 2,
+            })?;
+        store.push_msg(msg).map_err(Error::HandleMessage)
+    }
+
     // fn proceed_state(&mut self, may_block: bool) -> Result<()> {
     //     self.proceed_round(may_block)?;
     //     self.proceed_decommit_round(may_block)
@@ -140,7 +254,7 @@ impl OfflineStage {
                     .finish()
                     .map_err(InternalError::RetrieveMessagesFromStore)?;
                 next_state = round
-                    .proceed(msgs, &mut self.msgs_queue)
+                    .proceed(&mut self.rng, msgs, &mut self.msgs_queue)
                     .map(OfflineR::R2)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -155,7 +269,7 @@ impl OfflineStage {
                     .finish()
                     .map_err(InternalError::RetrieveMessagesFromStore)?;
                 next_state = round
-                    .proceed(msgs, &mut self.msgs_queue)
+                    .proceed(&mut self.rng, msgs, &mut self.msgs_queue)
                     .map(OfflineR::R3)
                     .map_err(Error::ProceedRound)?;
                 true
@@ -200,7 +314,7 @@ impl OfflineStage {
                     .finish()
                     .map_err(InternalError::RetrieveMessagesFromStore)?;
                 next_state = round
-                    .proceed(msgs, &mut self.msgs_queue)
+                    .proceed(&mut self.rng, msgs, &mut self.msgs_queue)
                     .map(OfflineR::R6)
                     .map_err(Error::ProceedRound)?;
                 false
@@ -215,7 +329,7 @@ impl OfflineStage {
                     .finish()
                     .map_err(InternalError::RetrieveMessagesFromStore)?;
                 next_state = round
-                    .proceed(msgs, &mut self.msgs_queue)
+                    .proceed(&mut self.rng, msgs, &mut self.msgs_queue)
                     .map(OfflineR::R7)
                     .map_err(Error::ProceedRound)?;
                 false
@@ -277,8 +391,14 @@ impl StateMachine for OfflineStage {
     fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<(), Self::Err> {
         let current_round = self.current_round();
 
+        let sender = msg.sender;
+        let invalid = move |reason| Error::InvalidMessage { sender, reason };
+
         match msg.body {
             OfflineProtocolMessage(OfflineM::M1(m)) => {
+                if usize::from(m) >= self.paillier_key_vector.len() {
+                    return Err(invalid(InvalidValue::IndexOutOfRange("keygen party index")));
+                }
                 let store = self
                     .msgs1
                     .as_mut()
@@ -293,30 +413,33 @@ impl StateMachine for OfflineStage {
                         body: m,
                     })
                     .map_err(Error::HandleMessage)?;
+                // store accepted the message, so sender is in range
+                self.signers[usize::from(msg.sender - 1)] = Some(m);
+                let (ready, pending) = take(&mut self.pending_msgs2)
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|pending| pending.sender == msg.sender);
+                self.pending_msgs2 = pending;
+                for pending in ready {
+                    self.handle_msg2(pending)?;
+                }
             }
             OfflineProtocolMessage(OfflineM::M2(m)) => {
-                let store = self
-                    .msgs2
-                    .as_mut()
-                    .ok_or(Error::ReceivedOutOfOrderMessage {
-                        current_round,
-                        msg_round: 2,
-                    })?;
-                store
-                    .push_msg(Msg {
-                        sender: msg.sender,
-                        receiver: msg.receiver,
-                        body: m,
-                    })
-                    .map_err(Error::HandleMessage)?;
+                self.handle_msg2(Msg {
+                    sender: msg.sender,
+                    receiver: msg.receiver,
+                    body: m,
+                })?;
             }
             OfflineProtocolMessage(OfflineM::M3(m)) => {
+                let ek = self.own_paillier_key();
+                m.0 .0.validate(ek).map_err(invalid)?;
+                m.1 .0.validate(ek).map_err(invalid)?;
                 let store = self
                     .msgs3
                     .as_mut()
                     .ok_or(Error::ReceivedOutOfOrderMessage {
                         current_round,
-                        msg_round: 2,
+                        msg_round: 3,
                     })?;
                 store
                     .push_msg(Msg {
@@ -332,7 +455,7 @@ impl StateMachine for OfflineStage {
                     .as_mut()
                     .ok_or(Error::ReceivedOutOfOrderMessage {
                         current_round,
-                        msg_round: 2,
+                        msg_round: 4,
                     })?;
                 store
                     .push_msg(Msg {
@@ -343,12 +466,13 @@ impl StateMachine for OfflineStage {
                     .map_err(Error::HandleMessage)?;
             }
             OfflineProtocolMessage(OfflineM::M5(m)) => {
+                m.validate().map_err(invalid)?;
                 let store = self
                     .msgs5
                     .as_mut()
                     .ok_or(Error::ReceivedOutOfOrderMessage {
                         current_round,
-                        msg_round: 2,
+                        msg_round: 5,
                     })?;
                 store
                     .push_msg(Msg {
@@ -359,12 +483,15 @@ impl StateMachine for OfflineStage {
                     .map_err(Error::HandleMessage)?;
             }
             OfflineProtocolMessage(OfflineM::M6(m)) => {
+                m.1.validate().map_err(invalid)?;
+                m.2.validate().map_err(invalid)?;
+                m.3.validate().map_err(invalid)?;
                 let store = self
                     .msgs6
                     .as_mut()
                     .ok_or(Error::ReceivedOutOfOrderMessage {
                         current_round,
-                        msg_round: 2,
+                        msg_round: 6,
                     })?;
                 store
                     .push_msg(Msg {
@@ -375,12 +502,13 @@ impl StateMachine for OfflineStage {
                     .map_err(Error::HandleMessage)?;
             }
             OfflineProtocolMessage(OfflineM::M7(m)) => {
+                m.1.validate().map_err(invalid)?;
                 let store = self
                     .msgs7
                     .as_mut()
                     .ok_or(Error::ReceivedOutOfOrderMessage {
                         current_round,
-                        msg_round: 2,
+                        msg_round: 7,
                     })?;
                 store
                     .push_msg(Msg {
@@ -396,7 +524,7 @@ impl StateMachine for OfflineStage {
                     .as_mut()
                     .ok_or(Error::ReceivedOutOfOrderMessage {
                         current_round,
-                        msg_round: 2,
+                        msg_round: 8,
                     })?;
                 store
                     .push_msg(Msg {
@@ -524,6 +652,41 @@ impl crate::dkg::traits::RoundBlame for OfflineStage {
     }
 }
 
+impl fmt::Debug for OfflineStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let current_round = match &self.round {
+            OfflineR::Finished(_) => "[Finished]".to_string(),
+            OfflineR::Gone => "[Gone]".to_string(),
+            _ => self.current_round().to_string(),
+        };
+        let msgs = [
+            self.msgs1.as_ref().map(|s| (s.messages_received(), s.messages_total())),
+            self.msgs2.as_ref().map(|s| (s.messages_received(), s.messages_total())),
+            self.msgs3.as_ref().map(|s| (s.messages_received(), s.messages_total())),
+            self.msgs4.as_ref().map(|s| (s.messages_received(), s.messages_total())),
+            self.msgs5.as_ref().map(|s| (s.messages_received(), s.messages_total())),
+            self.msgs6.as_ref().map(|s| (s.messages_received(), s.messages_total())),
+            self.msgs7.as_ref().map(|s| (s.messages_received(), s.messages_total())),
+            self.msgs8.as_ref().map(|s| (s.messages_received(), s.messages_total())),
+        ];
+        let msgs = msgs
+            .iter()
+            .map(|m| match m {
+                Some((received, total)) => format!("[{}/{}]", received, total),
+                None => "[None]".into(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        write!(
+            f,
+            "{{OfflineStage at round={} msgs={} queue=[len={}]}}",
+            current_round,
+            msgs,
+            self.msgs_queue.0.len()
+        )
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 enum OfflineR {
     R0(Round0),
@@ -552,10 +715,10 @@ enum OfflineM {
     M5(SignDecommitPhase1),
     M6((Phase5Com1,
         Phase5ADecom1,
-        HomoELGamalProof<Secp256k1, Sha256>,
-        DLogProof<Secp256k1, Sha256>)),
+        HomoELGamalProof,
+        DLogProof)),
     M7((Phase5Com2, Phase5DDecom2)),
-    M8(Scalar<Secp256k1>),
+    M8(PartialSignature),
 }
 
 struct MsgQueue(Vec<Msg<OfflineProtocolMessage>>);
@@ -584,10 +747,10 @@ make_pushable! {
     M5 SignDecommitPhase1,
     M6 (Phase5Com1,
         Phase5ADecom1,
-        HomoELGamalProof<Secp256k1, Sha256>,
-        DLogProof<Secp256k1, Sha256>),
+        HomoELGamalProof,
+        DLogProof),
     M7 (Phase5Com2, Phase5DDecom2),
-    M8 Scalar<Secp256k1>,
+    M8 PartialSignature,
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -608,10 +771,24 @@ pub enum Error {
     /// participated in DKG (`exist i. s_l[i] = 0 || s_l[i] > keygen_n`).
     #[error("invalid s_l")]
     InvalidSl,
+    /// Access structure recorded in local key share is malformed or doesn't match the key
+    #[error("invalid access structure: {0}")]
+    InvalidAccessStructure(#[source] AccessStructureError),
+    /// Local key share doesn't have `h1, h2, N_tilde` of every party, which are required for
+    /// range proofs in MtA (e.g. key was imported from ZenGo key store)
+    #[error("local key share is missing h1, h2, N_tilde setup of other parties")]
+    MissingDLogSetup,
 
     /// Round proceeding resulted in protocol error
     #[error("proceeding round: {0}")]
     ProceedRound(crate::signing::rounds::Error),
+    /// Party `sender` sent a message with invalid curve point, ciphertext or index
+    #[error("party {sender} sent invalid message: {reason}")]
+    InvalidMessage {
+        sender: u16,
+        #[source]
+        reason: InvalidValue,
+    },
 
     /// Received message which we didn't expect to receive now (e.g. message from previous round)
     #[error(
@@ -654,7 +831,10 @@ impl IsCritical for Error {
             Error::TooManyParties { .. } => true,
             Error::InvalidPartyIndex => true,
             Error::InvalidSl => true,
+            Error::InvalidAccessStructure(_) => true,
+            Error::MissingDLogSetup => true,
             Error::ProceedRound(_) => true,
+            Error::InvalidMessage { .. } => true,
             Error::ReceivedOutOfOrderMessage { .. } => false,
             Error::HandleMessage(_) => false,
             Error::DoublePickOutput => true,
@@ -663,6 +843,25 @@ impl IsCritical for Error {
     }
 }
 
+impl Blame for Error {
+    fn culprits(&self) -> Vec<u16> {
+        match self {
+            Error::InvalidMessage { sender, .. } => vec![*sender],
+            Error::ProceedRound(err) => match err {
+                ProceedError::Round1InvalidSigner { party, .. }
+                | ProceedError::Round2MtA { party, .. }
+                | ProceedError::Round3MtA { party, .. }
+                | ProceedError::Round5Decommitment { party }
+                | ProceedError::Round6Decommitment { party }
+                | ProceedError::Round7Decommitment { party }
+                | ProceedError::Round8PartialSignature { party } => vec![*party],
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+}
+
 /// Manual GG20 signing
 ///
 /// After you completed [OfflineStage] and got [CompletedOfflineStagE], parties can perform signing
@@ -729,3 +928,327 @@ pub enum SignError {
     #[error("couldn't complete signing: {0}")]
     CompleteSigning(crate::signing::rounds::Error),
 }
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use curv::arithmetic::traits::Converter;
+    use curv::elliptic::curves::Scalar;
+    use rand::rngs::{OsRng, StdRng};
+    use rand::SeedableRng;
+    use round_based::dev::Simulation;
+
+    use super::*;
+    use crate::dkg::keygen::test::{simulate_keygen, simulate_keygen_seeded, SESSION_ID};
+
+    /// Party that spoils its own outgoing messages with `corrupt` and ignores its own errors, so
+    /// that simulation fails with an error of the honest party
+    struct Corrupted {
+        stage: OfflineStage,
+        corrupt: Option<fn(&mut OfflineM)>,
+    }
+
+    impl StateMachine for Corrupted {
+        type MessageBody = OfflineProtocolMessage;
+        type Err = Error;
+        type Output = CompletedOfflineStage;
+
+        fn handle_incoming(&mut self, msg: Msg<Self::MessageBody>) -> Result<()> {
+            let result = self.stage.handle_incoming(msg);
+            if self.corrupt.is_some() {
+                Ok(())
+            } else {
+                result
+            }
+        }
+
+        fn message_queue(&mut self) -> &mut Vec<Msg<Self::MessageBody>> {
+            let queue = self.stage.message_queue();
+            if let Some(corrupt) = self.corrupt {
+                for msg in queue.iter_mut() {
+                    corrupt(&mut msg.body.0);
+                }
+            }
+            queue
+        }
+
+        fn wants_to_proceed(&self) -> bool {
+            self.stage.wants_to_proceed()
+        }
+
+        fn proceed(&mut self) -> Result<()> {
+            let result = self.stage.proceed();
+            if self.corrupt.is_some() {
+                Ok(())
+            } else {
+                result
+            }
+        }
+
+        fn round_timeout(&self) -> Option<Duration> {
+            self.stage.round_timeout()
+        }
+
+        fn round_timeout_reached(&mut self) -> Self::Err {
+            self.stage.round_timeout_reached()
+        }
+
+        fn is_finished(&self) -> bool {
+            self.stage.is_finished()
+        }
+
+        fn pick_output(&mut self) -> Option<Result<Self::Output>> {
+            self.stage.pick_output()
+        }
+
+        fn current_round(&self) -> u16 {
+            self.stage.current_round()
+        }
+
+        fn total_rounds(&self) -> Option<u16> {
+            self.stage.total_rounds()
+        }
+
+        fn party_ind(&self) -> u16 {
+            self.stage.party_ind()
+        }
+
+        fn parties(&self) -> u16 {
+            self.stage.parties()
+        }
+    }
+
+    impl fmt::Debug for Corrupted {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.stage.fmt(f)
+        }
+    }
+
+    /// Signs with keygen parties 3 and 1 (signing parties 1 and 2), party 2 corrupts its messages
+    fn sign_with_corrupted_party_2(keys: &[LocalKeyShare], corrupt: fn(&mut OfflineM)) -> Error {
+        let message = BigInt::from_bytes(b"message to sign");
+        let mut simulation = Simulation::new();
+        for (i, key, corrupt) in [(1, &keys[2], None), (2, &keys[0], Some(corrupt))].iter() {
+            let stage =
+                OfflineStage::new(SESSION_ID, *i, 1, (*key).clone(), message.clone(), OsRng)
+                    .unwrap();
+            simulation.add_party(Corrupted {
+                stage,
+                corrupt: *corrupt,
+            });
+        }
+        match simulation.run() {
+            Ok(_) => panic!("signing with corrupted messages succeeded"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn garbage_from_peer_is_reported_not_panicked() {
+        let keys: Vec<LocalKeyShare> = simulate_keygen(1, 3)
+            .into_iter()
+            .map(LocalKeyShare::from)
+            .collect();
+
+        let err = sign_with_corrupted_party_2(&keys, |m| {
+            if let OfflineM::M1(index) = m {
+                *index = 7
+            }
+        });
+        assert!(matches!(err, Error::InvalidMessage { sender: 2, .. }), "{:?}", err);
+
+        let err = sign_with_corrupted_party_2(&keys, |m| {
+            if let OfflineM::M2((_, bc)) = m {
+                bc.com = &bc.com + BigInt::from(1)
+            }
+        });
+        assert!(
+            matches!(err, Error::ProceedRound(ProceedError::Round5Decommitment { party: 2 })),
+            "{:?}",
+            err
+        );
+
+        let err = sign_with_corrupted_party_2(&keys, |m| {
+            if let OfflineM::M3((gamma, w)) = m {
+                *w = WI(gamma.0.clone())
+            }
+        });
+        assert!(
+            matches!(err, Error::ProceedRound(ProceedError::Round3MtA { party: 2, .. })),
+            "{:?}",
+            err
+        );
+
+        let err = sign_with_corrupted_party_2(&keys, |m| {
+            if let OfflineM::M4(delta) = m {
+                *delta = DeltaI(Scalar::random())
+            }
+        });
+        assert!(
+            matches!(err, Error::ProceedRound(ProceedError::Round6Decommitment { party: 2 })),
+            "{:?}",
+            err
+        );
+
+        let err = sign_with_corrupted_party_2(&keys, |m| {
+            if let OfflineM::M5(decom) = m {
+                decom.blind_factor = &decom.blind_factor + BigInt::from(1)
+            }
+        });
+        assert!(
+            matches!(err, Error::ProceedRound(ProceedError::Round5Decommitment { party: 2 })),
+            "{:?}",
+            err
+        );
+
+        let err = sign_with_corrupted_party_2(&keys, |m| {
+            if let OfflineM::M6((com, ..)) = m {
+                com.com = &com.com + BigInt::from(1)
+            }
+        });
+        assert!(
+            matches!(err, Error::ProceedRound(ProceedError::Round6Decommitment { party: 2 })),
+            "{:?}",
+            err
+        );
+
+        let err = sign_with_corrupted_party_2(&keys, |m| {
+            if let OfflineM::M7((com, _)) = m {
+                com.com = &com.com + BigInt::from(1)
+            }
+        });
+        assert!(
+            matches!(err, Error::ProceedRound(ProceedError::Round7Decommitment { party: 2 })),
+            "{:?}",
+            err
+        );
+
+        let err = sign_with_corrupted_party_2(&keys, |m| {
+            if let OfflineM::M8(partial) = m {
+                partial.s_i = Scalar::random()
+            }
+        });
+        assert!(
+            matches!(
+                err,
+                Error::ProceedRound(ProceedError::Round8PartialSignature { party: 2 })
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn missing_alice_range_proof_is_rejected() {
+        let keys: Vec<LocalKeyShare> = simulate_keygen(1, 3)
+            .into_iter()
+            .map(LocalKeyShare::from)
+            .collect();
+
+        let err = sign_with_corrupted_party_2(&keys, |m| {
+            if let OfflineM::M2((m_a, _)) = m {
+                m_a.range_proofs.clear()
+            }
+        });
+        assert!(
+            matches!(err, Error::ProceedRound(ProceedError::Round2MtA { party: 2, .. })),
+            "{:?}",
+            err
+        );
+    }
+
+    thread_local! {
+        /// Messages sent by the party that runs [record_message]
+        static RECORDED: RefCell<Vec<OfflineM>> = RefCell::new(vec![]);
+    }
+
+    fn record_message(m: &mut OfflineM) {
+        RECORDED.with(|recorded| recorded.borrow_mut().push(m.clone()))
+    }
+
+    fn round_of(m: &OfflineM) -> u16 {
+        match m {
+            OfflineM::M1(_) => 1,
+            OfflineM::M2(_) => 2,
+            OfflineM::M3(_) => 3,
+            OfflineM::M4(_) => 4,
+            OfflineM::M5(_) => 5,
+            OfflineM::M6(_) => 6,
+            OfflineM::M7(_) => 7,
+            OfflineM::M8(_) => 8,
+        }
+    }
+
+    #[test]
+    fn late_message_is_reported_with_its_round() {
+        let keys: Vec<LocalKeyShare> = simulate_keygen(1, 3)
+            .into_iter()
+            .map(LocalKeyShare::from)
+            .collect();
+        let message = BigInt::from_bytes(b"message to sign");
+        let stage = |i, key: &LocalKeyShare| {
+            OfflineStage::new(SESSION_ID, i, 1, key.clone(), message.clone(), OsRng).unwrap()
+        };
+        let mut simulation = Simulation::new();
+        simulation.add_party(Corrupted {
+            stage: stage(1, &keys[2]),
+            corrupt: None,
+        });
+        simulation.add_party(Corrupted {
+            stage: stage(2, &keys[0]),
+            corrupt: Some(record_message),
+        });
+        simulation.run().unwrap();
+
+        // party 1 is done, so every message of party 2 delivered again is late
+        let recorded = RECORDED.with(|recorded| recorded.borrow().clone());
+        let party_1 = &mut simulation.parties[0].stage;
+        for round in 1..=8 {
+            let body = recorded
+                .iter()
+                .find(|m| round_of(m) == round)
+                .unwrap_or_else(|| panic!("party 2 sent nothing at round {}", round))
+                .clone();
+            let receiver = if round == 3 { Some(1) } else { None };
+            let err = party_1
+                .handle_incoming(Msg {
+                    sender: 2,
+                    receiver,
+                    body: OfflineProtocolMessage(body),
+                })
+                .unwrap_err();
+            assert!(
+                matches!(
+                    err,
+                    Error::ReceivedOutOfOrderMessage { msg_round, .. } if msg_round == round
+                ),
+                "{:?}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn signing_is_reproducible_from_seed() {
+        let keys: Vec<LocalKeyShare> = simulate_keygen_seeded(1, 2, 0)
+            .into_iter()
+            .map(LocalKeyShare::from)
+            .collect();
+        let message = BigInt::from_bytes(b"message to sign");
+        let sign = || {
+            let mut simulation = Simulation::new();
+            for (i, key) in (1..).zip(&keys) {
+                let rng = StdRng::from_seed([10 + i as u8; 32]);
+                let stage =
+                    OfflineStage::new(SESSION_ID, i, 1, key.clone(), message.clone(), rng).unwrap();
+                simulation.add_party(stage);
+            }
+            simulation.run().unwrap()
+        };
+
+        let (sig1, sig2) = (sign(), sign());
+        assert_eq!(sig1[0].signature_rec_id.r, sig2[0].signature_rec_id.r);
+        assert_eq!(sig1[0].signature_rec_id.s, sig2[0].signature_rec_id.s);
+    }
+}
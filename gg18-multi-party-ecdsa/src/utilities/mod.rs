@@ -1,3 +1,8 @@
 pub mod mta;
+pub mod paillier_crt;
+pub mod sampling;
+pub mod zk_composite_dlog;
+pub mod zk_dlog;
+pub mod zk_homo_elgamal;
 pub mod zk_pdl;
 pub mod zk_pdl_with_slack;
@@ -15,33 +15,52 @@
 */
 
 /// MtA is described in https://eprint.iacr.org/2019/114.pdf section 3
-use curv::arithmetic::traits::Samplable;
-use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+///
+/// Alice's proofs are bound to Alice's [ProofContext], Bob's proofs to Bob's one.
 use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
 use curv::BigInt;
 use paillier::traits::EncryptWithChosenRandomness;
-use paillier::{Add, Decrypt, Mul};
-use paillier::{DecryptionKey, EncryptionKey, Paillier, Randomness, RawCiphertext, RawPlaintext};
+use paillier::{Add, Mul};
+use paillier::{EncryptionKey, Paillier, Randomness, RawCiphertext, RawPlaintext};
+use rand::{CryptoRng, RngCore};
 use zk_paillier::zkproofs::DLogStatement;
 
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
 
 use crate::common::party_i::PartyPrivate;
-use crate::utilities::mta::range_proofs::AliceProof;
+use crate::common::transcript::ProofContext;
+use crate::common::validation::{InvalidValue, PaillierCiphertext};
+use crate::utilities::mta::range_proofs::{AliceProof, BobProof, BobProofExt};
+use crate::utilities::paillier_crt::PrecomputedDecryptionKey;
+use crate::utilities::sampling::{sample_below, sample_unit};
+use crate::utilities::zk_dlog::DLogProof;
 use crate::common::Error::{self, InvalidKey};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MessageA {
-    pub c: BigInt,                     // paillier encryption
+    pub c: PaillierCiphertext,         // paillier encryption
     pub range_proofs: Vec<AliceProof>, // proofs (using other parties' h1,h2,N_tilde) that the plaintext is small
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MessageB {
-    pub c: BigInt, // paillier encryption
-    pub b_proof: DLogProof<Secp256k1, Sha256>,
-    pub beta_tag_proof: DLogProof<Secp256k1, Sha256>,
+    pub c: PaillierCiphertext, // paillier encryption
+    pub b_proof: DLogProof,
+    pub beta_tag_proof: DLogProof,
+    pub range_proof: Option<BobRangeProof>, // proof (using Alice's h1,h2,N_tilde) that b is small
+}
+
+/// [MessageA] whose ciphertext and range proofs were checked by Bob, see [MessageA::verify]
+#[derive(Clone, Debug)]
+pub struct VerifiedMessageA(MessageA);
+
+/// Bob's range proof, see [MessageB::b_with_range_proof]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BobRangeProof {
+    /// MtA: `b` is small and `c` is computed from Alice's ciphertext correctly
+    Plain(BobProof),
+    /// MtAwc: in addition, `b` is the discrete log of a point known to Alice
+    WithCheck(BobProofExt),
 }
 
 impl MessageA {
@@ -49,17 +68,53 @@ impl MessageA {
     /// - other parties' `h1,h2,N_tilde`s for range proofs.
     /// If range proofs are not needed (one example is identification of aborts where we
     /// only want to reconstruct a ciphertext), `dlog_statements` can be an empty slice.
-    pub fn a(
+    pub fn a<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        alice_ctx: &ProofContext,
         a: &Scalar<Secp256k1>,
         alice_ek: &EncryptionKey,
         dlog_statements: &[DLogStatement],
     ) -> (Self, BigInt) {
-        let randomness = BigInt::sample_below(&alice_ek.n);
-        let m_a = MessageA::a_with_predefined_randomness(a, alice_ek, &randomness, dlog_statements);
+        let randomness = sample_unit(rng, &alice_ek.n);
+        let m_a = MessageA::a_with_predefined_randomness(
+            rng,
+            alice_ctx,
+            a,
+            alice_ek,
+            &randomness,
+            dlog_statements,
+        );
+        (m_a, randomness)
+    }
+
+    /// Same as [MessageA::a], but encrypts `a` using factorization of Alice's own modulus,
+    /// which is several times faster than [MessageA::a]
+    pub fn a_with_own_key<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        alice_ctx: &ProofContext,
+        a: &Scalar<Secp256k1>,
+        alice_dk: &PrecomputedDecryptionKey,
+        dlog_statements: &[DLogStatement],
+    ) -> (Self, BigInt) {
+        let alice_ek = alice_dk.ek();
+        let randomness = sample_unit(rng, &alice_ek.n);
+        let c_a = alice_dk.encrypt_with_chosen_randomness(&a.to_bigint(), &randomness);
+        let m_a = MessageA::from_ciphertext(
+            rng,
+            alice_ctx,
+            a,
+            alice_ek,
+            c_a,
+            &randomness,
+            dlog_statements,
+        );
         (m_a, randomness)
     }
 
-    pub fn a_with_predefined_randomness(
+    /// `randomness` must be in `Z*_N`
+    pub fn a_with_predefined_randomness<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        alice_ctx: &ProofContext,
         a: &Scalar<Secp256k1>,
         alice_ek: &EncryptionKey,
         randomness: &BigInt,
@@ -73,30 +128,90 @@ impl MessageA {
         .0
         .clone()
         .into_owned();
+        MessageA::from_ciphertext(rng, alice_ctx, a, alice_ek, c_a, randomness, dlog_statements)
+    }
+
+    /// Attaches range proofs to `c_a`, an encryption of `a` with `randomness`
+    fn from_ciphertext<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        alice_ctx: &ProofContext,
+        a: &Scalar<Secp256k1>,
+        alice_ek: &EncryptionKey,
+        c_a: BigInt,
+        randomness: &BigInt,
+        dlog_statements: &[DLogStatement],
+    ) -> Self {
         let alice_range_proofs = dlog_statements
             .iter()
             .map(|dlog_statement| {
-                AliceProof::generate(&a.to_bigint(), &c_a, alice_ek, dlog_statement, randomness)
+                AliceProof::generate(
+                    rng,
+                    alice_ctx,
+                    &a.to_bigint(),
+                    &c_a,
+                    alice_ek,
+                    dlog_statement,
+                    randomness,
+                )
             })
             .collect::<Vec<AliceProof>>();
 
         Self {
-            c: c_a,
+            c: PaillierCiphertext::new(c_a, alice_ek, "c_a")
+                .expect("encryption with randomness from Z*_N is in Z*_{N^2}"),
             range_proofs: alice_range_proofs,
         }
     }
+
+    /// Checks message received from Alice before it's used
+    pub fn validate(&self, alice_ek: &EncryptionKey) -> Result<(), InvalidValue> {
+        self.c.validate(alice_ek, "c_a")
+    }
+
+    /// Checks the ciphertext and Alice's range proofs made with `dlog_statements` - Bob's
+    /// `h1,h2,N_tilde`s
+    ///
+    /// Bob checks Alice's message once, and then builds any number of [MessageB]s from the
+    /// result with [MessageB::b_with_range_proof].
+    pub fn verify(
+        self,
+        alice_ctx: &ProofContext,
+        alice_ek: &EncryptionKey,
+        dlog_statements: &[DLogStatement],
+    ) -> Result<VerifiedMessageA, Error> {
+        if self.range_proofs.len() != dlog_statements.len() || self.validate(alice_ek).is_err() {
+            return Err(InvalidKey);
+        }
+        let proofs_are_valid =
+            self.range_proofs
+                .iter()
+                .zip(dlog_statements)
+                .all(|(proof, dlog_statement)| {
+                    proof.verify(alice_ctx, &self.c, alice_ek, dlog_statement)
+                });
+        if !proofs_are_valid {
+            return Err(InvalidKey);
+        }
+        Ok(VerifiedMessageA(self))
+    }
 }
 
 impl MessageB {
-    pub fn b(
+    pub fn b<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        alice_ctx: &ProofContext,
+        bob_ctx: &ProofContext,
         b: &Scalar<Secp256k1>,
         alice_ek: &EncryptionKey,
         m_a: MessageA,
         dlog_statements: &[DLogStatement],
     ) -> Result<(Self, Scalar<Secp256k1>, BigInt, BigInt), Error> {
-        let beta_tag = BigInt::sample_below(&alice_ek.n);
-        let randomness = BigInt::sample_below(&alice_ek.n);
+        let beta_tag = sample_below(rng, &alice_ek.n);
+        let randomness = sample_unit(rng, &alice_ek.n);
         let (m_b, beta) = MessageB::b_with_predefined_randomness(
+            rng,
+            alice_ctx,
+            bob_ctx,
             b,
             alice_ek,
             m_a,
@@ -108,7 +223,11 @@ impl MessageB {
         Ok((m_b, beta, randomness, beta_tag))
     }
 
-    pub fn b_with_predefined_randomness(
+    #[allow(clippy::too_many_arguments)]
+    pub fn b_with_predefined_randomness<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        alice_ctx: &ProofContext,
+        bob_ctx: &ProofContext,
         b: &Scalar<Secp256k1>,
         alice_ek: &EncryptionKey,
         m_a: MessageA,
@@ -116,19 +235,20 @@ impl MessageB {
         beta_tag: &BigInt,
         dlog_statements: &[DLogStatement],
     ) -> Result<(Self, Scalar<Secp256k1>), Error> {
-        if m_a.range_proofs.len() != dlog_statements.len() {
-            return Err(InvalidKey);
-        }
-        // verify proofs
-        if !m_a
-            .range_proofs
-            .iter()
-            .zip(dlog_statements)
-            .map(|(proof, dlog_statement)| proof.verify(&m_a.c, alice_ek, dlog_statement))
-            .all(|x| x)
-        {
-            return Err(InvalidKey);
-        };
+        let m_a = m_a.verify(alice_ctx, alice_ek, dlog_statements)?;
+        MessageB::from_verified(rng, bob_ctx, b, alice_ek, &m_a, randomness, beta_tag)
+    }
+
+    /// Bob's side of MtA for Alice's message that is already checked
+    fn from_verified<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        bob_ctx: &ProofContext,
+        b: &Scalar<Secp256k1>,
+        alice_ek: &EncryptionKey,
+        m_a: &VerifiedMessageA,
+        randomness: &BigInt,
+        beta_tag: &BigInt,
+    ) -> Result<(Self, Scalar<Secp256k1>), Error> {
         let beta_tag_fe = Scalar::<Secp256k1>::from(beta_tag);
         let c_beta_tag = Paillier::encrypt_with_chosen_randomness(
             alice_ek,
@@ -139,40 +259,125 @@ impl MessageB {
         let b_bn = b.to_bigint();
         let b_c_a = Paillier::mul(
             alice_ek,
-            RawCiphertext::from(m_a.c),
+            RawCiphertext::from(m_a.0.c.as_bigint()),
             RawPlaintext::from(b_bn),
         );
         let c_b = Paillier::add(alice_ek, b_c_a, c_beta_tag);
         let beta = Scalar::<Secp256k1>::zero() - &beta_tag_fe;
-        let dlog_proof_b = DLogProof::prove(b);
-        let dlog_proof_beta_tag = DLogProof::prove(&beta_tag_fe);
+        let dlog_proof_b = DLogProof::prove(rng, bob_ctx, b);
+        let dlog_proof_beta_tag = DLogProof::prove(rng, bob_ctx, &beta_tag_fe);
 
         Ok((
             Self {
-                c: c_b.0.clone().into_owned(),
+                c: PaillierCiphertext::new(c_b.0.into_owned(), alice_ek, "c_b")
+                    .map_err(|_| InvalidKey)?,
                 b_proof: dlog_proof_b,
                 beta_tag_proof: dlog_proof_beta_tag,
+                range_proof: None,
             },
             beta,
         ))
     }
 
+    /// Same as [MessageB::b], but takes Alice's message [checked](MessageA::verify) in
+    /// advance, and also attaches Bob's range proof made with Alice's `h1,h2,N_tilde`
+    /// (`alice_dlog_statement`)
+    ///
+    /// If `check` is set, runs MtAwc: the proof additionally shows that `b` is the discrete log of
+    /// `g^b`, which Alice compares against the public value she expects (see
+    /// [MessageB::verify_range_proof]).
+    pub fn b_with_range_proof<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        bob_ctx: &ProofContext,
+        b: &Scalar<Secp256k1>,
+        alice_ek: &EncryptionKey,
+        m_a: &VerifiedMessageA,
+        alice_dlog_statement: &DLogStatement,
+        check: bool,
+    ) -> Result<(Self, Scalar<Secp256k1>), Error> {
+        let beta_tag = sample_below(rng, &alice_ek.n);
+        let randomness = sample_unit(rng, &alice_ek.n);
+        let (mut m_b, beta) =
+            MessageB::from_verified(rng, bob_ctx, b, alice_ek, m_a, &randomness, &beta_tag)?;
+        let randomness = Randomness::from(randomness);
+        let c_a = &m_a.0.c;
+        let range_proof = if check {
+            BobRangeProof::WithCheck(BobProofExt::generate(
+                rng,
+                bob_ctx,
+                c_a,
+                &m_b.c,
+                b,
+                &beta_tag,
+                alice_ek,
+                alice_dlog_statement,
+                &randomness,
+            ))
+        } else {
+            let (proof, _) = BobProof::generate(
+                rng,
+                bob_ctx,
+                c_a,
+                &m_b.c,
+                b,
+                &beta_tag,
+                alice_ek,
+                alice_dlog_statement,
+                &randomness,
+                false,
+            );
+            BobRangeProof::Plain(proof)
+        };
+        m_b.range_proof = Some(range_proof);
+
+        Ok((m_b, beta))
+    }
+
+    /// Verifies Bob's range proof against Alice's ciphertext `c_a` and her `h1,h2,N_tilde`
+    ///
+    /// `expected_g_b` must be set iff MtAwc is expected: then the proof must be an extended one,
+    /// made for exactly this point.
+    pub fn verify_range_proof(
+        &self,
+        bob_ctx: &ProofContext,
+        c_a: &BigInt,
+        alice_ek: &EncryptionKey,
+        alice_dlog_statement: &DLogStatement,
+        expected_g_b: Option<&Point<Secp256k1>>,
+    ) -> Result<(), Error> {
+        let valid = match (&self.range_proof, expected_g_b) {
+            (Some(BobRangeProof::Plain(proof)), None) => {
+                proof.verify(bob_ctx, c_a, &self.c, alice_ek, alice_dlog_statement, None)
+            }
+            (Some(BobRangeProof::WithCheck(proof)), Some(g_b)) => {
+                proof.verify(bob_ctx, c_a, &self.c, alice_ek, alice_dlog_statement, g_b)
+            }
+            _ => false,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(InvalidKey)
+        }
+    }
+
     pub fn verify_proofs_get_alpha(
         &self,
-        dk: &DecryptionKey,
+        bob_ctx: &ProofContext,
+        dk: &PrecomputedDecryptionKey,
         a: &Scalar<Secp256k1>,
     ) -> Result<(Scalar<Secp256k1>, BigInt), Error> {
-        let alice_share = Paillier::decrypt(dk, &RawCiphertext::from(self.c.clone()));
+        let alice_share = dk.decrypt(self.c.as_bigint());
         let g = Point::generator();
-        let alpha = Scalar::<Secp256k1>::from(alice_share.0.as_ref());
+        let alpha = Scalar::<Secp256k1>::from(&alice_share);
         let g_alpha = g * &alpha;
         let ba_btag = &self.b_proof.pk * a + &self.beta_tag_proof.pk;
-        if DLogProof::verify(&self.b_proof).is_ok()
-            && DLogProof::verify(&self.beta_tag_proof).is_ok()
+        if self.b_proof.verify(bob_ctx).is_ok()
+            && self.beta_tag_proof.verify(bob_ctx).is_ok()
             // we prove the correctness of the ciphertext using this check and the proof of knowledge of dlog of beta_tag
             && ba_btag == g_alpha
         {
-            Ok((alpha, alice_share.0.into_owned()))
+            Ok((alpha, alice_share))
         } else {
             Err(InvalidKey)
         }
@@ -182,17 +387,18 @@ impl MessageB {
     //  with the regular version mta can be used in general
     pub fn verify_proofs_get_alpha_gg18(
         &self,
+        bob_ctx: &ProofContext,
         private: &PartyPrivate,
         a: &Scalar<Secp256k1>,
     ) -> Result<Scalar<Secp256k1>, Error> {
-        let alice_share = private.decrypt(self.c.clone());
+        let alice_share = private.decrypt(self.c.as_bigint().clone());
         let g = Point::generator();
         let alpha = Scalar::<Secp256k1>::from(alice_share.0.as_ref());
         let g_alpha = g * &alpha;
         let ba_btag = &self.b_proof.pk * a + &self.beta_tag_proof.pk;
 
-        if DLogProof::verify(&self.b_proof).is_ok()
-            && DLogProof::verify(&self.beta_tag_proof).is_ok()
+        if self.b_proof.verify(bob_ctx).is_ok()
+            && self.beta_tag_proof.verify(bob_ctx).is_ok()
             && ba_btag == g_alpha
         {
             Ok(alpha)
@@ -201,6 +407,13 @@ impl MessageB {
         }
     }
 
+    /// Checks message received from Bob before it's used, `alice_ek` is our own key
+    pub fn validate(&self, alice_ek: &EncryptionKey) -> Result<(), InvalidValue> {
+        self.c.validate(alice_ek, "c_b")?;
+        self.b_proof.validate()?;
+        self.beta_tag_proof.validate()
+    }
+
     pub fn verify_b_against_public(
         public_gb: &Point<Secp256k1>,
         mta_gb: &Point<Secp256k1>,
@@ -8,6 +8,8 @@
 //! There are some deviations from the original specification:
 //! 1) In Bob's proofs `gamma` is sampled from `[0;q^2 * N]` and `tau` from `[0;q^3 * N_tilde]`.
 //! 2) A non-interactive version is implemented, with challenge `e` computed via Fiat-Shamir.
+//! 3) Challenge `e` also absorbs [ProofContext], so the proof is valid for the single session
+//!    and prover only.
 
 use curv::arithmetic::traits::*;
 use curv::cryptographic_primitives::hashing::{Digest, DigestExt};
@@ -16,12 +18,16 @@ use curv::BigInt;
 use sha2::Sha256;
 
 use paillier::{EncryptionKey, Randomness};
+use rand::{CryptoRng, RngCore};
 use zk_paillier::zkproofs::DLogStatement;
 
 use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use zeroize::Zeroize;
 
+use crate::common::transcript::ProofContext;
+use crate::utilities::sampling::{sample_below, sample_unit};
+
 /// Represents the first round of the interactive version of the proof
 #[derive(Zeroize)]
 #[zeroize(drop)]
@@ -36,7 +42,8 @@ struct AliceZkpRound1 {
 }
 
 impl AliceZkpRound1 {
-    fn from(
+    fn from<R: RngCore + CryptoRng>(
+        rng: &mut R,
         alice_ek: &EncryptionKey,
         dlog_statement: &DLogStatement,
         a: &BigInt,
@@ -45,10 +52,10 @@ impl AliceZkpRound1 {
         let h1 = &dlog_statement.g;
         let h2 = &dlog_statement.ni;
         let N_tilde = &dlog_statement.N;
-        let alpha = BigInt::sample_below(&q.pow(3));
-        let beta = BigInt::from_paillier_key(alice_ek);
-        let gamma = BigInt::sample_below(&(q.pow(3) * N_tilde));
-        let ro = BigInt::sample_below(&(q * N_tilde));
+        let alpha = sample_below(rng, &q.pow(3));
+        let beta = sample_unit(rng, &alice_ek.n);
+        let gamma = sample_below(rng, &(q.pow(3) * N_tilde));
+        let ro = sample_below(rng, &(q * N_tilde));
         let z = (BigInt::mod_pow(h1, a, N_tilde) * BigInt::mod_pow(h2, &ro, N_tilde)) % N_tilde;
         let u = ((alpha.borrow() * &alice_ek.n + 1)
             * BigInt::mod_pow(&beta, &alice_ek.n, &alice_ek.nn))
@@ -104,6 +111,7 @@ impl AliceProof {
     /// verify Alice's proof using the proof and public keys
     pub fn verify(
         &self,
+        ctx: &ProofContext,
         cipher: &BigInt,
         alice_ek: &EncryptionKey,
         dlog_statement: &DLogStatement,
@@ -140,7 +148,8 @@ impl AliceProof {
 
         let u = (gs1 * BigInt::mod_pow(&self.s, N, NN) * cipher_e_inv) % NN;
 
-        let e = Sha256::new()
+        let e = ctx
+            .chain(Sha256::new())
             .chain_bigint(N)
             .chain_bigint(&Gen)
             .chain_bigint(cipher)
@@ -157,7 +166,9 @@ impl AliceProof {
     /// Create the proof using Alice's Paillier private keys and public ZKP setup.
     /// Requires randomness used for encrypting Alice's secret a.
     /// It is assumed that secp256k1 curve is used.
-    pub fn generate(
+    pub fn generate<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ctx: &ProofContext,
         a: &BigInt,
         cipher: &BigInt,
         alice_ek: &EncryptionKey,
@@ -165,6 +176,7 @@ impl AliceProof {
         r: &BigInt,
     ) -> Self {
         let round1 = AliceZkpRound1::from(
+            rng,
             alice_ek,
             dlog_statement,
             a,
@@ -172,7 +184,8 @@ impl AliceProof {
         );
 
         let Gen = alice_ek.n.borrow() + 1;
-        let e = Sha256::new()
+        let e = ctx
+            .chain(Sha256::new())
             .chain_bigint(&alice_ek.n)
             .chain_bigint(&Gen)
             .chain_bigint(cipher)
@@ -215,7 +228,8 @@ impl BobZkpRound1 {
     /// `b` - Bob's secret
     /// `beta_prim`  - randomly chosen in `MtA` by Bob
     /// `a_encrypted` - Alice's secret encrypted by Alice
-    fn from(
+    fn from<R: RngCore + CryptoRng>(
+        rng: &mut R,
         alice_ek: &EncryptionKey,
         dlog_statement: &DLogStatement,
         b: &Scalar<Secp256k1>,
@@ -228,13 +242,13 @@ impl BobZkpRound1 {
         let N_tilde = &dlog_statement.N;
         let b_bn = b.to_bigint();
 
-        let alpha = BigInt::sample_below(&q.pow(3));
-        let beta = BigInt::from_paillier_key(alice_ek);
-        let gamma = BigInt::sample_below(&(q.pow(2) * &alice_ek.n));
-        let ro = BigInt::sample_below(&(q * N_tilde));
-        let ro_prim = BigInt::sample_below(&(q.pow(3) * N_tilde));
-        let sigma = BigInt::sample_below(&(q * N_tilde));
-        let tau = BigInt::sample_below(&(q.pow(3) * N_tilde));
+        let alpha = sample_below(rng, &q.pow(3));
+        let beta = sample_unit(rng, &alice_ek.n);
+        let gamma = sample_below(rng, &(q.pow(2) * &alice_ek.n));
+        let ro = sample_below(rng, &(q * N_tilde));
+        let ro_prim = sample_below(rng, &(q.pow(3) * N_tilde));
+        let sigma = sample_below(rng, &(q * N_tilde));
+        let tau = sample_below(rng, &(q.pow(3) * N_tilde));
         let z = (BigInt::mod_pow(h1, &b_bn, N_tilde) * BigInt::mod_pow(h2, &ro, N_tilde)) % N_tilde;
         let z_prim = (BigInt::mod_pow(h1, &alpha, N_tilde)
             * BigInt::mod_pow(h2, &ro_prim, N_tilde))
@@ -320,6 +334,7 @@ pub struct BobProof {
 impl BobProof {
     pub fn verify(
         &self,
+        ctx: &ProofContext,
         a_enc: &BigInt,
         mta_avc_out: &BigInt,
         alice_ek: &EncryptionKey,
@@ -395,12 +410,12 @@ impl BobProof {
                 values_to_hash.push(&u_y_coor);
                 values_to_hash
                     .into_iter()
-                    .fold(Sha256::new(), |acc, b| acc.chain_bigint(b))
+                    .fold(ctx.chain(Sha256::new()), |acc, b| acc.chain_bigint(b))
                     .result_bigint()
             }
             None => values_to_hash
                 .into_iter()
-                .fold(Sha256::new(), |acc, b| acc.chain_bigint(b))
+                .fold(ctx.chain(Sha256::new()), |acc, b| acc.chain_bigint(b))
                 .result_bigint(),
         };
 
@@ -411,7 +426,9 @@ impl BobProof {
         true
     }
 
-    pub fn generate(
+    pub fn generate<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ctx: &ProofContext,
         a_encrypted: &BigInt,
         mta_encrypted: &BigInt,
         b: &Scalar<Secp256k1>,
@@ -422,6 +439,7 @@ impl BobProof {
         check: bool,
     ) -> (BobProof, Option<Point<Secp256k1>>) {
         let round1 = BobZkpRound1::from(
+            rng,
             alice_ek,
             dlog_statement,
             b,
@@ -460,12 +478,12 @@ impl BobProof {
             values_to_hash.push(&u_y_coor);
             values_to_hash
                 .into_iter()
-                .fold(Sha256::new(), |acc, b| acc.chain_bigint(b))
+                .fold(ctx.chain(Sha256::new()), |acc, b| acc.chain_bigint(b))
                 .result_bigint()
         } else {
             values_to_hash
                 .into_iter()
-                .fold(Sha256::new(), |acc, b| acc.chain_bigint(b))
+                .fold(ctx.chain(Sha256::new()), |acc, b| acc.chain_bigint(b))
                 .result_bigint()
         };
 
@@ -496,8 +514,41 @@ pub struct BobProofExt {
 
 #[allow(clippy::too_many_arguments)]
 impl BobProofExt {
+    /// Same as [BobProof::generate] with `check = true`, also proves knowledge of `b` in `g^b`
+    pub fn generate<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ctx: &ProofContext,
+        a_encrypted: &BigInt,
+        mta_encrypted: &BigInt,
+        b: &Scalar<Secp256k1>,
+        beta_prim: &BigInt,
+        alice_ek: &EncryptionKey,
+        dlog_statement: &DLogStatement,
+        r: &Randomness,
+    ) -> BobProofExt {
+        // proving a basic proof (with modified hash)
+        let (bob_proof, u) = BobProof::generate(
+            rng,
+            ctx,
+            a_encrypted,
+            mta_encrypted,
+            b,
+            beta_prim,
+            alice_ek,
+            dlog_statement,
+            r,
+            true,
+        );
+
+        BobProofExt {
+            proof: bob_proof,
+            u: u.expect("u is always returned when check is requested"),
+        }
+    }
+
     pub fn verify(
         &self,
+        ctx: &ProofContext,
         a_enc: &BigInt,
         mta_avc_out: &BigInt,
         alice_ek: &EncryptionKey,
@@ -506,6 +557,7 @@ impl BobProofExt {
     ) -> bool {
         // check basic proof first
         if !self.proof.verify(
+            ctx,
             a_enc,
             mta_avc_out,
             alice_ek,
@@ -561,33 +613,7 @@ pub(crate) mod tests {
     use super::*;
     use paillier::traits::{Encrypt, EncryptWithChosenRandomness, KeyGeneration};
     use paillier::{Add, DecryptionKey, Mul, Paillier, RawCiphertext, RawPlaintext};
-
-    fn generate(
-        a_encrypted: &BigInt,
-        mta_encrypted: &BigInt,
-        b: &Scalar<Secp256k1>,
-        beta_prim: &BigInt,
-        alice_ek: &EncryptionKey,
-        dlog_statement: &DLogStatement,
-        r: &Randomness,
-    ) -> BobProofExt {
-        // proving a basic proof (with modified hash)
-        let (bob_proof, u) = BobProof::generate(
-            a_encrypted,
-            mta_encrypted,
-            b,
-            beta_prim,
-            alice_ek,
-            dlog_statement,
-            r,
-            true,
-        );
-
-        BobProofExt {
-            proof: bob_proof,
-            u: u.unwrap(),
-        }
-    }
+    use rand::rngs::OsRng;
 
     pub(crate) fn generate_init() -> (DLogStatement, EncryptionKey, DecryptionKey) {
         let (ek_tilde, dk_tilde) = Paillier::keypair().keys();
@@ -628,14 +654,25 @@ pub(crate) mod tests {
         .clone()
         .into_owned();
 
-        let alice_proof = AliceProof::generate(&a, &cipher, &ek, &dlog_statement, &r);
+        let ctx = ProofContext::new([1; 32], 1);
+        let alice_proof =
+            AliceProof::generate(&mut OsRng, &ctx, &a, &cipher, &ek, &dlog_statement, &r);
 
-        assert!(alice_proof.verify(&cipher, &ek, &dlog_statement));
+        assert!(alice_proof.verify(&ctx, &cipher, &ek, &dlog_statement));
+        // proof doesn't verify in another session or on behalf of another party
+        assert!(!alice_proof.verify(
+            &ProofContext::new([2; 32], 1),
+            &cipher,
+            &ek,
+            &dlog_statement
+        ));
+        assert!(!alice_proof.verify(&ctx.of_party(2), &cipher, &ek, &dlog_statement));
     }
 
     #[test]
     fn bob_zkp() {
         let (dlog_statement, ek, _) = generate_init();
+        let ctx = ProofContext::new([1; 32], 2);
 
         (0..5).for_each(|_| {
             let alice_public_key = &ek;
@@ -668,6 +705,8 @@ pub(crate) mod tests {
                 let mta_out = Paillier::add(alice_public_key, b_times_enc_a, enc_beta_prim);
 
                 let (bob_proof, _) = BobProof::generate(
+                    &mut OsRng,
+                    &ctx,
                     &encrypted_a,
                     &mta_out.0.clone().into_owned(),
                     &b,
@@ -678,6 +717,15 @@ pub(crate) mod tests {
                     false,
                 );
                 assert!(bob_proof.verify(
+                    &ctx,
+                    &encrypted_a,
+                    &mta_out.0.clone().into_owned(),
+                    alice_public_key,
+                    &dlog_statement,
+                    None
+                ));
+                assert!(!bob_proof.verify(
+                    &ProofContext::new([2; 32], 2),
                     &encrypted_a,
                     &mta_out.0.clone().into_owned(),
                     alice_public_key,
@@ -688,7 +736,9 @@ pub(crate) mod tests {
                 // Bob follows MtAwc
                 let ec_gen = Point::generator();
                 let X = ec_gen * &b;
-                let bob_proof = generate(
+                let bob_proof = BobProofExt::generate(
+                    &mut OsRng,
+                    &ctx,
                     &encrypted_a,
                     &mta_out.0.clone().into_owned(),
                     &b,
@@ -698,12 +748,22 @@ pub(crate) mod tests {
                     &r,
                 );
                 assert!(bob_proof.verify(
+                    &ctx,
                     &encrypted_a,
                     &mta_out.0.clone().into_owned(),
                     alice_public_key,
                     &dlog_statement,
                     &X
                 ));
+                // proof doesn't verify against another public point
+                assert!(!bob_proof.verify(
+                    &ctx,
+                    &encrypted_a,
+                    &mta_out.0.clone().into_owned(),
+                    alice_public_key,
+                    &dlog_statement,
+                    &(&X + &ec_gen.to_point())
+                ));
             });
         });
     }
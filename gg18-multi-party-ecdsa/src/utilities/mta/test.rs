@@ -1,19 +1,147 @@
+use crate::common::transcript::ProofContext;
 use crate::utilities::mta::range_proofs::tests::generate_init;
 use crate::utilities::mta::{MessageA, MessageB};
-use curv::elliptic::curves::{secp256_k1::Secp256k1, Scalar};
+use crate::utilities::paillier_crt::PrecomputedDecryptionKey;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use rand::rngs::OsRng;
 
 #[test]
 fn test_mta() {
     let alice_input = Scalar::<Secp256k1>::random();
     let (dlog_statement, ek_alice, dk_alice) = generate_init();
+    let dk_alice = PrecomputedDecryptionKey::new(dk_alice).unwrap();
     let bob_input = Scalar::<Secp256k1>::random();
-    let (m_a, _) = MessageA::a(&alice_input, &ek_alice, &[dlog_statement.clone()]);
-    let (m_b, beta, _, _) = MessageB::b(&bob_input, &ek_alice, m_a, &[dlog_statement]).unwrap();
+    let alice_ctx = ProofContext::new([1; 32], 1);
+    let bob_ctx = alice_ctx.of_party(2);
+    let (m_a, _) = MessageA::a(
+        &mut OsRng,
+        &alice_ctx,
+        &alice_input,
+        &ek_alice,
+        &[dlog_statement.clone()],
+    );
+    let (m_b, beta, _, _) = MessageB::b(
+        &mut OsRng,
+        &alice_ctx,
+        &bob_ctx,
+        &bob_input,
+        &ek_alice,
+        m_a.clone(),
+        &[dlog_statement.clone()],
+    )
+    .unwrap();
     let alpha = m_b
-        .verify_proofs_get_alpha(&dk_alice, &alice_input)
+        .verify_proofs_get_alpha(&bob_ctx, &dk_alice, &alice_input)
         .expect("wrong dlog or m_b");
+    // Bob's proofs don't verify on behalf of Alice, Alice's proofs don't verify in another session
+    assert!(m_b
+        .verify_proofs_get_alpha(&alice_ctx, &dk_alice, &alice_input)
+        .is_err());
+    let other_session = ProofContext::new([2; 32], 1);
+    assert!(MessageB::b(
+        &mut OsRng,
+        &other_session,
+        &bob_ctx,
+        &bob_input,
+        &ek_alice,
+        m_a,
+        &[dlog_statement]
+    )
+    .is_err());
 
     let left = alpha.0 + beta;
     let right = alice_input * bob_input;
     assert_eq!(left, right);
 }
+
+#[test]
+fn test_mta_with_range_proof() {
+    let alice_input = Scalar::<Secp256k1>::random();
+    let (dlog_statement, ek_alice, dk_alice) = generate_init();
+    let dk_alice = PrecomputedDecryptionKey::new(dk_alice).unwrap();
+    let bob_input = Scalar::<Secp256k1>::random();
+    let g_b = Point::generator() * &bob_input;
+    let alice_ctx = ProofContext::new([1; 32], 1);
+    let bob_ctx = alice_ctx.of_party(2);
+    let (m_a, _) = MessageA::a(&mut OsRng, &alice_ctx, &alice_input, &ek_alice, &[]);
+    let verified_m_a = m_a.clone().verify(&alice_ctx, &ek_alice, &[]).unwrap();
+
+    // MtA
+    let (m_b, beta) = MessageB::b_with_range_proof(
+        &mut OsRng,
+        &bob_ctx,
+        &bob_input,
+        &ek_alice,
+        &verified_m_a,
+        &dlog_statement,
+        false,
+    )
+    .unwrap();
+    m_b.verify_range_proof(&bob_ctx, &m_a.c, &ek_alice, &dlog_statement, None)
+        .expect("plain range proof");
+    // Alice expecting MtAwc doesn't accept a plain proof
+    assert!(m_b
+        .verify_range_proof(&bob_ctx, &m_a.c, &ek_alice, &dlog_statement, Some(&g_b))
+        .is_err());
+    let alpha = m_b
+        .verify_proofs_get_alpha(&bob_ctx, &dk_alice, &alice_input)
+        .expect("wrong dlog or m_b");
+    assert_eq!(alpha.0 + beta, &alice_input * &bob_input);
+
+    // MtAwc
+    let (m_b, beta) = MessageB::b_with_range_proof(
+        &mut OsRng,
+        &bob_ctx,
+        &bob_input,
+        &ek_alice,
+        &verified_m_a,
+        &dlog_statement,
+        true,
+    )
+    .unwrap();
+    m_b.verify_range_proof(&bob_ctx, &m_a.c, &ek_alice, &dlog_statement, Some(&g_b))
+        .expect("range proof with check");
+    // proof is bound to Bob's share and to Bob
+    let other_g_b = Point::generator() * Scalar::<Secp256k1>::random();
+    assert!(m_b
+        .verify_range_proof(&bob_ctx, &m_a.c, &ek_alice, &dlog_statement, Some(&other_g_b))
+        .is_err());
+    assert!(m_b
+        .verify_range_proof(&alice_ctx, &m_a.c, &ek_alice, &dlog_statement, Some(&g_b))
+        .is_err());
+    let alpha = m_b
+        .verify_proofs_get_alpha(&bob_ctx, &dk_alice, &alice_input)
+        .expect("wrong dlog or m_b");
+    assert_eq!(alpha.0 + beta, alice_input * bob_input);
+}
+
+#[test]
+fn test_mta_with_own_key_encryption() {
+    let alice_input = Scalar::<Secp256k1>::random();
+    let (dlog_statement, ek_alice, dk_alice) = generate_init();
+    let dk_alice = PrecomputedDecryptionKey::new(dk_alice).unwrap();
+    let bob_input = Scalar::<Secp256k1>::random();
+    let alice_ctx = ProofContext::new([1; 32], 1);
+    let bob_ctx = alice_ctx.of_party(2);
+    let (m_a, _) = MessageA::a_with_own_key(
+        &mut OsRng,
+        &alice_ctx,
+        &alice_input,
+        &dk_alice,
+        &[dlog_statement.clone()],
+    );
+    let (m_b, beta, _, _) = MessageB::b(
+        &mut OsRng,
+        &alice_ctx,
+        &bob_ctx,
+        &bob_input,
+        &ek_alice,
+        m_a,
+        &[dlog_statement],
+    )
+    .unwrap();
+    let alpha = m_b
+        .verify_proofs_get_alpha(&bob_ctx, &dk_alice, &alice_input)
+        .expect("wrong dlog or m_b");
+    assert_eq!(alpha.0 + beta, alice_input * bob_input);
+}
@@ -0,0 +1,313 @@
+//! Paillier operations that use factorization of our own modulus
+//!
+//! `Paillier::decrypt` recomputes `p^2`, `q^2`, `h_p`, `h_q` and `p^-1 mod q` on every call.
+//! [PrecomputedDecryptionKey] computes them once when the key is created or loaded, and
+//! serializes as a plain [DecryptionKey], so stored keys keep their format. Loading a key whose
+//! primes aren't coprime fails with [InvalidDecryptionKey].
+//!
+//! It also speeds up encryption under our own `ek`: base `g = N + 1` is fixed, so `g^m` is
+//! `1 + mN`, and `r^N mod N^2` is computed modulo `p^2` and `q^2` with exponents reduced by
+//! `phi(p^2)`, `phi(q^2)`. Signing encrypts under own `ek` only once per run, in
+//! [MessageA::a_with_own_key](crate::utilities::mta::MessageA::a_with_own_key).
+//!
+//! Bob's homomorphic operations in [MessageB](crate::utilities::mta::MessageB) are done under
+//! the counterparty's `ek`, whose factorization we don't know, so nothing here applies to them:
+//! they still go through `paillier` crate.
+//!
+//! [PrecomputedDecryptionKey::without_crt] makes a key that falls back to `paillier` crate, so
+//! that benchmarks can run signing both ways.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Deref;
+
+use curv::arithmetic::traits::*;
+use curv::BigInt;
+use paillier::traits::EncryptWithChosenRandomness;
+use paillier::{
+    Decrypt, DecryptionKey, EncryptionKey, Paillier, Randomness, RawCiphertext, RawPlaintext,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+/// Paillier decryption key with CRT parameters computed in advance
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "DecryptionKey", into = "DecryptionKey")]
+pub struct PrecomputedDecryptionKey {
+    dk: DecryptionKey,
+    ek: EncryptionKey,
+    /// `None` if operations fall back to `paillier` crate
+    crt: Option<CrtParameters>,
+}
+
+#[derive(Clone)]
+struct CrtParameters {
+    pp: BigInt,
+    qq: BigInt,
+    p_minus_1: BigInt,
+    q_minus_1: BigInt,
+    /// `L_p(g^(p-1) mod p^2)^-1 mod p`
+    hp: BigInt,
+    /// `L_q(g^(q-1) mod q^2)^-1 mod q`
+    hq: BigInt,
+    /// `p^-1 mod q`
+    p_inv_q: BigInt,
+    /// `N mod phi(p^2)`
+    n_mod_phi_pp: BigInt,
+    /// `N mod phi(q^2)`
+    n_mod_phi_qq: BigInt,
+    /// `p^-2 mod q^2`
+    pp_inv_qq: BigInt,
+}
+
+impl PrecomputedDecryptionKey {
+    /// Returns error if `p` and `q` aren't distinct primes, as far as CRT parameters can tell
+    pub fn new(dk: DecryptionKey) -> Result<Self, InvalidDecryptionKey> {
+        let one = BigInt::one();
+        let (p, q) = (&dk.p, &dk.q);
+        if p <= &one || q <= &one {
+            return Err(InvalidDecryptionKey);
+        }
+        let n = p * q;
+        let g = &n + &one;
+        let pp = p * p;
+        let qq = q * q;
+        let p_minus_1 = p - &one;
+        let q_minus_1 = q - &one;
+
+        let hp = BigInt::mod_inv(&l(&BigInt::mod_pow(&g, &p_minus_1, &pp), p), p)
+            .ok_or(InvalidDecryptionKey)?;
+        let hq = BigInt::mod_inv(&l(&BigInt::mod_pow(&g, &q_minus_1, &qq), q), q)
+            .ok_or(InvalidDecryptionKey)?;
+        let p_inv_q = BigInt::mod_inv(p, q).ok_or(InvalidDecryptionKey)?;
+        let n_mod_phi_pp = n.mod_floor(&(p * &p_minus_1));
+        let n_mod_phi_qq = n.mod_floor(&(q * &q_minus_1));
+        let pp_inv_qq = BigInt::mod_inv(&pp, &qq).ok_or(InvalidDecryptionKey)?;
+
+        let ek = EncryptionKey {
+            nn: &n * &n,
+            n,
+        };
+        Ok(Self {
+            dk,
+            ek,
+            crt: Some(CrtParameters {
+                pp,
+                qq,
+                p_minus_1,
+                q_minus_1,
+                hp,
+                hq,
+                p_inv_q,
+                n_mod_phi_pp,
+                n_mod_phi_qq,
+                pp_inv_qq,
+            }),
+        })
+    }
+
+    /// Key that doesn't precompute anything, its operations are done by `paillier` crate
+    ///
+    /// Meant for benchmarks comparing both ways. Deserialized key always has CRT parameters.
+    pub fn without_crt(dk: DecryptionKey) -> Self {
+        let n = &dk.p * &dk.q;
+        let ek = EncryptionKey {
+            nn: &n * &n,
+            n,
+        };
+        Self { dk, ek, crt: None }
+    }
+
+    /// Encryption key matching this decryption key
+    pub fn ek(&self) -> &EncryptionKey {
+        &self.ek
+    }
+
+    /// Decrypts `c`, which must be in `Z*_{N^2}`
+    pub fn decrypt(&self, c: &BigInt) -> BigInt {
+        let crt = match &self.crt {
+            Some(crt) => crt,
+            None => {
+                let m: RawPlaintext = Paillier::decrypt(&self.dk, &RawCiphertext::from(c));
+                return m.0.into_owned();
+            }
+        };
+        let (p, q) = (&self.dk.p, &self.dk.q);
+        let cp = BigInt::mod_pow(&c.mod_floor(&crt.pp), &crt.p_minus_1, &crt.pp);
+        let mp = BigInt::mod_mul(&l(&cp, p), &crt.hp, p);
+        let cq = BigInt::mod_pow(&c.mod_floor(&crt.qq), &crt.q_minus_1, &crt.qq);
+        let mq = BigInt::mod_mul(&l(&cq, q), &crt.hq, q);
+
+        // m = mp + p * ((mq - mp) * p^-1 mod q)
+        let h = BigInt::mod_mul(&BigInt::mod_sub(&mq, &mp, q), &crt.p_inv_q, q);
+        mp + p * h
+    }
+
+    /// Encrypts `m` under [our own key](Self::ek) with randomness `r` from `Z*_N`
+    ///
+    /// Gives the same ciphertext as `Paillier::encrypt_with_chosen_randomness`.
+    pub fn encrypt_with_chosen_randomness(&self, m: &BigInt, r: &BigInt) -> BigInt {
+        let crt = match &self.crt {
+            Some(crt) => crt,
+            None => {
+                let c = Paillier::encrypt_with_chosen_randomness(
+                    &self.ek,
+                    RawPlaintext::from(m),
+                    &Randomness::from(r.clone()),
+                );
+                return c.0.into_owned();
+            }
+        };
+        let nn = &self.ek.nn;
+        let rp = BigInt::mod_pow(&r.mod_floor(&crt.pp), &crt.n_mod_phi_pp, &crt.pp);
+        let rq = BigInt::mod_pow(&r.mod_floor(&crt.qq), &crt.n_mod_phi_qq, &crt.qq);
+        // r^N mod N^2 = rp + p^2 * ((rq - rp) * p^-2 mod q^2)
+        let h = BigInt::mod_mul(&BigInt::mod_sub(&rq, &rp, &crt.qq), &crt.pp_inv_qq, &crt.qq);
+        let r_n = rp + &crt.pp * h;
+
+        let g_m = (BigInt::one() + m * &self.ek.n).mod_floor(nn);
+        BigInt::mod_mul(&g_m, &r_n, nn)
+    }
+}
+
+/// `L(x) = (x - 1) / p`
+fn l(x: &BigInt, p: &BigInt) -> BigInt {
+    (x - BigInt::one()) / p
+}
+
+impl Deref for PrecomputedDecryptionKey {
+    type Target = DecryptionKey;
+
+    fn deref(&self) -> &DecryptionKey {
+        &self.dk
+    }
+}
+
+impl TryFrom<DecryptionKey> for PrecomputedDecryptionKey {
+    type Error = InvalidDecryptionKey;
+
+    fn try_from(dk: DecryptionKey) -> Result<Self, InvalidDecryptionKey> {
+        Self::new(dk)
+    }
+}
+
+impl From<PrecomputedDecryptionKey> for DecryptionKey {
+    fn from(dk: PrecomputedDecryptionKey) -> Self {
+        dk.dk.clone()
+    }
+}
+
+impl fmt::Debug for PrecomputedDecryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("PrecomputedDecryptionKey([redacted])")
+    }
+}
+
+impl Drop for PrecomputedDecryptionKey {
+    fn drop(&mut self) {
+        self.dk.p.zeroize();
+        self.dk.q.zeroize();
+    }
+}
+
+/// Paillier primes of the key aren't distinct primes, so CRT parameters can't be computed
+#[derive(Debug, Error)]
+#[error("paillier decryption key is malformed")]
+pub struct InvalidDecryptionKey;
+
+impl Drop for CrtParameters {
+    fn drop(&mut self) {
+        self.pp.zeroize();
+        self.qq.zeroize();
+        self.p_minus_1.zeroize();
+        self.q_minus_1.zeroize();
+        self.hp.zeroize();
+        self.hq.zeroize();
+        self.p_inv_q.zeroize();
+        self.n_mod_phi_pp.zeroize();
+        self.n_mod_phi_qq.zeroize();
+        self.pp_inv_qq.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::OsRng;
+
+    use super::*;
+    use crate::utilities::sampling::{paillier_keypair, sample_below, sample_unit};
+
+    #[test]
+    fn matches_paillier_crate() {
+        let (ek, dk) = paillier_keypair(&mut OsRng, 1024);
+        let precomputed = PrecomputedDecryptionKey::new(dk.clone()).unwrap();
+        assert_eq!(precomputed.ek().n, ek.n);
+
+        for _ in 0..10 {
+            let m = sample_below(&mut OsRng, &ek.n);
+            let r = sample_unit(&mut OsRng, &ek.n);
+            let c = Paillier::encrypt_with_chosen_randomness(
+                &ek,
+                RawPlaintext::from(m.clone()),
+                &Randomness::from(r.clone()),
+            )
+            .0
+            .into_owned();
+
+            assert_eq!(precomputed.encrypt_with_chosen_randomness(&m, &r), c);
+            assert_eq!(precomputed.decrypt(&c), m);
+            let expected: RawPlaintext = Paillier::decrypt(&dk, &RawCiphertext::from(c.clone()));
+            assert_eq!(precomputed.decrypt(&c), expected.0.into_owned());
+        }
+    }
+
+    #[test]
+    fn serializes_as_decryption_key() {
+        let (_, dk) = paillier_keypair(&mut OsRng, 1024);
+        let precomputed = PrecomputedDecryptionKey::new(dk.clone()).unwrap();
+        let json = serde_json::to_string(&precomputed).unwrap();
+        assert_eq!(json, serde_json::to_string(&dk).unwrap());
+
+        let restored: PrecomputedDecryptionKey = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.p, dk.p);
+        assert_eq!(
+            restored.crt.as_ref().unwrap().pp_inv_qq,
+            precomputed.crt.as_ref().unwrap().pp_inv_qq
+        );
+    }
+
+    #[test]
+    fn malformed_key_is_rejected() {
+        let (_, dk) = paillier_keypair(&mut OsRng, 1024);
+        let same_primes = DecryptionKey {
+            p: dk.p.clone(),
+            q: dk.p.clone(),
+        };
+        let json = serde_json::to_string(&same_primes).unwrap();
+        let result = serde_json::from_str::<PrecomputedDecryptionKey>(&json);
+        assert!(result.is_err());
+
+        let zero = DecryptionKey {
+            p: BigInt::zero(),
+            q: dk.q.clone(),
+        };
+        let json = serde_json::to_string(&zero).unwrap();
+        let result = serde_json::from_str::<PrecomputedDecryptionKey>(&json);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn key_without_crt_matches_precomputed_one() {
+        let (_, dk) = paillier_keypair(&mut OsRng, 1024);
+        let precomputed = PrecomputedDecryptionKey::new(dk.clone()).unwrap();
+        let plain = PrecomputedDecryptionKey::without_crt(dk);
+        assert_eq!(plain.ek().nn, precomputed.ek().nn);
+
+        let m = sample_below(&mut OsRng, &plain.ek().n);
+        let r = sample_unit(&mut OsRng, &plain.ek().n);
+        let c = plain.encrypt_with_chosen_randomness(&m, &r);
+        assert_eq!(c, precomputed.encrypt_with_chosen_randomness(&m, &r));
+        assert_eq!(plain.decrypt(&c), m);
+    }
+}
@@ -0,0 +1,187 @@
+//! Sampling from an injected random number generator
+//!
+//! Protocol code doesn't use global randomness: every party takes `R: RngCore + CryptoRng`, so
+//! a keygen or signing run can be reproduced from a recorded seed. Functions below replace
+//! `Scalar::random`, `BigInt::sample*` and `Paillier::keypair*`, which use thread-local rng.
+
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Scalar};
+use curv::BigInt;
+use paillier::{DecryptionKey, EncryptionKey, Keypair};
+use rand::{CryptoRng, RngCore};
+
+/// Bit length of Paillier modulus
+pub const PAILLIER_KEY_SIZE: usize = 2048;
+/// Number of Miller-Rabin rounds in primality tests
+const PRIMALITY_TEST_ROUNDS: u32 = 40;
+/// Odd primes used to sieve out candidates before Miller-Rabin test
+const SMALL_PRIMES: [u32; 45] = [
+    3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199,
+];
+
+/// `RngCore + CryptoRng` as a single object-safe trait, so state machines can own an injected rng
+pub trait CryptoRngCore: RngCore + CryptoRng {}
+
+impl<R: RngCore + CryptoRng> CryptoRngCore for R {}
+
+/// Rng owned by a state machine
+pub type BoxedRng = Box<dyn CryptoRngCore + Send>;
+
+/// Uniform in `[0; 2^bit_length)`
+pub fn sample_bits<R: RngCore + CryptoRng>(rng: &mut R, bit_length: usize) -> BigInt {
+    let mut bytes = vec![0u8; (bit_length + 7) / 8];
+    rng.fill_bytes(&mut bytes);
+    if let Some(first) = bytes.first_mut() {
+        *first &= 0xff >> (bytes.len() * 8 - bit_length);
+    }
+    BigInt::from_bytes(&bytes)
+}
+
+/// Uniform in `[0; upper)`, `upper` must be positive
+pub fn sample_below<R: RngCore + CryptoRng>(rng: &mut R, upper: &BigInt) -> BigInt {
+    assert!(*upper > BigInt::zero(), "upper bound must be positive");
+    let bit_length = upper.bit_length();
+    loop {
+        let n = sample_bits(rng, bit_length);
+        if n < *upper {
+            return n;
+        }
+    }
+}
+
+/// Uniform in `[lower; upper)`
+pub fn sample_range<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    lower: &BigInt,
+    upper: &BigInt,
+) -> BigInt {
+    lower + sample_below(rng, &(upper - lower))
+}
+
+/// Uniform non-zero scalar
+pub fn sample_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Scalar<Secp256k1> {
+    let q = Scalar::<Secp256k1>::group_order();
+    Scalar::from(&sample_range(rng, &BigInt::one(), q))
+}
+
+/// Uniform in `Z*_n`, e.g. Paillier encryption randomness
+pub fn sample_unit<R: RngCore + CryptoRng>(rng: &mut R, n: &BigInt) -> BigInt {
+    loop {
+        let r = sample_range(rng, &BigInt::one(), n);
+        if r.gcd(n) == BigInt::one() {
+            return r;
+        }
+    }
+}
+
+/// Random prime of exactly `bit_length` bits with two top bits set
+pub fn sample_prime<R: RngCore + CryptoRng>(rng: &mut R, bit_length: usize) -> BigInt {
+    loop {
+        let candidate = sample_odd_with_top_bits(rng, bit_length);
+        if !has_small_factor(&candidate) && candidate.is_probable_prime(PRIMALITY_TEST_ROUNDS) {
+            return candidate;
+        }
+    }
+}
+
+/// Random safe prime `p = 2q + 1` of exactly `bit_length` bits with two top bits set
+pub fn sample_safe_prime<R: RngCore + CryptoRng>(rng: &mut R, bit_length: usize) -> BigInt {
+    loop {
+        let q = sample_odd_with_top_bits(rng, bit_length - 1);
+        let p = BigInt::from(2) * &q + BigInt::one();
+        if !has_small_factor(&q)
+            && !has_small_factor(&p)
+            && q.is_probable_prime(PRIMALITY_TEST_ROUNDS)
+            && p.is_probable_prime(PRIMALITY_TEST_ROUNDS)
+        {
+            return p;
+        }
+    }
+}
+
+/// Paillier key pair with modulus of `modulus_bit_length` bits
+pub fn paillier_keypair<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    modulus_bit_length: usize,
+) -> (EncryptionKey, DecryptionKey) {
+    loop {
+        let p = sample_prime(rng, modulus_bit_length / 2);
+        let q = sample_prime(rng, modulus_bit_length / 2);
+        if p != q {
+            return Keypair { p, q }.keys();
+        }
+    }
+}
+
+/// Same as [paillier_keypair], but primes are safe primes
+pub fn paillier_keypair_safe_primes<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    modulus_bit_length: usize,
+) -> Keypair {
+    loop {
+        let p = sample_safe_prime(rng, modulus_bit_length / 2);
+        let q = sample_safe_prime(rng, modulus_bit_length / 2);
+        if p != q {
+            return Keypair { p, q };
+        }
+    }
+}
+
+/// Odd number of exactly `bit_length` bits with two top bits set, so that product of two such
+/// numbers has exactly `2 * bit_length` bits
+fn sample_odd_with_top_bits<R: RngCore + CryptoRng>(rng: &mut R, bit_length: usize) -> BigInt {
+    let top_bits = BigInt::from(3) * BigInt::from(2).pow(bit_length as u32 - 2);
+    let n = top_bits + sample_bits(rng, bit_length - 2);
+    if n.test_bit(0) {
+        n
+    } else {
+        n + BigInt::one()
+    }
+}
+
+fn has_small_factor(n: &BigInt) -> bool {
+    SMALL_PRIMES.iter().any(|&p| {
+        let p = BigInt::from(p);
+        *n != p && n.mod_floor(&p) == BigInt::zero()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_samples() {
+        let sample = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (
+                sample_scalar(&mut rng),
+                sample_below(&mut rng, &BigInt::from(1_000_000)),
+                sample_prime(&mut rng, 256),
+            )
+        };
+        assert_eq!(sample(1), sample(1));
+        assert_ne!(sample(1), sample(2));
+    }
+
+    #[test]
+    fn samples_are_in_range() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let upper = BigInt::from(1000);
+        for _ in 0..100 {
+            let n = sample_below(&mut rng, &upper);
+            assert!(n >= BigInt::zero() && n < upper);
+            assert!(sample_bits(&mut rng, 5) < BigInt::from(32));
+        }
+
+        let p = sample_safe_prime(&mut rng, 128);
+        assert_eq!(p.bit_length(), 128);
+        assert!(p.is_probable_prime(PRIMALITY_TEST_ROUNDS));
+        assert!(((p - BigInt::one()) / BigInt::from(2)).is_probable_prime(PRIMALITY_TEST_ROUNDS));
+    }
+}
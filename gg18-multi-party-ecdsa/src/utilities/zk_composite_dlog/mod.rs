@@ -0,0 +1,94 @@
+#![allow(non_snake_case)]
+
+//! Proof of knowledge of discrete log in a group of unknown order
+//!
+//! Statement: `(N, g, ni)` (represented as [DLogStatement]), witness: `secret` such that
+//! `ni = g^secret mod N`. Used to prove that `h1` and `h2` of `N_tilde` setup generate the
+//! same group. It's the same proof as `CompositeDLogProof` from zk-paillier (which proves
+//! `ni = g^-secret`), but the challenge also absorbs [ProofContext], so the proof is valid for
+//! the single session and prover only.
+
+use curv::arithmetic::traits::*;
+use curv::cryptographic_primitives::hashing::{Digest, DigestExt};
+use curv::BigInt;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+use zk_paillier::zkproofs::DLogStatement;
+
+use crate::common::transcript::ProofContext;
+use crate::utilities::sampling::sample_bits;
+
+/// Bit length of the challenge
+const CHALLENGE_BITS: u32 = 256;
+/// Statistical security parameter: `r` hides `e * secret` with `2^-STATISTICAL_SECURITY` distance
+const STATISTICAL_SECURITY: u32 = 128;
+
+#[derive(Error, Debug)]
+pub enum ZkCompositeDLogError {
+    #[error("zk composite dlog verification failed")]
+    Verify,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CompositeDLogProof {
+    pub x: BigInt,
+    pub y: BigInt,
+}
+
+impl CompositeDLogProof {
+    /// Proves knowledge of `secret` such that `ni = g^secret mod N`, where `secret < N`
+    pub fn prove<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ctx: &ProofContext,
+        statement: &DLogStatement,
+        secret: &BigInt,
+    ) -> Self {
+        let r_bits = statement.N.bit_length() + (CHALLENGE_BITS + STATISTICAL_SECURITY) as usize;
+        let r = sample_bits(rng, r_bits);
+        let x = BigInt::mod_pow(&statement.g, &r, &statement.N);
+        let e = challenge(ctx, statement, &x);
+        let y = r + e * secret;
+        Self { x, y }
+    }
+
+    pub fn verify(
+        &self,
+        ctx: &ProofContext,
+        statement: &DLogStatement,
+    ) -> Result<(), ZkCompositeDLogError> {
+        let N = &statement.N;
+        let one = BigInt::one();
+        let in_group = |v: &BigInt| v > &BigInt::zero() && v < N && v.gcd(N) == one;
+        if N <= &one
+            || !in_group(&statement.g)
+            || !in_group(&statement.ni)
+            || !in_group(&self.x)
+            || self.y < BigInt::zero()
+        {
+            return Err(ZkCompositeDLogError::Verify);
+        }
+
+        let e = challenge(ctx, statement, &self.x);
+        let g_y = BigInt::mod_pow(&statement.g, &self.y, N);
+        let x_ni_e = BigInt::mod_mul(&self.x, &BigInt::mod_pow(&statement.ni, &e, N), N);
+        if g_y == x_ni_e {
+            Ok(())
+        } else {
+            Err(ZkCompositeDLogError::Verify)
+        }
+    }
+}
+
+fn challenge(ctx: &ProofContext, statement: &DLogStatement, x: &BigInt) -> BigInt {
+    ctx.chain(Sha256::new())
+        .chain_bigint(&statement.N)
+        .chain_bigint(&statement.g)
+        .chain_bigint(&statement.ni)
+        .chain_bigint(x)
+        .result_bigint()
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,51 @@
+#![allow(non_snake_case)]
+
+use curv::arithmetic::traits::*;
+use curv::BigInt;
+use paillier::traits::KeyGeneration;
+use paillier::Paillier;
+use rand::rngs::OsRng;
+use zk_paillier::zkproofs::DLogStatement;
+
+use crate::common::transcript::ProofContext;
+use crate::utilities::zk_composite_dlog::CompositeDLogProof;
+
+fn statement() -> (DLogStatement, BigInt) {
+    let (ek_tilde, dk_tilde) = Paillier::keypair().keys();
+    let one = BigInt::one();
+    let phi = (&dk_tilde.p - &one) * (&dk_tilde.q - &one);
+    let h1 = BigInt::sample_below(&phi);
+    let xhi = BigInt::sample_below(&phi);
+    let h2 = BigInt::mod_pow(&h1, &xhi, &ek_tilde.n);
+    let statement = DLogStatement {
+        N: ek_tilde.n,
+        g: h1,
+        ni: h2,
+    };
+    (statement, xhi)
+}
+
+#[test]
+fn test_zk_composite_dlog() {
+    let ctx = ProofContext::new([7; 32], 2);
+    let (statement, xhi) = statement();
+    let proof = CompositeDLogProof::prove(&mut OsRng, &ctx, &statement, &xhi);
+    assert!(proof.verify(&ctx, &statement).is_ok());
+}
+
+#[test]
+fn test_zk_composite_dlog_soundness() {
+    let ctx = ProofContext::new([7; 32], 2);
+    let (statement, xhi) = statement();
+    let proof = CompositeDLogProof::prove(&mut OsRng, &ctx, &statement, &(xhi + BigInt::one()));
+    assert!(proof.verify(&ctx, &statement).is_err());
+}
+
+#[test]
+fn test_zk_composite_dlog_is_bound_to_context() {
+    let ctx = ProofContext::new([7; 32], 2);
+    let (statement, xhi) = statement();
+    let proof = CompositeDLogProof::prove(&mut OsRng, &ctx, &statement, &xhi);
+    assert!(proof.verify(&ProofContext::new([8; 32], 2), &statement).is_err());
+    assert!(proof.verify(&ctx.of_party(1), &statement).is_err());
+}
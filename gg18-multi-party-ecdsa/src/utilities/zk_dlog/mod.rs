@@ -0,0 +1,82 @@
+//! Proof of knowledge of discrete log (Schnorr)
+//!
+//! Statement: `pk`, witness: `sk` such that `pk = sk * G`. Same proof as `sigma_dlog` from curv,
+//! but the challenge also absorbs [ProofContext], so the proof is valid for the single session
+//! and prover only.
+
+use curv::cryptographic_primitives::hashing::{Digest, DigestExt};
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::common::transcript::ProofContext;
+use crate::common::validation::{validate_point, InvalidValue};
+use crate::utilities::sampling::sample_scalar;
+
+#[derive(Error, Debug)]
+pub enum ZkDLogError {
+    #[error("zk dlog verification failed")]
+    Verify,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DLogProof {
+    pub pk: Point<Secp256k1>,
+    pub pk_t_rand_commitment: Point<Secp256k1>,
+    pub challenge_response: Scalar<Secp256k1>,
+}
+
+impl DLogProof {
+    pub fn prove<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ctx: &ProofContext,
+        sk: &Scalar<Secp256k1>,
+    ) -> Self {
+        let g = Point::generator();
+        let sk_t_rand_commitment = sample_scalar(rng);
+        let pk_t_rand_commitment = g * &sk_t_rand_commitment;
+        let pk = g * sk;
+        let challenge = challenge(ctx, &pk_t_rand_commitment, &pk);
+        let challenge_response = sk_t_rand_commitment - challenge * sk;
+        Self {
+            pk,
+            pk_t_rand_commitment,
+            challenge_response,
+        }
+    }
+
+    /// Checks proof received from another party before it's used
+    pub fn validate(&self) -> Result<(), InvalidValue> {
+        validate_point(&self.pk, "pk")?;
+        validate_point(&self.pk_t_rand_commitment, "pk_t_rand_commitment")
+    }
+
+    pub fn verify(&self, ctx: &ProofContext) -> Result<(), ZkDLogError> {
+        let challenge = challenge(ctx, &self.pk_t_rand_commitment, &self.pk);
+        let pk_verifier = Point::generator() * &self.challenge_response + &self.pk * challenge;
+        if pk_verifier == self.pk_t_rand_commitment {
+            Ok(())
+        } else {
+            Err(ZkDLogError::Verify)
+        }
+    }
+}
+
+fn challenge(
+    ctx: &ProofContext,
+    pk_t_rand_commitment: &Point<Secp256k1>,
+    pk: &Point<Secp256k1>,
+) -> Scalar<Secp256k1> {
+    ctx.chain(Sha256::new())
+        .chain_points([
+            pk_t_rand_commitment,
+            &Point::generator().to_point(),
+            pk,
+        ])
+        .result_scalar()
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,22 @@
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Scalar};
+use rand::rngs::OsRng;
+
+use crate::common::transcript::ProofContext;
+use crate::utilities::zk_dlog::DLogProof;
+
+#[test]
+fn test_zk_dlog() {
+    let ctx = ProofContext::new([7; 32], 1);
+    let proof = DLogProof::prove(&mut OsRng, &ctx, &Scalar::<Secp256k1>::random());
+    assert!(proof.verify(&ctx).is_ok());
+}
+
+#[test]
+fn test_zk_dlog_is_bound_to_context() {
+    let ctx = ProofContext::new([7; 32], 1);
+    let proof = DLogProof::prove(&mut OsRng, &ctx, &Scalar::<Secp256k1>::random());
+    // replayed in another session
+    assert!(proof.verify(&ProofContext::new([8; 32], 1)).is_err());
+    // replayed on behalf of another party
+    assert!(proof.verify(&ctx.of_party(2)).is_err());
+}
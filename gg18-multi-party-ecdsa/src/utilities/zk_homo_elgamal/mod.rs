@@ -0,0 +1,93 @@
+#![allow(non_snake_case)]
+
+//! Proof of correct homomorphic ElGamal encryption
+//!
+//! Statement: `(G, H, Y, D, E)`, witness: `(x, r)` such that `D = x * H + r * Y` and
+//! `E = r * G`. Same proof as `sigma_correct_homomorphic_elgamal_enc` from curv, but the
+//! challenge also absorbs [ProofContext], so the proof is valid for the single session and
+//! prover only.
+
+use curv::cryptographic_primitives::hashing::{Digest, DigestExt};
+pub use curv::cryptographic_primitives::proofs::sigma_correct_homomorphic_elgamal_enc::{
+    HomoElGamalStatement, HomoElGamalWitness,
+};
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::common::transcript::ProofContext;
+use crate::common::validation::{validate_point, InvalidValue};
+use crate::utilities::sampling::sample_scalar;
+
+#[derive(Error, Debug)]
+pub enum ZkHomoElGamalError {
+    #[error("zk homomorphic elgamal verification failed")]
+    Verify,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HomoELGamalProof {
+    pub T: Point<Secp256k1>,
+    pub A3: Point<Secp256k1>,
+    pub z1: Scalar<Secp256k1>,
+    pub z2: Scalar<Secp256k1>,
+}
+
+impl HomoELGamalProof {
+    pub fn prove<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ctx: &ProofContext,
+        w: &HomoElGamalWitness<Secp256k1>,
+        delta: &HomoElGamalStatement<Secp256k1>,
+    ) -> Self {
+        let s1 = sample_scalar(rng);
+        let s2 = sample_scalar(rng);
+        let A1 = &delta.H * &s1;
+        let A2 = &delta.Y * &s2;
+        let A3 = &delta.G * &s2;
+        let T = A1 + A2;
+        let e = challenge(ctx, &T, &A3, delta);
+        let z1 = &s1 + &w.x * &e;
+        let z2 = s2 + &w.r * e;
+        Self { T, A3, z1, z2 }
+    }
+
+    /// Checks proof received from another party before it's used
+    pub fn validate(&self) -> Result<(), InvalidValue> {
+        validate_point(&self.T, "T")?;
+        validate_point(&self.A3, "A3")
+    }
+
+    pub fn verify(
+        &self,
+        ctx: &ProofContext,
+        delta: &HomoElGamalStatement<Secp256k1>,
+    ) -> Result<(), ZkHomoElGamalError> {
+        let e = challenge(ctx, &self.T, &self.A3, delta);
+        let z1H_plus_z2Y = &delta.H * &self.z1 + &delta.Y * &self.z2;
+        let T_plus_eD = &self.T + &delta.D * &e;
+        let z2G = &delta.G * &self.z2;
+        let A3_plus_eE = &self.A3 + &delta.E * &e;
+        if z1H_plus_z2Y == T_plus_eD && z2G == A3_plus_eE {
+            Ok(())
+        } else {
+            Err(ZkHomoElGamalError::Verify)
+        }
+    }
+}
+
+fn challenge(
+    ctx: &ProofContext,
+    T: &Point<Secp256k1>,
+    A3: &Point<Secp256k1>,
+    delta: &HomoElGamalStatement<Secp256k1>,
+) -> Scalar<Secp256k1> {
+    ctx.chain(Sha256::new())
+        .chain_points([T, A3, &delta.G, &delta.H, &delta.Y, &delta.D, &delta.E])
+        .result_scalar()
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,37 @@
+#![allow(non_snake_case)]
+
+use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
+use rand::rngs::OsRng;
+
+use crate::common::transcript::ProofContext;
+use crate::utilities::zk_homo_elgamal::*;
+
+fn statement() -> (HomoElGamalWitness<Secp256k1>, HomoElGamalStatement<Secp256k1>) {
+    let witness = HomoElGamalWitness {
+        r: Scalar::<Secp256k1>::random(),
+        x: Scalar::<Secp256k1>::random(),
+    };
+    let G = Point::generator() * Scalar::<Secp256k1>::random();
+    let H = Point::generator() * Scalar::<Secp256k1>::random();
+    let Y = Point::generator().to_point();
+    let D = &H * &witness.x + &Y * &witness.r;
+    let E = &G * &witness.r;
+    (witness, HomoElGamalStatement { G, H, Y, D, E })
+}
+
+#[test]
+fn test_zk_homo_elgamal() {
+    let ctx = ProofContext::new([7; 32], 3);
+    let (witness, delta) = statement();
+    let proof = HomoELGamalProof::prove(&mut OsRng, &ctx, &witness, &delta);
+    assert!(proof.verify(&ctx, &delta).is_ok());
+}
+
+#[test]
+fn test_zk_homo_elgamal_is_bound_to_context() {
+    let ctx = ProofContext::new([7; 32], 3);
+    let (witness, delta) = statement();
+    let proof = HomoELGamalProof::prove(&mut OsRng, &ctx, &witness, &delta);
+    assert!(proof.verify(&ProofContext::new([8; 32], 3), &delta).is_err());
+    assert!(proof.verify(&ctx.of_party(1), &delta).is_err());
+}
@@ -28,14 +28,17 @@ use curv::cryptographic_primitives::commitments::traits::Commitment;
 use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
 use curv::BigInt;
 use paillier::Paillier;
-use paillier::{Add, Decrypt, Encrypt, Mul};
-use paillier::{DecryptionKey, EncryptionKey, RawCiphertext, RawPlaintext};
+use paillier::{Add, Decrypt, EncryptWithChosenRandomness, Mul};
+use paillier::{DecryptionKey, EncryptionKey, Randomness, RawCiphertext, RawPlaintext};
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use thiserror::Error;
 use zk_paillier::zkproofs::IncorrectProof;
 use zk_paillier::zkproofs::RangeProofNi;
 
+use crate::utilities::sampling::{sample_below, sample_scalar, sample_unit};
+
 #[derive(Error, Debug)]
 pub enum ZkPdlError {
     #[error("zk pdl message2 failed")]
@@ -109,14 +112,21 @@ pub struct Prover {}
 pub struct Verifier {}
 
 impl Verifier {
-    pub fn message1(statement: &PDLStatement) -> (PDLVerifierFirstMessage, PDLVerifierState) {
-        let a_fe = Scalar::<Secp256k1>::random();
+    pub fn message1<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        statement: &PDLStatement,
+    ) -> (PDLVerifierFirstMessage, PDLVerifierState) {
+        let a_fe = sample_scalar(rng);
         let a = a_fe.to_bigint();
         let q = Scalar::<Secp256k1>::group_order();
         let q_sq = q.pow(2);
-        let b = BigInt::sample_below(&q_sq);
+        let b = sample_below(rng, &q_sq);
         let b_fe = Scalar::<Secp256k1>::from(&b);
-        let b_enc = Paillier::encrypt(&statement.ek, RawPlaintext::from(b.clone()));
+        let b_enc = Paillier::encrypt_with_chosen_randomness(
+            &statement.ek,
+            RawPlaintext::from(b.clone()),
+            &Randomness(sample_unit(rng, &statement.ek.n)),
+        );
         let ac = Paillier::mul(
             &statement.ek,
             RawCiphertext::from(statement.ciphertext.clone()),
@@ -124,7 +134,7 @@ impl Verifier {
         );
         let c_tag = Paillier::add(&statement.ek, ac, b_enc).0.into_owned();
         let ab_concat = a.clone() + b.clone().shl(a.bit_length());
-        let blindness = BigInt::sample_below(q);
+        let blindness = sample_below(rng, q);
         let c_tag_tag = HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
             &ab_concat, &blindness,
         );
@@ -188,7 +198,8 @@ impl Verifier {
 }
 
 impl Prover {
-    pub fn message1(
+    pub fn message1<R: RngCore + CryptoRng>(
+        rng: &mut R,
         witness: &PDLWitness,
         statement: &PDLStatement,
         verifier_first_message: &PDLVerifierFirstMessage,
@@ -197,7 +208,7 @@ impl Prover {
         let alpha = Paillier::decrypt(&witness.dk, &RawCiphertext::from(c_tag));
         let alpha_fe = Scalar::<Secp256k1>::from(alpha.0.as_ref());
         let q_hat = &statement.G * alpha_fe;
-        let blindness = BigInt::sample_below(Scalar::<Secp256k1>::group_order());
+        let blindness = sample_below(rng, Scalar::<Secp256k1>::group_order());
         let c_hat = HashCommitment::<Sha256>::create_commitment_with_user_defined_randomness(
             &BigInt::from_bytes(q_hat.to_bytes(true).as_ref()),
             &blindness,
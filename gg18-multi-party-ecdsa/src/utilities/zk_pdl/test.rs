@@ -7,6 +7,7 @@ use paillier::core::Randomness;
 use paillier::traits::{EncryptWithChosenRandomness, KeyGeneration};
 use paillier::Paillier;
 use paillier::RawPlaintext;
+use rand::rngs::OsRng;
 
 use crate::utilities::zk_pdl::{PDLStatement, PDLWitness, Prover, Verifier};
 
@@ -41,9 +42,9 @@ fn test_zk_pdl() {
         dk,
     };
     //
-    let (verifier_message1, mut verifier_state) = Verifier::message1(&statement);
+    let (verifier_message1, mut verifier_state) = Verifier::message1(&mut OsRng, &statement);
     let (prover_message1, prover_state) =
-        Prover::message1(&witness, &statement, &verifier_message1);
+        Prover::message1(&mut OsRng, &witness, &statement, &verifier_message1);
     let verifier_message2 =
         Verifier::message2(&prover_message1, &statement, &mut verifier_state).expect("");
     let prover_message2 = Prover::message2(
@@ -21,16 +21,23 @@
 //! Statement: (c, pk, Q, G)
 //! witness (x, r) such that Q = xG, c = Enc(pk, x, r)
 //! note that because of the range proof, the proof has a slack in the range: x in [-q^3, q^3]
+//!
+//! Fiat-Shamir challenge absorbs [ProofContext], so the proof is valid for the single session
+//! and prover only.
 
 use curv::arithmetic::traits::*;
 use curv::cryptographic_primitives::hashing::{Digest, DigestExt};
 use curv::elliptic::curves::{secp256_k1::Secp256k1, Point, Scalar};
 use curv::BigInt;
 use paillier::EncryptionKey;
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use thiserror::Error;
 
+use crate::common::transcript::ProofContext;
+use crate::utilities::sampling::{sample_below, sample_range};
+
 #[derive(Error, Debug)]
 pub enum ZkPdlWithSlackError {
     #[error("zk pdl with slack verification failed")]
@@ -65,16 +72,21 @@ pub struct PDLwSlackProof {
 }
 
 impl PDLwSlackProof {
-    pub fn prove(witness: &PDLwSlackWitness, statement: &PDLwSlackStatement) -> Self {
+    pub fn prove<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ctx: &ProofContext,
+        witness: &PDLwSlackWitness,
+        statement: &PDLwSlackStatement,
+    ) -> Self {
         let q3 = Scalar::<Secp256k1>::group_order().pow(3);
         let q_N_tilde = Scalar::<Secp256k1>::group_order() * &statement.N_tilde;
         let q3_N_tilde = &q3 * &statement.N_tilde;
 
-        let alpha = BigInt::sample_below(&q3);
+        let alpha = sample_below(rng, &q3);
         let one = BigInt::one();
-        let beta = BigInt::sample_range(&one, &(&statement.ek.n - &one));
-        let rho = BigInt::sample_below(&q_N_tilde);
-        let gamma = BigInt::sample_below(&q3_N_tilde);
+        let beta = sample_range(rng, &one, &(&statement.ek.n - &one));
+        let rho = sample_below(rng, &q_N_tilde);
+        let gamma = sample_below(rng, &q3_N_tilde);
 
         let z = commitment_unknown_order(
             &statement.h1,
@@ -99,7 +111,8 @@ impl PDLwSlackProof {
             &gamma,
         );
 
-        let e = Sha256::new()
+        let e = ctx
+            .chain(Sha256::new())
             .chain_bigint(&BigInt::from_bytes(statement.G.to_bytes(true).as_ref()))
             .chain_bigint(&BigInt::from_bytes(statement.Q.to_bytes(true).as_ref()))
             .chain_bigint(&statement.ciphertext)
@@ -124,8 +137,13 @@ impl PDLwSlackProof {
         }
     }
 
-    pub fn verify(&self, statement: &PDLwSlackStatement) -> Result<(), ZkPdlWithSlackError> {
-        let e = Sha256::new()
+    pub fn verify(
+        &self,
+        ctx: &ProofContext,
+        statement: &PDLwSlackStatement,
+    ) -> Result<(), ZkPdlWithSlackError> {
+        let e = ctx
+            .chain(Sha256::new())
             .chain_bigint(&BigInt::from_bytes(statement.G.to_bytes(true).as_ref()))
             .chain_bigint(&BigInt::from_bytes(statement.Q.to_bytes(true).as_ref()))
             .chain_bigint(&statement.ciphertext)
@@ -6,8 +6,11 @@ use paillier::core::Randomness;
 use paillier::traits::{EncryptWithChosenRandomness, KeyGeneration};
 use paillier::Paillier;
 use paillier::RawPlaintext;
+use rand::rngs::OsRng;
 use zk_paillier::zkproofs::{CompositeDLogProof, DLogStatement};
 
+use crate::common::transcript::ProofContext;
+
 #[test]
 fn test_zk_pdl_with_slack() {
     //  N_tilde, h1, h2 generation
@@ -59,12 +62,23 @@ fn test_zk_pdl_with_slack() {
 
     let pdl_w_slack_witness = PDLwSlackWitness { x, r: randomness.0 };
 
-    let proof = PDLwSlackProof::prove(&pdl_w_slack_witness, &pdl_w_slack_statement);
+    let ctx = ProofContext::new([1; 32], 1);
+    let proof = PDLwSlackProof::prove(
+        &mut OsRng,
+        &ctx,
+        &pdl_w_slack_witness,
+        &pdl_w_slack_statement,
+    );
     // verify h1,h2, N_tilde
     let setup_result = composite_dlog_proof.verify(&statement);
     assert!(setup_result.is_ok());
-    let result = proof.verify(&pdl_w_slack_statement);
+    let result = proof.verify(&ctx, &pdl_w_slack_statement);
     assert!(result.is_ok());
+    // proof made for another session or another prover is rejected
+    let result = proof.verify(&ProofContext::new([2; 32], 1), &pdl_w_slack_statement);
+    assert!(result.is_err());
+    let result = proof.verify(&ctx.of_party(2), &pdl_w_slack_statement);
+    assert!(result.is_err());
 }
 
 #[test]
@@ -120,10 +134,16 @@ fn test_zk_pdl_with_slack_soundness() {
 
     let pdl_w_slack_witness = PDLwSlackWitness { x, r: randomness.0 };
 
-    let proof = PDLwSlackProof::prove(&pdl_w_slack_witness, &pdl_w_slack_statement);
+    let ctx = ProofContext::new([1; 32], 1);
+    let proof = PDLwSlackProof::prove(
+        &mut OsRng,
+        &ctx,
+        &pdl_w_slack_witness,
+        &pdl_w_slack_statement,
+    );
     // verify h1,h2, N_tilde
     let setup_result = composite_dlog_proof.verify(&statement);
     assert!(setup_result.is_ok());
-    let result = proof.verify(&pdl_w_slack_statement);
+    let result = proof.verify(&ctx, &pdl_w_slack_statement);
     assert!(result.is_ok());
 }
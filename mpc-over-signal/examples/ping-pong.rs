@@ -73,7 +73,10 @@ async fn main() -> Result<()> {
     let mut client = SignalClient::new()?
         .start_listening_for_incoming_messages(secrets.clone())
         .await?;
-    let (incoming, outgoing) = client.join_computation(me.addr, group).await?;
+    let computation_id = group.computation_id(b"ping-pong");
+    let (incoming, outgoing) = client
+        .join_computation(me.addr, computation_id, group)
+        .await?;
 
     let ping_pong: Pin<Box<dyn Future<Output = Result<()>>>> = if args.ping {
         Box::pin(ping(me_ind, other_ind, incoming, outgoing))
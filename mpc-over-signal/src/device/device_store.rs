@@ -1,16 +1,20 @@
+use std::ops::{Deref, DerefMut};
 use std::path::Path;
 use std::sync::Arc;
 use std::vec::Vec;
 use anyhow::{Context, Result};
 
 use tokio::io::AsyncWriteExt;
-use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::{watch, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use crate::Device;
 
 #[derive(Clone)]
 pub struct DeviceStore {
     secrets: Arc<RwLock<Device>>,
+    changes_tx: Arc<watch::Sender<u64>>,
+    // keeps the channel open, so sending a change never fails
+    changes_rx: watch::Receiver<u64>,
 }
 
 impl DeviceStore {
@@ -18,14 +22,21 @@ impl DeviceStore {
         let secrets = Self::device_read_vec(file_content)
             .await
             .context("cannot read device secrets from file")?;
-        Ok(Self {
-            secrets: Arc::new(RwLock::new(secrets)),
-        })
+        Ok(Self::new(secrets))
+    }
+
+    /// Serializes device secrets into the format accepted by [from_byte_vec](Self::from_byte_vec)
+    pub async fn to_byte_vec(&self) -> Result<Vec<u8>> {
+        let secrets = self.secrets.read().await;
+        serde_json::to_vec_pretty(&*secrets).context("serialize")
     }
 
     pub fn new(secrets: Device) -> Self {
+        let (changes_tx, changes_rx) = watch::channel(0);
         Self {
             secrets: Arc::new(RwLock::new(secrets)),
+            changes_tx: Arc::new(changes_tx),
+            changes_rx,
         }
     }
 
@@ -33,8 +44,23 @@ impl DeviceStore {
         self.secrets.read().await
     }
 
-    pub async fn write(&self) -> RwLockWriteGuard<'_, Device> {
-        self.secrets.write().await
+    /// Locks device for writing, subscribers are notified once the guard is dropped
+    pub async fn write(&self) -> DeviceWriteGuard<'_> {
+        DeviceWriteGuard {
+            device: self.secrets.write().await,
+            changes_tx: &self.changes_tx,
+            changes_rx: &self.changes_rx,
+        }
+    }
+
+    /// Subscribes to changes of the device
+    ///
+    /// Receiver is notified every time write access to the device is released (sessions are
+    /// ratcheted, parties are trusted, pre-keys are used up), so that updated secrets can be
+    /// persisted. Value is the number of changes made so far, intermediate notifications may be
+    /// skipped if receiver lags behind.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.changes_rx.clone()
     }
 
     pub async fn save(&mut self, path: impl AsRef<Path>) -> Result<()> {
@@ -48,7 +74,7 @@ impl DeviceStore {
     }
     
     pub async fn reload(&mut self, path: impl AsRef<Path>) -> Result<()> {
-        let mut secrets = self.secrets.write().await;
+        let mut secrets = self.write().await;
         *secrets = Self::device_read(path).await?;
         Ok(())
     }
@@ -93,3 +119,31 @@ impl DeviceStore {
         Ok(())
     }
 }
+
+/// Write access to [Device] obtained from [DeviceStore::write]
+pub struct DeviceWriteGuard<'a> {
+    device: RwLockWriteGuard<'a, Device>,
+    changes_tx: &'a watch::Sender<u64>,
+    changes_rx: &'a watch::Receiver<u64>,
+}
+
+impl Deref for DeviceWriteGuard<'_> {
+    type Target = Device;
+    fn deref(&self) -> &Device {
+        &self.device
+    }
+}
+
+impl DerefMut for DeviceWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Device {
+        &mut self.device
+    }
+}
+
+impl Drop for DeviceWriteGuard<'_> {
+    fn drop(&mut self) {
+        // the lock is still held here, so concurrent writers can't skip a version
+        let version = *self.changes_rx.borrow() + 1;
+        let _ = self.changes_tx.send(version);
+    }
+}
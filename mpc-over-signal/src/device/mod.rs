@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::{anyhow, bail};
 use serde::{Deserialize, Serialize};
@@ -20,11 +20,11 @@ mod keys;
 mod stores;
 
 pub use creds::{DeviceAuth, DeviceCreds, Username};
-pub use device_store::DeviceStore;
+pub use device_store::{DeviceStore, DeviceWriteGuard};
 pub use keys::{DeviceKeys, PreKey, SignedPreKey};
 
 use crate::helpers::serde as serde_helpers;
-use crate::{Group, ParticipantIdentity};
+use crate::{ComputationID, Group, ParticipantIdentity};
 
 #[derive(Serialize, Deserialize)]
 pub struct Device {
@@ -35,6 +35,9 @@ pub struct Device {
         with = "serde_helpers::hash_map::UsingWrappers::<serde_helpers::protocol_address::Wrapper, serde_helpers::identity_key::Wrapper>"
     )]
     trusted_parties: HashMap<ProtocolAddress, IdentityKey>,
+    /// Computations this device has taken part in, see [Device::join_computation]
+    #[serde(default)]
+    joined_computations: HashSet<ComputationID>,
 }
 
 impl Device {
@@ -43,6 +46,7 @@ impl Device {
             creds,
             keys,
             trusted_parties: Default::default(),
+            joined_computations: Default::default(),
         }
     }
 
@@ -72,6 +76,20 @@ impl Device {
         Ok(())
     }
 
+    /// Records that the device takes part in computation `id`, fails if it already did
+    ///
+    /// Computation id must never repeat: proofs are bound to it, and messages left over from
+    /// an earlier computation with the same id would be mixed into the new one.
+    pub fn join_computation(&mut self, id: ComputationID) -> anyhow::Result<()> {
+        if !self.joined_computations.insert(id) {
+            bail!(
+                "computation {} was already carried out by this device",
+                hex::encode(id)
+            );
+        }
+        Ok(())
+    }
+
     pub fn is_known_party(&self, address: &ProtocolAddress) -> bool {
         self.trusted_parties.contains_key(address)
     }
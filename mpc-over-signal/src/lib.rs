@@ -3,6 +3,7 @@
 use std::convert::{TryFrom, TryInto};
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 mod actors;
 mod device;
@@ -14,9 +15,13 @@ mod proto {
     include!(concat!(env!("OUT_DIR"), "/signalservice.rs"));
 }
 
-pub use device::{Device, DeviceStore};
+pub use actors::ComputationID;
+pub use device::{Device, DeviceStore, DeviceWriteGuard};
 use libsignal_protocol::ProtocolAddress;
-pub use signal_client::{SignalClient, SignalClientBuilder, SignalClientConnected};
+pub use signal_client::{
+    ComputationAborted, SignalClient, SignalClientBuilder, SignalClientConnected,
+    SIGNAL_SERVER_CERTIFICATE,
+};
 
 #[derive(Clone, Debug)]
 pub struct Group {
@@ -54,6 +59,27 @@ impl Group {
     pub fn parties(&self) -> impl Iterator<Item = &ParticipantIdentity> {
         self.participants.iter()
     }
+
+    /// Derives computation id from `label` and identities of the group members
+    ///
+    /// Every party of the group obtains the same id for the same `label`. Label must be unique
+    /// for every computation carried out by the group (e.g. include message being signed),
+    /// otherwise messages of concurrent computations get mixed up.
+    pub fn computation_id(&self, label: &[u8]) -> ComputationID {
+        let mut hasher = Sha256::new()
+            .chain(b"mpc-over-signal-computation-id")
+            .chain(&(label.len() as u64).to_be_bytes())
+            .chain(label);
+        for party in &self.participants {
+            let name = party.addr.name().as_bytes();
+            hasher = hasher
+                .chain(&(name.len() as u64).to_be_bytes())
+                .chain(name)
+                .chain(&party.addr.device_id().to_be_bytes())
+                .chain(party.public_key.serialize());
+        }
+        hasher.finalize().into()
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
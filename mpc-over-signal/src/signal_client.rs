@@ -8,7 +8,8 @@ use std::{
 
 use anyhow::{anyhow, bail, Context, Result};
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use actix::Actor;
 use actix_rt::task::JoinHandle;
@@ -30,6 +31,23 @@ use crate::{actors, proto, Device, Group};
 
 static MPC_MESSAGE_TAG: &[u8] = b"MPC_OVER_SIGNAL_MESSAGE:";
 
+/// PEM-encoded root certificate of original Signal Server
+pub const SIGNAL_SERVER_CERTIFICATE: &str = include_str!("../signal-server.pem");
+
+/// Error yielded by incoming stream of a computation once another party aborted it, see
+/// [SignalClientConnected::abort_computation]
+#[derive(Debug, Error)]
+#[error("party {party} aborted the computation")]
+pub struct ComputationAborted {
+    pub party: u16,
+}
+
+/// Sent in place of a protocol message by party leaving the computation
+#[derive(Serialize, Deserialize)]
+struct AbortNotice {
+    aborted_by: u16,
+}
+
 pub struct SignalClient {
     webapi_client: WebAPIClient,
 }
@@ -114,7 +132,7 @@ impl SignalClientBuilder {
     fn default_http_client() -> Result<awc::Client> {
         let mut root_certs = rustls::RootCertStore::empty();
         root_certs
-            .add_pem_file(&mut &include_bytes!("../signal-server.pem")[..])
+            .add_pem_file(&mut SIGNAL_SERVER_CERTIFICATE.as_bytes())
             .map_err(|()| anyhow!("read root ca"))?;
 
         let mut tls_config = rustls::ClientConfig::new();
@@ -147,6 +165,7 @@ impl SignalClientConnected {
     pub async fn join_computation<T>(
         &mut self,
         me: ProtocolAddress,
+        computation_id: actors::ComputationID,
         group: Group,
     ) -> Result<(
         impl Stream<Item = Result<Msg<T>>> + Unpin,
@@ -155,8 +174,6 @@ impl SignalClientConnected {
     where
         T: Serialize + DeserializeOwned,
     {
-        let computation_id = [0; 32]; // TODO
-
         let parties = group.parties_count();
         let me_ind = group
             .party_index(&me)
@@ -178,6 +195,12 @@ impl SignalClientConnected {
         let (incoming, outgoing) = self.join(computation_id, group).await?;
         let incoming = incoming.and_then(move |(sender, plaintext)| {
             Box::pin(async move {
+                if let Ok(notice) = serde_json::from_slice::<AbortNotice>(&plaintext) {
+                    if notice.aborted_by != sender {
+                        bail!("sender tried to forge sender index")
+                    }
+                    return Err(ComputationAborted { party: sender }.into());
+                }
                 let msg: Msg<T> = serde_json::from_slice(&plaintext)
                     .context("failed to deserialize a message")?;
                 if msg.receiver.is_some() && msg.receiver != Some(me_ind) {
@@ -219,6 +242,55 @@ impl SignalClientConnected {
         Ok((incoming, outgoing))
     }
 
+    /// Tells other parties of the group that this party left the computation
+    ///
+    /// Their incoming streams yield [ComputationAborted] error. Resolves once notices are sent out
+    /// to every party (or sending failed), so the client can be dropped right after.
+    pub async fn abort_computation(
+        &self,
+        me: &ProtocolAddress,
+        computation_id: actors::ComputationID,
+        group: &Group,
+    ) -> Result<()> {
+        let me_ind = group
+            .party_index(me)
+            .context("group doesn't include this party")?;
+        let mut notice = computation_id.to_vec();
+        serde_json::to_writer(&mut notice, &AbortNotice { aborted_by: me_ind })
+            .context("serialize abort notice")?;
+        let notice: Box<[u8]> = notice.into_boxed_slice();
+
+        let others: Vec<_> = group
+            .parties()
+            .filter(|p| &p.addr != me)
+            .map(|p| p.addr.clone())
+            .collect();
+        // every permit is released once the notice holding it is sent out
+        let sent = Arc::new(Semaphore::new(others.len()));
+        let mut outgoing = self.outgoing_tx.clone();
+        for addr in &others {
+            let permit = sent
+                .clone()
+                .acquire_owned()
+                .await
+                .context("semaphore has been closed")?;
+            outgoing
+                .send((addr.clone(), notice.clone(), permit))
+                .await
+                .context("send to outgoing channel")?;
+        }
+        let mut permits = Vec::with_capacity(others.len());
+        for _ in &others {
+            permits.push(
+                sent.clone()
+                    .acquire_owned()
+                    .await
+                    .context("semaphore has been closed")?,
+            );
+        }
+        Ok(())
+    }
+
     async fn join(
         &self,
         computation_id: actors::ComputationID,